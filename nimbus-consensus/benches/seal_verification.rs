@@ -0,0 +1,84 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compares verifying the seals of a chunk of initial sync blocks one after the other, as the
+//! verifier does, with verifying them on several threads.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use nimbus_consensus::{verify_seals, SealVerification};
+use nimbus_primitives::{verify_seal, CompatibleDigestItem, NimbusPair};
+use sp_core::Pair;
+use sp_runtime::{generic::DigestItem, testing::Header, traits::Header as HeaderT};
+
+/// The number of blocks in a chunk the sync requests from a peer.
+const CHUNK_SIZE: u64 = 64;
+
+fn sealed_headers(count: u64) -> Vec<Header> {
+	let author = NimbusPair::from_seed(&[1; 32]);
+	(1..=count)
+		.map(|number| {
+			let mut header = Header::new(
+				number,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			);
+			header
+				.digest_mut()
+				.push(DigestItem::nimbus_pre_digest(author.public()));
+			let signature = author.sign(header.hash().as_ref());
+			header.digest_mut().push(DigestItem::nimbus_seal(signature));
+			header
+		})
+		.collect()
+}
+
+fn seal_verification(c: &mut Criterion) {
+	let threads = match SealVerification::parallel() {
+		SealVerification::Parallel { threads } => threads,
+		SealVerification::Sequential => 1,
+	};
+	let mut group = c.benchmark_group("seal_verification");
+
+	for chunks in [1, 4] {
+		let headers = sealed_headers(chunks * CHUNK_SIZE);
+		assert!(headers.iter().all(|header| verify_seal(header).is_ok()));
+
+		group.bench_with_input(
+			BenchmarkId::new("sequential", headers.len()),
+			&headers,
+			|b, headers| b.iter(|| headers.iter().map(verify_seal).collect::<Vec<_>>()),
+		);
+		group.bench_with_input(
+			BenchmarkId::new(format!("parallel_{}_threads", threads), headers.len()),
+			&headers,
+			|b, headers| {
+				b.iter(|| {
+					headers
+						.chunks(CHUNK_SIZE as usize)
+						.flat_map(|chunk| verify_seals(chunk, threads))
+						.collect::<Vec<_>>()
+				})
+			},
+		);
+	}
+
+	group.finish();
+}
+
+criterion_group!(benches, seal_verification);
+criterion_main!(benches);
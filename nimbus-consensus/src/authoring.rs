@@ -0,0 +1,140 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! How much time and space the worker gives the proposer for each block.
+//!
+//! Chains differ widely here: heavy EVM blocks may need more than the default 500ms to be built,
+//! while light chains may want more headroom in the PoV. An `AuthoringConfig` fixes the budget
+//! for the whole node, and an `AuthoringBudgetProvider` can compute it per block instead.
+
+use cumulus_primitives_core::PersistedValidationData;
+use sp_runtime::{traits::Block as BlockT, Percent};
+use std::time::Duration;
+
+/// How long the proposer may spend building a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposalDuration {
+	/// The same duration for every block.
+	Fixed(Duration),
+	/// A share of the relay chain slot.
+	RelativeToRelaySlot {
+		/// The duration of a relay chain slot, e.g. 6 seconds on Polkadot and Kusama.
+		relay_slot_duration: Duration,
+		/// The share of that slot the proposer may use.
+		fraction: Percent,
+	},
+}
+
+impl ProposalDuration {
+	/// The proposal duration this setting resolves to.
+	pub fn duration(&self) -> Duration {
+		match self {
+			Self::Fixed(duration) => *duration,
+			Self::RelativeToRelaySlot {
+				relay_slot_duration,
+				fraction,
+			} => Duration::from_millis(fraction.mul_floor(relay_slot_duration.as_millis() as u64)),
+		}
+	}
+}
+
+/// The node-wide authoring settings of the nimbus worker.
+///
+/// The default matches the historical behavior: 500ms to propose, and half of the maximum
+/// PoV size for the block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthoringConfig {
+	/// How long the proposer may spend building a block.
+	pub proposal_duration: ProposalDuration,
+	/// The share of the relay chain's maximum PoV size that a block may use.
+	pub pov_fraction: Percent,
+	/// An absolute limit on the block size in bytes, applied on top of `pov_fraction`.
+	pub max_block_size: Option<usize>,
+}
+
+impl Default for AuthoringConfig {
+	fn default() -> Self {
+		Self {
+			proposal_duration: ProposalDuration::Fixed(Duration::from_millis(500)),
+			pov_fraction: Percent::from_percent(50),
+			max_block_size: None,
+		}
+	}
+}
+
+impl AuthoringConfig {
+	/// The budget for a block built against the given validation data.
+	pub fn budget(&self, validation_data: &PersistedValidationData) -> AuthoringBudget {
+		let pov_limit = self.pov_fraction.mul_floor(validation_data.max_pov_size) as usize;
+		let block_size_limit = match self.max_block_size {
+			Some(max_block_size) => pov_limit.min(max_block_size),
+			None => pov_limit,
+		};
+
+		AuthoringBudget {
+			proposal_duration: self.proposal_duration.duration(),
+			block_size_limit: Some(block_size_limit),
+		}
+	}
+}
+
+/// The resources the proposer may use for a single block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthoringBudget {
+	/// How long the proposer may spend building the block.
+	pub proposal_duration: Duration,
+	/// The maximum size of the block in bytes, or `None` for no limit.
+	pub block_size_limit: Option<usize>,
+}
+
+/// Computes the authoring budget of each block.
+pub trait AuthoringBudgetProvider<B: BlockT> {
+	/// The budget for a block built on top of `parent`.
+	fn authoring_budget(
+		&self,
+		config: &AuthoringConfig,
+		parent: &B::Header,
+		validation_data: &PersistedValidationData,
+	) -> AuthoringBudget;
+}
+
+/// Every block gets the budget of the authoring config.
+impl<B: BlockT> AuthoringBudgetProvider<B> for () {
+	fn authoring_budget(
+		&self,
+		config: &AuthoringConfig,
+		_parent: &B::Header,
+		validation_data: &PersistedValidationData,
+	) -> AuthoringBudget {
+		config.budget(validation_data)
+	}
+}
+
+/// A closure computes the budget from the parent header and validation data, ignoring the
+/// authoring config.
+impl<B: BlockT, F> AuthoringBudgetProvider<B> for F
+where
+	F: Fn(&B::Header, &PersistedValidationData) -> AuthoringBudget,
+{
+	fn authoring_budget(
+		&self,
+		_config: &AuthoringConfig,
+		parent: &B::Header,
+		validation_data: &PersistedValidationData,
+	) -> AuthoringBudget {
+		(*self)(parent, validation_data)
+	}
+}
@@ -0,0 +1,233 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A stand-in remote signer for tests.
+//!
+//! It holds nimbus keys derived from the given secret URIs and answers the requests of
+//! `nimbus_consensus::RemoteSigner` over a Unix socket or HTTP. Once it listens, it prints
+//! `listening on <endpoint>` on stdout. Do not use it to hold real keys.
+//!
+//! Usage:
+//!   nimbus-test-signer (--unix <path> | --http <ip>:<port>)
+//!     [--sr25519 <suri>]... [--ed25519 <suri>]... [--ecdsa <suri>]... [--drop-first <n>]
+//!
+//! `--drop-first` closes the first `n` connections without answering, to exercise retries.
+
+use codec::{Decode, Encode};
+use nimbus_consensus::{SignerEndpoint, SignerRequest, SignerResponse};
+use nimbus_primitives::{
+	multi_signer::{NimbusEcdsaId, NimbusEcdsaSignature, NimbusEd25519Id, NimbusEd25519Signature},
+	MultiNimbusId, MultiNimbusSignature, NimbusId, NimbusSignature,
+};
+use sp_core::{ecdsa, ed25519, sr25519, Pair};
+use std::{
+	io::{BufRead, BufReader, Read, Write},
+	net::TcpListener,
+	os::unix::net::UnixListener,
+	process,
+};
+
+enum Key {
+	Sr25519(sr25519::Pair),
+	Ed25519(ed25519::Pair),
+	Ecdsa(ecdsa::Pair),
+}
+
+impl Key {
+	fn public(&self) -> MultiNimbusId {
+		match self {
+			Key::Sr25519(pair) => NimbusId::from(pair.public()).into(),
+			Key::Ed25519(pair) => NimbusEd25519Id::from(pair.public()).into(),
+			Key::Ecdsa(pair) => NimbusEcdsaId::from(pair.public()).into(),
+		}
+	}
+
+	fn sign(&self, message: &[u8]) -> MultiNimbusSignature {
+		match self {
+			Key::Sr25519(pair) => NimbusSignature::from(pair.sign(message)).into(),
+			Key::Ed25519(pair) => NimbusEd25519Signature::from(pair.sign(message)).into(),
+			Key::Ecdsa(pair) => NimbusEcdsaSignature::from(pair.sign(message)).into(),
+		}
+	}
+}
+
+struct Signer {
+	keys: Vec<Key>,
+	drop_first: usize,
+	connections: usize,
+}
+
+impl Signer {
+	/// Whether the current connection should be closed without answering.
+	fn drop_connection(&mut self) -> bool {
+		self.connections += 1;
+		self.connections <= self.drop_first
+	}
+
+	fn handle(&self, request: &[u8]) -> SignerResponse {
+		let request: SignerRequest = match serde_json::from_slice(request) {
+			Ok(request) => request,
+			Err(e) => return SignerResponse::Error(format!("invalid request: {}", e)),
+		};
+
+		match request {
+			SignerRequest::PublicKeys => SignerResponse::PublicKeys(
+				self.keys
+					.iter()
+					.map(|key| key.public().encode().into())
+					.collect(),
+			),
+			SignerRequest::Sign { public, message } => {
+				let public = match MultiNimbusId::decode(&mut &public[..]) {
+					Ok(public) => public,
+					Err(e) => return SignerResponse::Error(format!("invalid key: {}", e)),
+				};
+
+				match self.keys.iter().find(|key| key.public() == public) {
+					Some(key) => SignerResponse::Signature(key.sign(&message).encode().into()),
+					None => SignerResponse::UnknownKey,
+				}
+			}
+		}
+	}
+
+	fn serve_unix(mut self, listener: UnixListener) {
+		for stream in listener.incoming() {
+			let Ok(stream) = stream else { continue };
+			if self.drop_connection() {
+				continue;
+			}
+
+			let mut reader = BufReader::new(&stream);
+			let mut request = Vec::new();
+			if reader.read_until(b'\n', &mut request).is_err() {
+				continue;
+			}
+
+			let mut response = serde_json::to_vec(&self.handle(&request))
+				.expect("Signer responses can always be serialized");
+			response.push(b'\n');
+			let _ = (&stream).write_all(&response);
+		}
+	}
+
+	fn serve_http(mut self, listener: TcpListener) {
+		for stream in listener.incoming() {
+			let Ok(stream) = stream else { continue };
+			if self.drop_connection() {
+				continue;
+			}
+
+			let mut reader = BufReader::new(&stream);
+			let mut content_length = 0;
+			loop {
+				let mut line = String::new();
+				if reader.read_line(&mut line).unwrap_or(0) == 0 {
+					break;
+				}
+				let line = line.trim();
+				if line.is_empty() {
+					break;
+				}
+				if let Some((name, value)) = line.split_once(':') {
+					if name.eq_ignore_ascii_case("content-length") {
+						content_length = value.trim().parse().unwrap_or(0);
+					}
+				}
+			}
+
+			let mut request = vec![0; content_length];
+			if reader.read_exact(&mut request).is_err() {
+				continue;
+			}
+
+			let response = serde_json::to_vec(&self.handle(&request))
+				.expect("Signer responses can always be serialized");
+			let _ = write!(
+				&stream,
+				"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+				Connection: close\r\n\r\n",
+				response.len()
+			)
+			.and_then(|_| (&stream).write_all(&response));
+		}
+	}
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("nimbus-test-signer: {}", message);
+	process::exit(1)
+}
+
+fn main() {
+	let mut endpoint = None;
+	let mut signer = Signer {
+		keys: Vec::new(),
+		drop_first: 0,
+		connections: 0,
+	};
+
+	let mut args = std::env::args().skip(1);
+	while let Some(flag) = args.next() {
+		let value = args
+			.next()
+			.unwrap_or_else(|| fail(&format!("missing value for {}", flag)));
+		match flag.as_str() {
+			"--unix" => endpoint = Some(SignerEndpoint::Unix(value.into())),
+			"--http" => {
+				let address = value
+					.parse()
+					.unwrap_or_else(|e| fail(&format!("invalid address {}: {}", value, e)));
+				endpoint = Some(SignerEndpoint::Http(address));
+			}
+			"--sr25519" => signer.keys.push(Key::Sr25519(
+				sr25519::Pair::from_string(&value, None)
+					.unwrap_or_else(|_| fail(&format!("invalid secret URI {}", value))),
+			)),
+			"--ed25519" => signer.keys.push(Key::Ed25519(
+				ed25519::Pair::from_string(&value, None)
+					.unwrap_or_else(|_| fail(&format!("invalid secret URI {}", value))),
+			)),
+			"--ecdsa" => signer.keys.push(Key::Ecdsa(
+				ecdsa::Pair::from_string(&value, None)
+					.unwrap_or_else(|_| fail(&format!("invalid secret URI {}", value))),
+			)),
+			"--drop-first" => {
+				signer.drop_first = value
+					.parse()
+					.unwrap_or_else(|_| fail(&format!("invalid count {}", value)));
+			}
+			_ => fail(&format!("unknown flag {}", flag)),
+		}
+	}
+
+	match endpoint {
+		Some(SignerEndpoint::Unix(path)) => {
+			let listener = UnixListener::bind(&path)
+				.unwrap_or_else(|e| fail(&format!("cannot listen on {}: {}", path.display(), e)));
+			println!("listening on {}", SignerEndpoint::Unix(path));
+			signer.serve_unix(listener);
+		}
+		Some(SignerEndpoint::Http(address)) => {
+			let listener = TcpListener::bind(address)
+				.unwrap_or_else(|e| fail(&format!("cannot listen on {}: {}", address, e)));
+			let address = listener.local_addr().expect("The listener is bound");
+			println!("listening on {}", SignerEndpoint::Http(address));
+			signer.serve_http(listener);
+		}
+		None => fail("either --unix or --http is required"),
+	}
+}
@@ -0,0 +1,213 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Client-side detection of authors that seal more than one block in the same slot.
+//!
+//! The verifier records every (author, slot) pair it sees in aux storage, together with the
+//! sealed header. When a second header from the same author in the same slot conflicts with one
+//! of them, the two headers make an `EquivocationProof`. Headers that build on each other do
+//! not conflict, because the runtime may allow several blocks per slot. Old slots are pruned so
+//! storage stays bounded.
+
+use codec::{Decode, Encode};
+use nimbus_primitives::{headers_conflict, EquivocationProof, MultiNimbusId};
+use sc_client_api::backend::AuxStore;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_runtime::traits::Header as HeaderT;
+
+/// The prefix of the keys under which the headers seen in each slot are stored.
+const SLOT_HEADER_MAP_KEY: &[u8] = b"nimbus_slot_header_map";
+/// The key of the first slot that is still stored.
+const SLOT_HEADER_START: &[u8] = b"nimbus_slot_header_start";
+
+/// The number of most recent slots for which headers are kept.
+pub const MAX_SLOT_CAPACITY: u32 = 1000;
+/// Headers are pruned once this many slots have been stored.
+pub const PRUNING_BOUND: u32 = 2 * MAX_SLOT_CAPACITY;
+
+pub(crate) fn load_decode<C: AuxStore, T: Decode>(
+	backend: &C,
+	key: &[u8],
+) -> ClientResult<Option<T>> {
+	match backend.get_aux(key)? {
+		None => Ok(None),
+		Some(t) => T::decode(&mut &t[..])
+			.map_err(|e| {
+				ClientError::Backend(format!("Nimbus DB is corrupted. Decode error: {}", e))
+			})
+			.map(Some),
+	}
+}
+
+fn slot_header_key(slot: u32) -> Vec<u8> {
+	let mut key = SLOT_HEADER_MAP_KEY.to_vec();
+	slot.using_encoded(|s| key.extend(s));
+	key
+}
+
+/// Record that `author` sealed `header` in `slot`, and return a proof if it had already sealed a
+/// conflicting header in the same slot.
+///
+/// `header` must still carry its seal so that the proof can be checked by others.
+/// Headers from slots older than what is currently stored are ignored.
+pub fn check_equivocation<C, H>(
+	backend: &C,
+	slot: u32,
+	header: &H,
+	author: &MultiNimbusId,
+) -> ClientResult<Option<EquivocationProof<H>>>
+where
+	C: AuxStore,
+	H: HeaderT,
+{
+	let slot_key = slot_header_key(slot);
+	let mut headers_with_author =
+		load_decode::<_, Vec<(H, MultiNimbusId)>>(backend, &slot_key)?.unwrap_or_default();
+
+	let first_saved_slot = load_decode::<_, u32>(backend, SLOT_HEADER_START)?.unwrap_or(slot);
+	if slot < first_saved_slot {
+		return Ok(None);
+	}
+
+	let mut previous_headers = headers_with_author
+		.iter()
+		.filter(|(_, previous_author)| previous_author == author)
+		.map(|(previous_header, _)| previous_header);
+
+	// We have already seen this exact header, eg. because it was re-imported.
+	if previous_headers
+		.clone()
+		.any(|previous_header| previous_header.hash() == header.hash())
+	{
+		return Ok(None);
+	}
+
+	if let Some(previous_header) =
+		previous_headers.find(|previous_header| headers_conflict(*previous_header, header))
+	{
+		return Ok(Some(EquivocationProof {
+			offender: author.clone(),
+			slot,
+			first_header: previous_header.clone(),
+			second_header: header.clone(),
+		}));
+	}
+
+	// Prune old slots, a bounded number at a time.
+	let mut keys_to_delete = Vec::new();
+	let mut new_first_saved_slot = first_saved_slot;
+	if slot - first_saved_slot >= PRUNING_BOUND {
+		new_first_saved_slot = slot.saturating_sub(MAX_SLOT_CAPACITY);
+		let prune_until = new_first_saved_slot.min(first_saved_slot.saturating_add(PRUNING_BOUND));
+		keys_to_delete.extend((first_saved_slot..prune_until).map(slot_header_key));
+		new_first_saved_slot = prune_until;
+	}
+
+	headers_with_author.push((header.clone(), author.clone()));
+
+	backend.insert_aux(
+		&[
+			(&slot_key[..], headers_with_author.encode().as_slice()),
+			(SLOT_HEADER_START, new_first_saved_slot.encode().as_slice()),
+		],
+		&keys_to_delete
+			.iter()
+			.map(|key| &key[..])
+			.collect::<Vec<_>>()[..],
+	)?;
+
+	Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::slashing_protection::tests::MemoryAuxStore;
+	use nimbus_primitives::NimbusId;
+	use sp_core::{sr25519, H256};
+	use sp_runtime::testing::Header;
+
+	fn author() -> MultiNimbusId {
+		NimbusId::from(sr25519::Public::from_raw([1; 32])).into()
+	}
+
+	fn header(number: u64, parent_hash: H256) -> Header {
+		Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			Default::default(),
+		)
+	}
+
+	#[test]
+	fn conflicting_headers_in_the_same_slot_are_an_equivocation() {
+		let backend = MemoryAuxStore::default();
+		let first = header(1, H256::repeat_byte(1));
+		let second = header(1, H256::repeat_byte(2));
+
+		assert_eq!(
+			check_equivocation(&backend, 7, &first, &author()).unwrap(),
+			None
+		);
+		let proof = check_equivocation(&backend, 7, &second, &author())
+			.unwrap()
+			.expect("Both headers are at height 1");
+		assert_eq!(proof.first_header, first);
+		assert_eq!(proof.second_header, second);
+	}
+
+	#[test]
+	fn a_parent_and_its_child_in_the_same_slot_are_not_an_equivocation() {
+		let backend = MemoryAuxStore::default();
+		let parent = header(1, H256::repeat_byte(1));
+		let child = header(2, parent.hash());
+
+		assert_eq!(
+			check_equivocation(&backend, 7, &parent, &author()).unwrap(),
+			None
+		);
+		assert_eq!(
+			check_equivocation(&backend, 7, &child, &author()).unwrap(),
+			None
+		);
+
+		// A sibling of the child still conflicts with it.
+		let sibling = Header {
+			state_root: H256::repeat_byte(3),
+			..child.clone()
+		};
+		assert!(check_equivocation(&backend, 7, &sibling, &author())
+			.unwrap()
+			.is_some());
+	}
+
+	#[test]
+	fn reimported_headers_are_not_an_equivocation() {
+		let backend = MemoryAuxStore::default();
+		let first = header(1, H256::repeat_byte(1));
+
+		assert_eq!(
+			check_equivocation(&backend, 7, &first, &author()).unwrap(),
+			None
+		);
+		assert_eq!(
+			check_equivocation(&backend, 7, &first, &author()).unwrap(),
+			None
+		);
+	}
+}
@@ -0,0 +1,341 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fork choice for nimbus chains that do not follow a relay chain.
+//!
+//! Parachains import their best block from the relay chain, but standalone and manual seal
+//! chains must choose between forks themselves, for example when two eligible authors author in
+//! the same slot. Without a rule, the verifier applies the longest chain rule, and which of two
+//! blocks at the same height is best depends on which one arrived first. `SlotForkChoice`
+//! prefers the fork that filled more slots, then the fork whose last author has the higher
+//! priority in the slot filter, and only reorganizes to a strictly better fork. Every node
+//! therefore settles on the same block.
+//!
+//! Only slots checked by the runtime are trusted: otherwise an author could report a fresh slot
+//! in each of its blocks to make its fork look like it filled more slots. Blocks whose slot is not
+//! checked count as filling a slot of their own, as under the longest chain rule.
+
+use codec::{Decode, Encode};
+use nimbus_primitives::{claimed_author, MultiNimbusId, NimbusApi};
+use sc_client_api::backend::AuxStore;
+use sc_consensus::{BlockImportParams, ForkChoiceStrategy};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::error::Error as ConsensusError;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, UniqueSaturatedInto};
+use std::{cmp::Reverse, sync::Arc};
+
+/// The prefix of the keys under which the weight of each block is stored.
+const FORK_WEIGHT_KEY: &[u8] = b"nimbus_fork_weight";
+
+fn fork_weight_key<H: Encode>(hash: H) -> Vec<u8> {
+	let mut key = FORK_WEIGHT_KEY.to_vec();
+	hash.using_encoded(|h| key.extend(h));
+	key
+}
+
+/// Decides whether the blocks imported outside of the parachain context become the best block.
+pub trait ForkChoiceRule<Block: BlockT> {
+	/// Set the fork choice of the block being imported, whose seal is already stripped, and add
+	/// what the rule needs to remember about it to the auxiliary data. The longest chain rule
+	/// set by the verifier is kept when no fork choice is set.
+	fn fork_choice<Transaction>(
+		&self,
+		params: &mut BlockImportParams<Block, Transaction>,
+	) -> Result<(), ConsensusError>;
+}
+
+/// Keep the longest chain rule.
+impl<Block: BlockT> ForkChoiceRule<Block> for () {
+	fn fork_choice<Transaction>(
+		&self,
+		_params: &mut BlockImportParams<Block, Transaction>,
+	) -> Result<(), ConsensusError> {
+		Ok(())
+	}
+}
+
+/// How `SlotForkChoice` compares the fork ending in a block with the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct ForkWeight {
+	/// The number of slots in which the fork has at least one block
+	pub filled_slots: u64,
+	/// The position of the author of the last block among the eligible authors of its slot,
+	/// starting at 0 for the highest priority. `u32::MAX` when it is not known.
+	pub priority: u32,
+}
+
+impl ForkWeight {
+	/// The weight assumed for a block imported before the rule was used: each block is counted as
+	/// filling its own slot, and the priority of its author is not known.
+	fn assumed(number: u64) -> Self {
+		Self {
+			filled_slots: number,
+			priority: u32::MAX,
+		}
+	}
+
+	/// The weight of a child of the block with this weight. Blocks whose slot is not known to be
+	/// checked by the runtime are counted as filling a slot of their own.
+	fn child(&self, parent_slot: Option<u32>, slot: Option<u32>, priority: u32) -> Self {
+		let fills_new_slot = match (parent_slot, slot) {
+			(Some(parent_slot), Some(slot)) => slot != parent_slot,
+			_ => true,
+		};
+
+		Self {
+			filled_slots: self.filled_slots + u64::from(fills_new_slot),
+			priority,
+		}
+	}
+
+	/// Whether the fork with this weight should be preferred over the fork with `other`.
+	pub fn is_better_than(&self, other: &Self) -> bool {
+		(self.filled_slots, Reverse(self.priority)) > (other.filled_slots, Reverse(other.priority))
+	}
+}
+
+/// Whether a block with `weight` becomes the best block. Extending the best block is not a
+/// reorganization, so it always does. Otherwise the block must be strictly better than the best
+/// block, so that ties keep the current best block.
+fn becomes_best(weight: &ForkWeight, extends_best: bool, best_weight: &ForkWeight) -> bool {
+	extends_best || weight.is_better_than(best_weight)
+}
+
+/// A fork choice rule based on the slots filled by each fork.
+///
+/// The fork that filled more slots is preferred. Between forks that filled as many slots, the
+/// fork whose last author comes first in `NimbusApi::eligible_authors` for its slot is preferred.
+/// The weight of each block is stored in aux storage. Runtimes without `eligible_authors` give
+/// every author the same priority.
+pub struct SlotForkChoice<Client> {
+	client: Arc<Client>,
+}
+
+impl<Client> SlotForkChoice<Client> {
+	/// Create a new instance.
+	pub fn new(client: Arc<Client>) -> Self {
+		Self { client }
+	}
+}
+
+impl<Client> Clone for SlotForkChoice<Client> {
+	fn clone(&self) -> Self {
+		Self {
+			client: self.client.clone(),
+		}
+	}
+}
+
+impl<Client: AuxStore> SlotForkChoice<Client> {
+	/// The stored weight of a block, or the weight assumed for it if none is stored.
+	fn weight<Block: BlockT>(
+		&self,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+	) -> Result<ForkWeight, ConsensusError> {
+		let stored = crate::equivocation::load_decode(&*self.client, &fork_weight_key(hash))
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+		Ok(stored.unwrap_or_else(|| ForkWeight::assumed(number.unique_saturated_into())))
+	}
+}
+
+/// The slot reported by the pre-runtime digest of `header`, if the runtime that executes it
+/// rejects blocks that report another slot than the slot beacon. Runtimes say so through
+/// version 4 of `NimbusApi`.
+fn checked_slot<Block, Client>(client: &Client, header: &Block::Header) -> Option<u32>
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: NimbusApi<Block>,
+{
+	let parent_hash = *header.parent_hash();
+	let runtime_api = client.runtime_api();
+	let has_slot_check = runtime_api
+		.api_version::<dyn NimbusApi<Block>>(parent_hash)
+		.ok()
+		.flatten()
+		.map_or(false, |version| version >= 4);
+	if !has_slot_check {
+		return None;
+	}
+
+	match runtime_api.checks_pre_digest_slot(parent_hash) {
+		Ok(true) => crate::slot_of(header),
+		_ => None,
+	}
+}
+
+/// The position of the author of `header` among the eligible authors of its slot, according to
+/// the runtime at its parent, or `u32::MAX` when it is not known.
+fn priority<Block, Client>(client: &Client, parent: &Block::Header, header: &Block::Header) -> u32
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: NimbusApi<Block>,
+{
+	let (slot, author) = match (crate::slot_of(header), claimed_author(header.digest())) {
+		(Some(slot), Ok(author)) => (slot, author),
+		_ => return u32::MAX,
+	};
+
+	let runtime_api = client.runtime_api();
+	let has_eligible_authors = runtime_api
+		.api_version::<dyn NimbusApi<Block>>(parent.hash())
+		.ok()
+		.flatten()
+		.map_or(false, |version| version >= 2);
+	if !has_eligible_authors {
+		return u32::MAX;
+	}
+
+	runtime_api
+		.eligible_authors(parent.hash(), slot, parent)
+		.ok()
+		.and_then(|eligible| {
+			eligible
+				.into_iter()
+				.position(|id| MultiNimbusId::from(id) == author)
+		})
+		.map_or(u32::MAX, |position| position as u32)
+}
+
+impl<Block, Client> ForkChoiceRule<Block> for SlotForkChoice<Client>
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore,
+	Client::Api: NimbusApi<Block>,
+{
+	fn fork_choice<Transaction>(
+		&self,
+		params: &mut BlockImportParams<Block, Transaction>,
+	) -> Result<(), ConsensusError> {
+		let parent_hash = *params.header.parent_hash();
+		let parent = match self
+			.client
+			.header(parent_hash)
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+		{
+			Some(parent) => parent,
+			// The import fails anyway
+			None => return Ok(()),
+		};
+
+		// The import fails if the runtime rejects the slot of the block being imported.
+		let weight = self.weight::<Block>(parent_hash, *parent.number())?.child(
+			checked_slot::<Block, Client>(&*self.client, &parent),
+			checked_slot::<Block, Client>(&*self.client, &params.header),
+			priority::<Block, Client>(&*self.client, &parent, &params.header),
+		);
+
+		let info = self.client.info();
+		let best_weight = self.weight::<Block>(info.best_hash, info.best_number)?;
+		let is_best = becomes_best(&weight, parent_hash == info.best_hash, &best_weight);
+
+		let key = fork_weight_key(params.post_hash());
+		params.auxiliary.push((key, Some(weight.encode())));
+		params.fork_choice = Some(ForkChoiceStrategy::Custom(is_best));
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::{header, header_in_slot, Block, MockClient};
+	use sp_core::H256;
+
+	fn weight(filled_slots: u64, priority: u32) -> ForkWeight {
+		ForkWeight {
+			filled_slots,
+			priority,
+		}
+	}
+
+	#[test]
+	fn children_in_new_slots_fill_one_more_slot() {
+		let parent = weight(5, 0);
+
+		assert_eq!(parent.child(Some(10), Some(11), 2), weight(6, 2));
+		assert_eq!(parent.child(Some(10), Some(10), 2), weight(5, 2));
+		assert_eq!(parent.child(None, Some(10), 2), weight(6, 2));
+		assert_eq!(parent.child(Some(10), None, 2), weight(6, 2));
+	}
+
+	#[test]
+	fn more_filled_slots_win_over_priority() {
+		assert!(weight(6, 3).is_better_than(&weight(5, 0)));
+		assert!(!weight(5, 0).is_better_than(&weight(6, 3)));
+	}
+
+	#[test]
+	fn priority_breaks_ties() {
+		assert!(weight(5, 0).is_better_than(&weight(5, 1)));
+		assert!(weight(5, 1).is_better_than(&weight(5, u32::MAX)));
+		assert!(!weight(5, 1).is_better_than(&weight(5, 0)));
+	}
+
+	#[test]
+	fn equal_forks_do_not_reorganize() {
+		assert!(!becomes_best(&weight(5, 1), false, &weight(5, 1)));
+		assert!(becomes_best(&weight(5, 0), false, &weight(5, 1)));
+	}
+
+	#[test]
+	fn extending_the_best_block_always_wins() {
+		// A second block in the same slot, by a lower priority author
+		assert!(becomes_best(&weight(5, 3), true, &weight(5, 0)));
+	}
+
+	#[test]
+	fn blocks_without_weights_count_one_slot_each() {
+		let assumed = ForkWeight::assumed(7);
+
+		assert_eq!(assumed, weight(7, u32::MAX));
+		assert!(assumed
+			.child(None, Some(3), 0)
+			.is_better_than(&ForkWeight::assumed(7)));
+	}
+
+	#[test]
+	fn slots_checked_by_the_runtime_are_trusted() {
+		let client = MockClient::default();
+
+		assert_eq!(
+			checked_slot::<Block, _>(&client, &header_in_slot(2, H256::repeat_byte(1), 10)),
+			Some(10)
+		);
+		assert_eq!(
+			checked_slot::<Block, _>(&client, &header(2, H256::repeat_byte(1))),
+			None
+		);
+	}
+
+	#[test]
+	fn slots_not_checked_by_the_runtime_are_not_trusted() {
+		let client = MockClient {
+			unchecked_slots: true,
+			..Default::default()
+		};
+
+		assert_eq!(
+			checked_slot::<Block, _>(&client, &header_in_slot(2, H256::repeat_byte(1), 10)),
+			None
+		);
+	}
+}
@@ -14,48 +14,293 @@
 // You should have received a copy of the GNU General Public License
 // along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{marker::PhantomData, sync::Arc};
+use std::{fmt, marker::PhantomData, sync::Arc};
 
-use log::debug;
-use nimbus_primitives::{digests::CompatibleDigestItem, NimbusId, NimbusPair, NIMBUS_ENGINE_ID};
+use crate::fork_choice::ForkChoiceRule;
+use crate::metrics::{ImportQueueMetrics, RejectionReason};
+use crate::peer_reporting::{
+	NimbusImportQueue, ReputationSink, SharedBlockOrigins, SharedVerifiedSeals,
+};
+use crate::seal_verification::SealVerification;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use log::{debug, warn};
+use nimbus_primitives::{
+	claimed_author, strip_and_verify_seal, strip_seal, EquivocationProof, MultiNimbusId, NimbusApi,
+	SealError,
+};
+use sc_client_api::backend::AuxStore;
 use sc_consensus::{
-	import_queue::{BasicQueue, Verifier as VerifierT},
+	import_queue::{BasicQueue, RuntimeOrigin, Verifier as VerifierT},
 	BlockImport, BlockImportParams,
 };
-use sp_api::ProvideRuntimeApi;
-use sp_application_crypto::{ByteArray, Pair as _};
+use sc_network::ReputationChange;
+use sp_api::{ProvideRuntimeApi, RuntimeVersion};
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
-use sp_blockchain::Result as ClientResult;
+use sp_blockchain::{HeaderBackend, Result as ClientResult};
 use sp_consensus::error::Error as ConsensusError;
 use sp_inherents::{CreateInherentDataProviders, InherentDataProvider};
-use sp_runtime::{
-	traits::{Block as BlockT, Header as HeaderT},
-	DigestItem,
-};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use sp_runtime::ConsensusEngineId;
+
+/// The reputation changes of the peers that send blocks which fail verification.
+mod rep {
+	use sc_network::ReputationChange as Rep;
+
+	/// The block is not sealed, or is sealed by another consensus engine.
+	pub const UNSEALED_BLOCK: Rep = Rep::new(-(1 << 29), "Nimbus: unsealed block");
+	/// The block does not report its author properly.
+	pub const MALFORMED_AUTHOR: Rep = Rep::new(-(1 << 29), "Nimbus: malformed author digest");
+	/// The seal is not a valid signature by the reported author, so the block is forged.
+	pub const BAD_SIGNATURE: Rep = Rep::new_fatal("Nimbus: invalid seal signature");
+	/// The author may not author the block.
+	pub const INELIGIBLE_AUTHOR: Rep = Rep::new(-(1 << 20), "Nimbus: ineligible author");
+	/// The inherents are invalid. This may be caused by our own clock, so the cost is small.
+	pub const INHERENT_FAILURE: Rep = Rep::new(-(1 << 12), "Nimbus: invalid inherents");
+}
+
+/// Why the verifier rejected a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NimbusVerificationError {
+	/// The header is not sealed
+	MissingSeal,
+	/// The header is sealed by another consensus engine
+	ForeignSeal(ConsensusEngineId),
+	/// No digest reports the author of the block
+	MissingAuthor,
+	/// More than one digest reports the author of the block
+	DuplicateAuthor,
+	/// The reported author is not a valid `NimbusId`
+	MalformedAuthorId,
+	/// The seal is not a valid signature by the reported author
+	BadSignature,
+	/// The runtime at the parent block does not consider the author eligible
+	IneligibleAuthor(MultiNimbusId),
+	/// The inherents of the block could not be checked, or are invalid
+	InherentFailure(String),
+}
+
+impl NimbusVerificationError {
+	/// The reputation change of the peer that sent the block.
+	pub fn reputation_change(&self) -> ReputationChange {
+		match self {
+			Self::MissingSeal | Self::ForeignSeal(_) => rep::UNSEALED_BLOCK,
+			Self::MissingAuthor | Self::DuplicateAuthor | Self::MalformedAuthorId => {
+				rep::MALFORMED_AUTHOR
+			}
+			Self::BadSignature => rep::BAD_SIGNATURE,
+			Self::IneligibleAuthor(_) => rep::INELIGIBLE_AUTHOR,
+			Self::InherentFailure(_) => rep::INHERENT_FAILURE,
+		}
+	}
+}
+
+impl fmt::Display for NimbusVerificationError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::MissingSeal => write!(f, "the block is not sealed"),
+			Self::ForeignSeal(id) => write!(
+				f,
+				"the block is sealed by another consensus engine ({})",
+				String::from_utf8_lossy(id)
+			),
+			Self::MissingAuthor => write!(f, "the block does not report its author"),
+			Self::DuplicateAuthor => write!(f, "the block reports its author more than once"),
+			Self::MalformedAuthorId => write!(f, "the block's author is not a valid nimbus id"),
+			Self::BadSignature => write!(f, "the seal is not a valid signature by the author"),
+			Self::IneligibleAuthor(author) => {
+				write!(f, "author {:?} may not author the block", author)
+			}
+			Self::InherentFailure(e) => write!(f, "the inherents are invalid: {}", e),
+		}
+	}
+}
+
+impl From<SealError> for NimbusVerificationError {
+	fn from(e: SealError) -> Self {
+		match e {
+			SealError::NoDigests | SealError::HeaderUnsealed => Self::MissingSeal,
+			SealError::ForeignSeal(id) => Self::ForeignSeal(id),
+			SealError::MissingAuthor => Self::MissingAuthor,
+			SealError::DuplicateAuthor => Self::DuplicateAuthor,
+			SealError::InvalidAuthor => Self::MalformedAuthorId,
+			SealError::BadSignature => Self::BadSignature,
+		}
+	}
+}
 
 /// The Nimbus verifier strips the seal digest, and checks that it is a valid signature by
 /// the same key that was injected into the runtime and noted in the Seal digest.
 /// From Nimbu's perspective any block that faithfully reports its authorship to the runtime
 /// is valid. The intention is that the runtime itself may then put further restrictions on
 /// the identity of the author.
-struct Verifier<Client, Block, CIDP> {
+///
+/// The verifier also records the author and slot of each verified header, and reports authors
+/// that seal two conflicting headers in the same slot.
+///
+/// When `check_eligibility` is set, the verifier also asks the runtime whether the author may
+/// author in the block's slot before the block is executed, so that blocks from ineligible
+/// authors are dropped cheaply.
+///
+/// The peers that send blocks which fail verification lose reputation according to the
+/// `NimbusVerificationError`.
+///
+/// The seals that the import queue already verified in parallel are only stripped.
+struct Verifier<Client, Block: BlockT, CIDP> {
 	client: Arc<Client>,
 	create_inherent_data_providers: CIDP,
+	check_eligibility: bool,
+	equivocation_sink: Option<UnboundedSender<EquivocationProof<Block::Header>>>,
+	origins: SharedBlockOrigins<Block>,
+	verified_seals: SharedVerifiedSeals<Block>,
+	reputation_sink: ReputationSink,
+	metrics: Option<ImportQueueMetrics>,
 	_marker: PhantomData<Block>,
 }
 
-#[async_trait::async_trait]
-impl<Client, Block, CIDP> VerifierT<Block> for Verifier<Client, Block, CIDP>
+impl<Client, Block, CIDP> Verifier<Client, Block, CIDP>
+where
+	Block: BlockT,
+	Client: AuxStore,
+{
+	/// Count the rejection, and lower the reputation of the peer that sent the block, if known.
+	fn rejected(&self, origin: Option<RuntimeOrigin>, error: &NimbusVerificationError) {
+		if let Some(metrics) = &self.metrics {
+			metrics.rejected(RejectionReason::from(error));
+		}
+		if let Some(peer) = origin {
+			let _ = self
+				.reputation_sink
+				.unbounded_send((peer, error.reputation_change()));
+		}
+	}
+
+	/// Check whether the author of this sealed header has already sealed another header
+	/// in the same slot. Headers whose pre-runtime digest does not report the slot are not checked.
+	fn check_equivocation(&self, sealed_header: &Block::Header, author: &MultiNimbusId) {
+		let slot = match crate::slot_of(sealed_header) {
+			Some(slot) => slot,
+			None => return,
+		};
+
+		match crate::equivocation::check_equivocation(&*self.client, slot, sealed_header, author) {
+			Ok(Some(proof)) => {
+				warn!(
+					target: crate::LOG_TARGET,
+					"⚠️ Author {:?} equivocated in slot {}: {:?} and {:?}",
+					author,
+					slot,
+					proof.first_header.hash(),
+					proof.second_header.hash(),
+				);
+				if let Some(sink) = &self.equivocation_sink {
+					let _ = sink.unbounded_send(proof);
+				}
+			}
+			Ok(None) => {}
+			Err(e) => warn!(
+				target: crate::LOG_TARGET,
+				"Could not check for equivocation in slot {}: {:?}", slot, e
+			),
+		}
+	}
+}
+
+/// Whether `author` may author the block with `header`, according to the runtime at its parent.
+///
+/// Returns `None` when this cannot be known before execution: the header does not report its
+/// slot, the parent is unknown, the runtime was upgraded in the parent and has not run its
+/// migrations yet, or the runtime API fails. Such blocks are left for the runtime to check.
+fn eligible_before_execution<Block, Client>(
+	client: &Client,
+	header: &Block::Header,
+	author: &MultiNimbusId,
+	header_at: impl Fn(Block::Hash) -> Option<Block::Header>,
+	version_at: impl Fn(Block::Hash) -> Option<RuntimeVersion>,
+) -> Option<bool>
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block>,
+	Client::Api: NimbusApi<Block>,
+{
+	let slot = crate::slot_of(header)?;
+	let parent = header_at(*header.parent_hash())?;
+	if crate::runtime_upgraded::<Block>(&parent, version_at)? {
+		return None;
+	}
+
+	crate::can_author::<Block, Client>(client, author, &parent, slot)
+}
+
+impl<Client, Block, CIDP> Verifier<Client, Block, CIDP>
 where
 	Block: BlockT,
-	Client: ProvideRuntimeApi<Block> + Send + Sync,
-	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block>,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore + Send + Sync,
+	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block> + NimbusApi<Block>,
 	CIDP: CreateInherentDataProviders<Block, ()>,
 {
-	async fn verify(
+	/// Reject the block if the runtime at its parent says that `author` may not author it.
+	fn check_eligibility(
+		&self,
+		header: &Block::Header,
+		author: &MultiNimbusId,
+	) -> Result<(), NimbusVerificationError> {
+		let eligible = eligible_before_execution::<Block, Client>(
+			&*self.client,
+			header,
+			author,
+			|hash| self.client.header(hash).ok().flatten(),
+			|at| {
+				use sp_api::Core as _;
+				self.client.runtime_api().version(at).ok()
+			},
+		);
+		if eligible == Some(false) {
+			return Err(NimbusVerificationError::IneligibleAuthor(author.clone()));
+		}
+
+		Ok(())
+	}
+
+	// This part copied from RelayChainConsensus. I guess this is the inherent checking.
+	async fn check_inherents(&self, block: &Block) -> Result<(), String> {
+		let parent_hash = *block.header().parent_hash();
+		let inherent_data_providers = self
+			.create_inherent_data_providers
+			.create_inherent_data_providers(parent_hash, ())
+			.await
+			.map_err(|e| e.to_string())?;
+
+		let inherent_data = inherent_data_providers
+			.create_inherent_data()
+			.await
+			.map_err(|e| format!("{:?}", e))?;
+
+		let inherent_res = self
+			.client
+			.runtime_api()
+			.check_inherents(parent_hash, block.clone(), inherent_data)
+			.map_err(|e| format!("{:?}", e))?;
+
+		if !inherent_res.ok() {
+			for (i, e) in inherent_res.into_errors() {
+				match inherent_data_providers.try_handle_error(&i, &e).await {
+					Some(r) => r.map_err(|e| format!("{:?}", e))?,
+					None => Err(format!(
+						"Unhandled inherent error from `{}`.",
+						String::from_utf8_lossy(&i)
+					))?,
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn verify_block(
 		&mut self,
 		mut block_params: BlockImportParams<Block, ()>,
-	) -> Result<BlockImportParams<Block, ()>, String> {
+		seal_verified: bool,
+	) -> Result<BlockImportParams<Block, ()>, NimbusVerificationError> {
 		// Skip checks that include execution, if being told so or when importing only state.
 		//
 		// This is done for example when gap syncing and it is expected that the block after the gap
@@ -75,16 +320,13 @@ where
 			"🪲 Header hash before popping digest {:?}",
 			block_params.header.hash()
 		);
-		// Grab the seal digest. Assume it is last (since it is a seal after-all).
-		let seal = block_params
-			.header
-			.digest_mut()
-			.pop()
-			.ok_or("Block should have at least one digest on it")?;
-
-		let signature = seal
-			.as_nimbus_seal()
-			.ok_or_else(|| String::from("HeaderUnsealed"))?;
+		// Strip the seal and check that it is a valid signature by the claimed author, unless the
+		// import queue already did.
+		let (author, seal) = if seal_verified {
+			strip_seal(&mut block_params.header).map(|(author, seal, _)| (author, seal))?
+		} else {
+			strip_and_verify_seal(&mut block_params.header)?
+		};
 
 		debug!(
 			target: crate::LOG_TARGET,
@@ -94,85 +336,22 @@ where
 
 		debug!(
 			target: crate::LOG_TARGET,
-			"🪲 Signature according to verifier is {:?}", signature
+			"🪲 Valid seal by claimed author {:?}", author
 		);
 
-		// Grab the author information from either the preruntime digest or the consensus digest
-		//TODO use the trait
-		let claimed_author = block_params
-			.header
-			.digest()
-			.logs
-			.iter()
-			.find_map(|digest| match *digest {
-				DigestItem::Consensus(id, ref author_id) if id == NIMBUS_ENGINE_ID => {
-					Some(author_id.clone())
-				}
-				DigestItem::PreRuntime(id, ref author_id) if id == NIMBUS_ENGINE_ID => {
-					Some(author_id.clone())
-				}
-				_ => None,
-			})
-			.ok_or("Expected one consensus or pre-runtime digest that contains author id bytes")?;
-
-		debug!(
-			target: crate::LOG_TARGET,
-			"🪲 Claimed Author according to verifier is {:?}", claimed_author
-		);
-
-		// Verify the signature
-		let valid_signature = NimbusPair::verify(
-			&signature,
-			block_params.header.hash(),
-			&NimbusId::from_slice(&claimed_author)
-				.map_err(|_| "Invalid Nimbus ID (wrong length)")?,
-		);
-
-		debug!(
-			target: crate::LOG_TARGET,
-			"🪲 Valid signature? {:?}", valid_signature
-		);
-
-		if !valid_signature {
-			return Err("Block signature invalid".into());
+		if self.check_eligibility {
+			self.check_eligibility(&block_params.header, &author)?;
 		}
 
-		// This part copied from RelayChainConsensus. I guess this is the inherent checking.
-		if let Some(inner_body) = block_params.body.take() {
-			let inherent_data_providers = self
-				.create_inherent_data_providers
-				.create_inherent_data_providers(*block_params.header.parent_hash(), ())
-				.await
-				.map_err(|e| e.to_string())?;
-
-			let inherent_data = inherent_data_providers
-				.create_inherent_data()
-				.await
-				.map_err(|e| format!("{:?}", e))?;
+		let mut sealed_header = block_params.header.clone();
+		sealed_header.digest_mut().push(seal.clone());
+		self.check_equivocation(&sealed_header, &author);
 
+		if let Some(inner_body) = block_params.body.take() {
 			let block = Block::new(block_params.header.clone(), inner_body);
-
-			let inherent_res = self
-				.client
-				.runtime_api()
-				.check_inherents(
-					*block_params.header.parent_hash(),
-					block.clone(),
-					inherent_data,
-				)
-				.map_err(|e| format!("{:?}", e))?;
-
-			if !inherent_res.ok() {
-				for (i, e) in inherent_res.into_errors() {
-					match inherent_data_providers.try_handle_error(&i, &e).await {
-						Some(r) => r.map_err(|e| format!("{:?}", e))?,
-						None => Err(format!(
-							"Unhandled inherent error from `{}`.",
-							String::from_utf8_lossy(&i)
-						))?,
-					}
-				}
-			}
+			self.check_inherents(&block)
+				.await
+				.map_err(NimbusVerificationError::InherentFailure)?;
 
 			let (_, inner_body) = block.deconstruct();
 			block_params.body = Some(inner_body);
@@ -180,7 +359,8 @@ where
 
 		block_params.post_digests.push(seal);
 
-		// The standard is to use the longest chain rule. This is overridden by the `NimbusBlockImport` in the parachain context.
+		// The standard is to use the longest chain rule. This is overridden by the `NimbusBlockImport` in the parachain context,
+		// or by its fork choice rule.
 		block_params.fork_choice = Some(sc_consensus::ForkChoiceStrategy::LongestChain);
 
 		debug!(
@@ -193,34 +373,187 @@ where
 	}
 }
 
+#[async_trait::async_trait]
+impl<Client, Block, CIDP> VerifierT<Block> for Verifier<Client, Block, CIDP>
+where
+	Block: BlockT,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore + Send + Sync,
+	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block> + NimbusApi<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()>,
+{
+	async fn verify(
+		&mut self,
+		block_params: BlockImportParams<Block, ()>,
+	) -> Result<BlockImportParams<Block, ()>, String> {
+		// The hash of the sealed header, under which the import queue received the block
+		let hash = block_params.header.hash();
+		let origin = self.origins.lock().take(&hash);
+		let pending_seal = self.verified_seals.lock().take(&hash);
+		// Wait for the seal verifier thread, if it has the seal of this block.
+		let seal_verified = match pending_seal {
+			Some(result) => result.await.unwrap_or(false),
+			None => false,
+		};
+
+		self.verify_block(block_params, seal_verified)
+			.await
+			.map_err(|e| {
+				self.rejected(origin, &e);
+				format!("Nimbus verification failed: {}", e)
+			})
+	}
+}
+
 /// Start an import queue for a Cumulus collator that does not uses any special authoring logic.
-pub fn import_queue<Client, Block: BlockT, I, CIDP>(
+///
+/// With `check_eligibility`, blocks whose author the runtime does not consider eligible are
+/// rejected before they are executed.
+///
+/// With `SealVerification::Parallel`, the seals of initial sync blocks are verified on several
+/// threads, off the thread that queues the blocks, while they wait for the verifier.
+///
+/// Outside of the parachain context, `fork_choice` decides which blocks become the best block.
+/// Pass `()` to keep the longest chain rule, or see `SlotForkChoice`.
+pub fn import_queue<Client, Block: BlockT, I, CIDP, FC>(
+	client: Arc<Client>,
+	block_import: I,
+	create_inherent_data_providers: CIDP,
+	spawner: &impl sp_core::traits::SpawnEssentialNamed,
+	registry: Option<&substrate_prometheus_endpoint::Registry>,
+	parachain: bool,
+	check_eligibility: bool,
+	seal_verification: SealVerification,
+	fork_choice: FC,
+) -> ClientResult<NimbusImportQueue<Block, I::Transaction>>
+where
+	I: BlockImport<Block, Error = ConsensusError> + Send + Sync + 'static,
+	I::Transaction: Send + 'static,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore + Send + Sync + 'static,
+	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block> + NimbusApi<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()> + 'static,
+	FC: ForkChoiceRule<Block> + Send + Sync + 'static,
+{
+	build_import_queue(
+		client,
+		block_import,
+		create_inherent_data_providers,
+		spawner,
+		registry,
+		parachain,
+		check_eligibility,
+		seal_verification,
+		fork_choice,
+		None,
+	)
+}
+
+/// Start an import queue like `import_queue`, and also return a stream of the equivocations
+/// detected while verifying blocks. The proofs can be checked with
+/// `nimbus_primitives::check_equivocation_proof` and, for example, reported to the runtime.
+pub fn import_queue_with_equivocation_detection<Client, Block: BlockT, I, CIDP, FC>(
+	client: Arc<Client>,
+	block_import: I,
+	create_inherent_data_providers: CIDP,
+	spawner: &impl sp_core::traits::SpawnEssentialNamed,
+	registry: Option<&substrate_prometheus_endpoint::Registry>,
+	parachain: bool,
+	check_eligibility: bool,
+	seal_verification: SealVerification,
+	fork_choice: FC,
+) -> ClientResult<(
+	NimbusImportQueue<Block, I::Transaction>,
+	UnboundedReceiver<EquivocationProof<Block::Header>>,
+)>
+where
+	I: BlockImport<Block, Error = ConsensusError> + Send + Sync + 'static,
+	I::Transaction: Send + 'static,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore + Send + Sync + 'static,
+	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block> + NimbusApi<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()> + 'static,
+	FC: ForkChoiceRule<Block> + Send + Sync + 'static,
+{
+	let (sink, stream) = unbounded();
+	let queue = build_import_queue(
+		client,
+		block_import,
+		create_inherent_data_providers,
+		spawner,
+		registry,
+		parachain,
+		check_eligibility,
+		seal_verification,
+		fork_choice,
+		Some(sink),
+	)?;
+
+	Ok((queue, stream))
+}
+
+fn build_import_queue<Client, Block: BlockT, I, CIDP, FC>(
 	client: Arc<Client>,
 	block_import: I,
 	create_inherent_data_providers: CIDP,
 	spawner: &impl sp_core::traits::SpawnEssentialNamed,
 	registry: Option<&substrate_prometheus_endpoint::Registry>,
 	parachain: bool,
-) -> ClientResult<BasicQueue<Block, I::Transaction>>
+	check_eligibility: bool,
+	seal_verification: SealVerification,
+	fork_choice: FC,
+	equivocation_sink: Option<UnboundedSender<EquivocationProof<Block::Header>>>,
+) -> ClientResult<NimbusImportQueue<Block, I::Transaction>>
 where
 	I: BlockImport<Block, Error = ConsensusError> + Send + Sync + 'static,
-	I::Transaction: Send,
-	Client: ProvideRuntimeApi<Block> + Send + Sync + 'static,
-	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block>,
+	I::Transaction: Send + 'static,
+	Client: ProvideRuntimeApi<Block> + HeaderBackend<Block> + AuxStore + Send + Sync + 'static,
+	<Client as ProvideRuntimeApi<Block>>::Api: BlockBuilderApi<Block> + NimbusApi<Block>,
 	CIDP: CreateInherentDataProviders<Block, ()> + 'static,
+	FC: ForkChoiceRule<Block> + Send + Sync + 'static,
 {
+	let metrics = registry.and_then(|registry| {
+		ImportQueueMetrics::register(registry)
+			.map_err(|e| {
+				warn!(
+					target: crate::LOG_TARGET,
+					"Failed to register metrics: {:?}", e
+				)
+			})
+			.ok()
+	});
+
+	let origins = SharedBlockOrigins::default();
+	let verified_seals = SharedVerifiedSeals::default();
+	let (reputation_sink, reputation_changes) = unbounded();
 	let verifier = Verifier {
 		client,
 		create_inherent_data_providers,
+		check_eligibility,
+		origins: origins.clone(),
+		verified_seals: verified_seals.clone(),
+		reputation_sink,
+		equivocation_sink,
+		metrics: metrics.clone(),
 		_marker: PhantomData,
 	};
 
-	Ok(BasicQueue::new(
+	let queue = BasicQueue::new(
 		verifier,
-		Box::new(NimbusBlockImport::new(block_import, parachain)),
+		Box::new(NimbusBlockImport {
+			inner: block_import,
+			parachain_context: parachain,
+			fork_choice,
+			metrics,
+		}),
 		None,
 		spawner,
 		registry,
+	);
+
+	Ok(NimbusImportQueue::new(
+		queue,
+		origins,
+		seal_verification,
+		verified_seals,
+		reputation_changes,
 	))
 }
 
@@ -231,11 +564,14 @@ where
 /// handles this correctly, but does not work in non-parachain contexts.
 /// This block import has a field indicating whether we should apply parachain rules or not.
 ///
-/// There may be additional nimbus-specific logic here in the future, but for now it is
-/// only the conditional parachain logic
-pub struct NimbusBlockImport<I> {
+/// Outside of the parachain context, the fork choice rule decides which blocks become the best
+/// block. Wrap the block import given to a standalone or manual seal worker in a
+/// `NimbusBlockImport` with the same rule, so that its own blocks follow it too.
+pub struct NimbusBlockImport<I, FC = ()> {
 	inner: I,
 	parachain_context: bool,
+	fork_choice: FC,
+	metrics: Option<ImportQueueMetrics>,
 }
 
 impl<I> NimbusBlockImport<I> {
@@ -244,15 +580,31 @@ impl<I> NimbusBlockImport<I> {
 		Self {
 			inner,
 			parachain_context,
+			fork_choice: (),
+			metrics: None,
+		}
+	}
+}
+
+impl<I, FC> NimbusBlockImport<I, FC> {
+	/// Use `fork_choice` to choose the best block outside of the parachain context.
+	pub fn with_fork_choice<F>(self, fork_choice: F) -> NimbusBlockImport<I, F> {
+		NimbusBlockImport {
+			inner: self.inner,
+			parachain_context: self.parachain_context,
+			fork_choice,
+			metrics: self.metrics,
 		}
 	}
 }
 
 #[async_trait::async_trait]
-impl<Block, I> BlockImport<Block> for NimbusBlockImport<I>
+impl<Block, I, FC> BlockImport<Block> for NimbusBlockImport<I, FC>
 where
 	Block: BlockT,
 	I: BlockImport<Block> + Send,
+	I::Error: From<ConsensusError>,
+	FC: ForkChoiceRule<Block> + Send + Sync,
 {
 	type Error = I::Error;
 	type Transaction = I::Transaction;
@@ -274,9 +626,135 @@ where
 			block_import_params.fork_choice = Some(sc_consensus::ForkChoiceStrategy::Custom(
 				block_import_params.origin == sp_consensus::BlockOrigin::NetworkInitialSync,
 			));
+		} else {
+			self.fork_choice.fork_choice(&mut block_import_params)?;
 		}
 
+		let author = claimed_author(block_import_params.header.digest()).ok();
+
 		// Now continue on to the rest of the import pipeline.
-		self.inner.import_block(block_import_params).await
+		let result = self.inner.import_block(block_import_params).await;
+
+		if let (Some(metrics), Some(author), Ok(sc_consensus::ImportResult::Imported(_))) =
+			(&self.metrics, author, &result)
+		{
+			metrics.imported(&author);
+		}
+
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::{chain, header, header_in_slot, nimbus_key, Block, MockClient};
+	use sp_keystore::testing::MemoryKeystore;
+
+	#[test]
+	fn only_eligible_authors_pass_the_early_check() {
+		let keystore = MemoryKeystore::new();
+		let alice = nimbus_key(&keystore, "//Alice");
+		let bob = nimbus_key(&keystore, "//Bob");
+		let mut client = MockClient {
+			eligible: vec![alice.clone()],
+			..Default::default()
+		};
+		let headers = chain(&mut client, [1, 1, 1]);
+		let header_at = |hash| headers.iter().find(|header| header.hash() == hash).cloned();
+		let block = header_in_slot(3, headers[2].hash(), 10);
+
+		assert_eq!(
+			eligible_before_execution::<Block, MockClient>(
+				&client,
+				&block,
+				&alice.into(),
+				header_at,
+				|at| client.version_at(at),
+			),
+			Some(true)
+		);
+		assert_eq!(
+			eligible_before_execution::<Block, MockClient>(
+				&client,
+				&block,
+				&bob.into(),
+				header_at,
+				|at| client.version_at(at),
+			),
+			Some(false)
+		);
+	}
+
+	#[test]
+	fn seal_errors_become_verification_errors() {
+		assert_eq!(
+			NimbusVerificationError::from(SealError::HeaderUnsealed),
+			NimbusVerificationError::MissingSeal
+		);
+		assert_eq!(
+			NimbusVerificationError::from(SealError::ForeignSeal(*b"aura")),
+			NimbusVerificationError::ForeignSeal(*b"aura")
+		);
+		assert_eq!(
+			NimbusVerificationError::from(SealError::InvalidAuthor),
+			NimbusVerificationError::MalformedAuthorId
+		);
+	}
+
+	#[test]
+	fn forged_blocks_cost_the_most_reputation() {
+		let forged = NimbusVerificationError::BadSignature.reputation_change();
+		let unsealed = NimbusVerificationError::MissingSeal.reputation_change();
+		let inherents = NimbusVerificationError::InherentFailure(String::new()).reputation_change();
+
+		assert!(forged.value < unsealed.value);
+		assert!(unsealed.value < inherents.value);
+		assert!(inherents.value < 0);
+	}
+
+	#[test]
+	fn blocks_that_cannot_be_checked_early_are_left_to_the_runtime() {
+		let keystore = MemoryKeystore::new();
+		let bob: MultiNimbusId = nimbus_key(&keystore, "//Bob").into();
+		let mut client = MockClient::default();
+		let headers = chain(&mut client, [1, 1, 2]);
+		let header_at = |hash| headers.iter().find(|header| header.hash() == hash).cloned();
+		let check = |block| {
+			eligible_before_execution::<Block, MockClient>(&client, &block, &bob, header_at, |at| {
+				client.version_at(at)
+			})
+		};
+
+		// The runtime was upgraded in the parent.
+		assert_eq!(check(header_in_slot(3, headers[2].hash(), 10)), None);
+		// The header does not report its slot.
+		assert_eq!(check(header(2, headers[1].hash())), None);
+		// The parent is unknown.
+		assert_eq!(check(header_in_slot(3, Default::default(), 10)), None);
+	}
+
+	#[test]
+	fn blocks_are_left_to_the_runtime_when_the_api_fails() {
+		let keystore = MemoryKeystore::new();
+		let alice = nimbus_key(&keystore, "//Alice");
+		let mut client = MockClient {
+			eligible: vec![alice.clone()],
+			api_fails: true,
+			..Default::default()
+		};
+		let headers = chain(&mut client, [1, 1, 1]);
+		let header_at = |hash| headers.iter().find(|header| header.hash() == hash).cloned();
+
+		assert_eq!(
+			eligible_before_execution::<Block, MockClient>(
+				&client,
+				&header_in_slot(3, headers[2].hash(), 10),
+				&alice.into(),
+				header_at,
+				|at| client.version_at(at),
+			),
+			None
+		);
 	}
 }
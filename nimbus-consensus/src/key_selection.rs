@@ -0,0 +1,162 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Strategies to choose which key authors when a collator has several eligible keys.
+//!
+//! Collators may run several nimbus identities for redundancy. The worker first collects every
+//! key that may author in the slot, in a deterministic order, then asks a `KeySelector` to
+//! choose one of them.
+
+use nimbus_primitives::MultiNimbusId;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A key chosen by a `KeySelector`, along with why it was chosen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectedKey {
+	/// The key to author with
+	pub key: MultiNimbusId,
+	/// Why this key was chosen, for the logs
+	pub reason: String,
+}
+
+/// Chooses which key authors a block.
+pub trait KeySelector {
+	/// Chooses one of `candidates`, the keys that may author in `slot`, or `None` to not author.
+	/// `candidates` is never empty, and always comes in the same order.
+	fn select_key(&self, candidates: &[MultiNimbusId], slot: u32) -> Option<SelectedKey>;
+}
+
+/// Authors with the first candidate.
+impl KeySelector for () {
+	fn select_key(&self, candidates: &[MultiNimbusId], _slot: u32) -> Option<SelectedKey> {
+		candidates.first().map(|key| SelectedKey {
+			key: key.clone(),
+			reason: String::from("first eligible key"),
+		})
+	}
+}
+
+/// Takes turns between the candidates, one block each.
+#[derive(Default)]
+pub struct RoundRobin {
+	turn: AtomicUsize,
+}
+
+impl KeySelector for RoundRobin {
+	fn select_key(&self, candidates: &[MultiNimbusId], _slot: u32) -> Option<SelectedKey> {
+		if candidates.is_empty() {
+			return None;
+		}
+
+		let turn = self.turn.fetch_add(1, Ordering::Relaxed);
+		let index = turn % candidates.len();
+
+		Some(SelectedKey {
+			key: candidates[index].clone(),
+			reason: format!(
+				"round-robin turn {}, key {} of {}",
+				turn,
+				index + 1,
+				candidates.len()
+			),
+		})
+	}
+}
+
+/// Authors with the candidate that comes first in an explicit priority list.
+///
+/// Candidates that are not in the list are only used when none of the listed keys may author.
+pub struct PriorityList(pub Vec<MultiNimbusId>);
+
+impl KeySelector for PriorityList {
+	fn select_key(&self, candidates: &[MultiNimbusId], _slot: u32) -> Option<SelectedKey> {
+		let listed = self
+			.0
+			.iter()
+			.enumerate()
+			.find(|(_, key)| candidates.contains(key));
+
+		match listed {
+			Some((priority, key)) => Some(SelectedKey {
+				key: key.clone(),
+				reason: format!("priority {} of {} listed keys", priority + 1, self.0.len()),
+			}),
+			None => candidates.first().map(|key| SelectedKey {
+				key: key.clone(),
+				reason: String::from("no listed key is eligible, first eligible key"),
+			}),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nimbus_primitives::NimbusId;
+	use sp_core::{crypto::UncheckedFrom, sr25519};
+
+	fn key(byte: u8) -> MultiNimbusId {
+		NimbusId::from(sr25519::Public::unchecked_from([byte; 32])).into()
+	}
+
+	fn selected(
+		selector: &impl KeySelector,
+		candidates: &[MultiNimbusId],
+	) -> Option<MultiNimbusId> {
+		selector
+			.select_key(candidates, 0)
+			.map(|selected| selected.key)
+	}
+
+	#[test]
+	fn first_eligible_selects_first_candidate() {
+		assert_eq!(selected(&(), &[key(1), key(2)]), Some(key(1)));
+		assert_eq!(selected(&(), &[]), None);
+	}
+
+	#[test]
+	fn round_robin_takes_turns() {
+		let selector = RoundRobin::default();
+		let candidates = [key(1), key(2), key(3)];
+
+		let keys: Vec<_> = (0..4).map(|_| selected(&selector, &candidates)).collect();
+
+		assert_eq!(
+			keys,
+			vec![Some(key(1)), Some(key(2)), Some(key(3)), Some(key(1))]
+		);
+	}
+
+	#[test]
+	fn round_robin_handles_no_candidates() {
+		assert_eq!(selected(&RoundRobin::default(), &[]), None);
+	}
+
+	#[test]
+	fn priority_list_prefers_listed_keys() {
+		let selector = PriorityList(vec![key(3), key(2)]);
+
+		assert_eq!(selected(&selector, &[key(1), key(2), key(3)]), Some(key(3)));
+		assert_eq!(selected(&selector, &[key(1), key(2)]), Some(key(2)));
+	}
+
+	#[test]
+	fn priority_list_falls_back_to_unlisted_keys() {
+		let selector = PriorityList(vec![key(3)]);
+
+		assert_eq!(selected(&selector, &[key(1), key(2)]), Some(key(1)));
+	}
+}
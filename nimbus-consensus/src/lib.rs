@@ -24,37 +24,78 @@ use cumulus_client_consensus_common::{
 	ParachainBlockImport, ParachainCandidate, ParachainConsensus,
 };
 use cumulus_primitives_core::{relay_chain::Hash as PHash, ParaId, PersistedValidationData};
-pub use import_queue::import_queue;
+pub use fork_choice::{ForkChoiceRule, ForkWeight, SlotForkChoice};
+pub use import_queue::{
+	import_queue, import_queue_with_equivocation_detection, NimbusBlockImport,
+	NimbusVerificationError,
+};
 use log::{debug, info, warn};
 use nimbus_primitives::{
-	CompatibleDigestItem, DigestsProvider, NimbusApi, NimbusId, NIMBUS_KEY_ID,
+	multi_signer::{NimbusEcdsaId, NimbusEcdsaSignature, NimbusEd25519Id, NimbusEd25519Signature},
+	CompatibleDigestItem, DigestsProvider, MultiNimbusId, MultiNimbusSignature, NimbusApi,
+	NimbusId, NimbusPreDigest, NimbusSignature, NIMBUS_KEY_ID,
 };
 use parking_lot::Mutex;
 use sc_client_api::backend::Backend;
 use sc_consensus::{BlockImport, BlockImportParams};
-use sp_api::ProvideRuntimeApi;
-use sp_application_crypto::ByteArray;
+use sp_api::{ApiExt, ProvideRuntimeApi, RuntimeVersion};
+use sp_blockchain::HeaderBackend;
 use sp_consensus::{
 	BlockOrigin, EnableProofRecording, Environment, ProofRecording, Proposal, Proposer,
 };
-use sp_core::{crypto::CryptoTypeId, sr25519};
+use sp_core::{ecdsa, ed25519, sr25519};
 use sp_inherents::{CreateInherentDataProviders, InherentData, InherentDataProvider};
 use sp_keystore::{Keystore, KeystorePtr};
 use sp_runtime::{
-	traits::{Block as BlockT, Header as HeaderT},
+	traits::{Block as BlockT, Header as HeaderT, Zero},
 	DigestItem,
 };
-use std::convert::TryInto;
-use std::{marker::PhantomData, sync::Arc, time::Duration};
+use std::{marker::PhantomData, sync::Arc};
+use substrate_prometheus_endpoint::Registry;
 use tracing::error;
+mod authoring;
+pub mod equivocation;
+mod fork_choice;
 mod import_queue;
+mod key_selection;
 mod manual_seal;
+mod metrics;
+mod peer_reporting;
+mod remote_signer;
+mod seal_verification;
+pub mod slashing_protection;
+mod standalone;
+mod vrf;
+pub use authoring::{AuthoringBudget, AuthoringBudgetProvider, AuthoringConfig, ProposalDuration};
+pub use key_selection::{KeySelector, PriorityList, RoundRobin, SelectedKey};
 pub use manual_seal::NimbusManualSealConsensusDataProvider;
+use metrics::WorkerMetrics;
+pub use peer_reporting::{report_peers, NimbusImportQueue};
+pub use remote_signer::{
+	RemoteSigner, RemoteSignerConfig, RemoteSignerError, SignerEndpoint, SignerRequest,
+	SignerResponse,
+};
+pub use seal_verification::{verify_seals, SealVerification};
+pub use slashing_protection::{SlashingProtectionDb, SlashingProtectionError, SlashingProtector};
+pub use standalone::{start_nimbus_standalone, StandaloneNimbusParams};
+pub use vrf::{EligibilityProver, VrfEligibilityProver};
 
 const LOG_TARGET: &str = "filtering-consensus";
 
 /// The implementation of the relay-chain provided consensus for parachains.
-pub struct NimbusConsensus<B: BlockT, PF, BI, BE, ParaClient, CIDP, DP = ()> {
+pub struct NimbusConsensus<
+	B: BlockT,
+	PF,
+	BI,
+	BE,
+	ParaClient,
+	CIDP,
+	DP = (),
+	EP = (),
+	BP = (),
+	KS = (),
+	SP = (),
+> {
 	para_id: ParaId,
 	proposer_factory: Arc<Mutex<PF>>,
 	create_inherent_data_providers: Arc<CIDP>,
@@ -63,11 +104,17 @@ pub struct NimbusConsensus<B: BlockT, PF, BI, BE, ParaClient, CIDP, DP = ()> {
 	keystore: KeystorePtr,
 	skip_prediction: bool,
 	additional_digests_provider: Arc<DP>,
+	eligibility_prover: Arc<EP>,
+	authoring_config: AuthoringConfig,
+	authoring_budget_provider: Arc<BP>,
+	key_selector: Arc<KS>,
+	slashing_protection: Arc<SP>,
+	metrics: Option<WorkerMetrics>,
 	_phantom: PhantomData<B>,
 }
 
-impl<B: BlockT, PF, BI, BE, ParaClient, CIDP, DP> Clone
-	for NimbusConsensus<B, PF, BI, BE, ParaClient, CIDP, DP>
+impl<B: BlockT, PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP> Clone
+	for NimbusConsensus<B, PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP>
 {
 	fn clone(&self) -> Self {
 		Self {
@@ -79,20 +126,31 @@ impl<B: BlockT, PF, BI, BE, ParaClient, CIDP, DP> Clone
 			keystore: self.keystore.clone(),
 			skip_prediction: self.skip_prediction,
 			additional_digests_provider: self.additional_digests_provider.clone(),
+			eligibility_prover: self.eligibility_prover.clone(),
+			authoring_config: self.authoring_config,
+			authoring_budget_provider: self.authoring_budget_provider.clone(),
+			key_selector: self.key_selector.clone(),
+			slashing_protection: self.slashing_protection.clone(),
+			metrics: self.metrics.clone(),
 			_phantom: PhantomData,
 		}
 	}
 }
 
-impl<B, PF, BI, BE, ParaClient, CIDP, DP> NimbusConsensus<B, PF, BI, BE, ParaClient, CIDP, DP>
+impl<B, PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP>
+	NimbusConsensus<B, PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP>
 where
 	B: BlockT,
 	PF: 'static,
 	BI: 'static,
 	BE: Backend<B> + 'static,
 	ParaClient: ProvideRuntimeApi<B> + 'static,
-	CIDP: CreateInherentDataProviders<B, (PHash, PersistedValidationData, NimbusId)> + 'static,
-	DP: DigestsProvider<NimbusId, <B as BlockT>::Hash> + 'static,
+	CIDP: CreateInherentDataProviders<B, (PHash, PersistedValidationData, MultiNimbusId)> + 'static,
+	DP: DigestsProvider<MultiNimbusId, <B as BlockT>::Hash> + 'static,
+	EP: EligibilityProver<B> + 'static,
+	BP: AuthoringBudgetProvider<B> + 'static,
+	KS: KeySelector + 'static,
+	SP: SlashingProtector + 'static,
 {
 	/// Create a new instance of nimbus consensus.
 	pub fn build(
@@ -106,11 +164,23 @@ where
 			keystore,
 			skip_prediction,
 			additional_digests_provider,
-		}: BuildNimbusConsensusParams<PF, BI, BE, ParaClient, CIDP, DP>,
+			eligibility_prover,
+			authoring_config,
+			authoring_budget_provider,
+			key_selector,
+			slashing_protection,
+			prometheus_registry,
+		}: BuildNimbusConsensusParams<PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP>,
 	) -> Box<dyn ParachainConsensus<B>>
 	where
 		Self: ParachainConsensus<B>,
 	{
+		let metrics = prometheus_registry.as_ref().and_then(|registry| {
+			WorkerMetrics::register(registry)
+				.map_err(|e| warn!(target: LOG_TARGET, "Failed to register metrics: {:?}", e))
+				.ok()
+		});
+
 		Box::new(Self {
 			para_id,
 			proposer_factory: Arc::new(Mutex::new(proposer_factory)),
@@ -123,6 +193,12 @@ where
 			keystore,
 			skip_prediction,
 			additional_digests_provider: Arc::new(additional_digests_provider),
+			eligibility_prover: Arc::new(eligibility_prover),
+			authoring_config,
+			authoring_budget_provider: Arc::new(authoring_budget_provider),
+			key_selector: Arc::new(key_selector),
+			slashing_protection: Arc::new(slashing_protection),
+			metrics,
 			_phantom: PhantomData,
 		})
 	}
@@ -134,7 +210,7 @@ where
 		parent: B::Hash,
 		validation_data: &PersistedValidationData,
 		relay_parent: PHash,
-		author_id: NimbusId,
+		author_id: MultiNimbusId,
 	) -> Option<InherentData> {
 		let inherent_data_providers = self
 			.create_inherent_data_providers
@@ -166,46 +242,174 @@ where
 	}
 }
 
-/// Grabs any available nimbus key from the keystore.
-/// This may be useful in situations where you expect exactly one key
-/// and intend to perform an operation with it regardless of whether it is
+/// All the nimbus keys in the keystore, in every supported signature scheme.
+/// The keys are sorted, so that their order does not depend on the keystore: sr25519 keys come
+/// first, followed by ed25519 and ecdsa keys.
+pub(crate) fn nimbus_keys(keystore: &dyn Keystore) -> Vec<MultiNimbusId> {
+	let sr25519_keys = Keystore::sr25519_public_keys(keystore, NIMBUS_KEY_ID)
+		.into_iter()
+		.map(|key| NimbusId::from(key).into());
+	let ed25519_keys = Keystore::ed25519_public_keys(keystore, NIMBUS_KEY_ID)
+		.into_iter()
+		.map(|key| NimbusEd25519Id::from(key).into());
+	let ecdsa_keys = Keystore::ecdsa_public_keys(keystore, NIMBUS_KEY_ID)
+		.into_iter()
+		.map(|key| NimbusEcdsaId::from(key).into());
+
+	let mut keys: Vec<_> = sr25519_keys.chain(ed25519_keys).chain(ecdsa_keys).collect();
+	keys.sort();
+	keys
+}
+
+/// Let the key selector choose among the candidate keys, and log its choice.
+fn choose_key<KS: KeySelector>(
+	key_selector: &KS,
+	candidates: &[MultiNimbusId],
+	slot_number: u32,
+) -> Option<MultiNimbusId> {
+	let SelectedKey { key, reason } = key_selector.select_key(candidates, slot_number)?;
+	info!(
+		target: LOG_TARGET,
+		"🔑 Authoring in slot {} with {:?} ({})", slot_number, key, reason
+	);
+
+	Some(key)
+}
+
+/// Grabs one of the available nimbus keys, regardless of whether it is
 /// expected to be eligible. Concretely, this is used in the consensus worker
 /// to implement the `skip_prediction` feature.
-pub(crate) fn first_available_key(keystore: &dyn Keystore) -> Option<Vec<u8>> {
-	// Get all the available keys
-	match Keystore::keys(keystore, NIMBUS_KEY_ID) {
-		Ok(available_keys) => {
-			if available_keys.is_empty() {
-				warn!(
-					target: LOG_TARGET,
-					"🔏 No Nimbus keys available. We will not be able to author."
-				);
-				None
-			} else {
-				Some(available_keys[0].clone())
-			}
+pub(crate) fn available_key<KS: KeySelector>(
+	available_keys: Vec<MultiNimbusId>,
+	slot_number: u32,
+	key_selector: &KS,
+) -> Option<MultiNimbusId> {
+	if available_keys.is_empty() {
+		warn!(
+			target: LOG_TARGET,
+			"🔏 No Nimbus keys available. We will not be able to author."
+		);
+		return None;
+	}
+
+	choose_key(key_selector, &available_keys, slot_number)
+}
+
+/// Ask the runtime whether the given author can author in the given slot.
+/// Authors that do not use sr25519 can only be checked by runtimes with version 3 of `NimbusApi`.
+/// Returns `None` if the runtime API fails, for example because the parent's state was pruned.
+pub(crate) fn can_author<B: BlockT, C>(
+	client: &C,
+	author: &MultiNimbusId,
+	parent: &B::Header,
+	slot_number: u32,
+) -> Option<bool>
+where
+	C: ProvideRuntimeApi<B>,
+	C::Api: NimbusApi<B>,
+{
+	let runtime_api = client.runtime_api();
+
+	let eligible = match author.as_sr25519() {
+		Some(nimbus_id) => NimbusApi::can_author(
+			&*runtime_api,
+			parent.hash(),
+			nimbus_id.clone(),
+			slot_number,
+			parent,
+		),
+		None if !supports_multi_scheme::<B, C>(client, parent.hash()) => {
+			debug!(
+				target: LOG_TARGET,
+				"🔏 The runtime does not support non-sr25519 nimbus key {:?}", author
+			);
+			return Some(false);
+		}
+		None => NimbusApi::can_author_multi(
+			&*runtime_api,
+			parent.hash(),
+			author.clone(),
+			slot_number,
+			parent,
+		),
+	};
+
+	eligible
+		.map_err(|e| {
+			warn!(
+				target: LOG_TARGET,
+				"🔏 Could not ask the runtime at {:?} whether {:?} can author: {:?}",
+				parent.hash(),
+				author,
+				e
+			)
+		})
+		.ok()
+}
+
+/// Whether the runtime at `parent_hash` has version 3 of `NimbusApi`. Such runtimes accept authors
+/// of any signature scheme, and version 2 pre-runtime digests, which were introduced before it.
+pub(crate) fn supports_multi_scheme<B: BlockT, C>(client: &C, parent_hash: B::Hash) -> bool
+where
+	C: ProvideRuntimeApi<B>,
+	C::Api: NimbusApi<B>,
+{
+	client
+		.runtime_api()
+		.api_version::<dyn NimbusApi<B>>(parent_hash)
+		.ok()
+		.flatten()
+		.map_or(false, |version| version >= 3)
+}
+
+/// The pre-runtime digest announcing that `author` authored a block in the given slot, on top of
+/// `parent`. Older runtimes cannot decode version 2 digests, so they get a version 1 digest, which
+/// does not carry the slot and can only announce sr25519 authors. Returns `None` if the author
+/// cannot be announced to the runtime at all.
+pub(crate) fn pre_digest<B: BlockT, C>(
+	client: &C,
+	author: &MultiNimbusId,
+	parent: &B::Header,
+	slot_number: u32,
+) -> Option<NimbusPreDigest>
+where
+	C: ProvideRuntimeApi<B>,
+	C::Api: NimbusApi<B>,
+{
+	if supports_multi_scheme::<B, C>(client, parent.hash()) {
+		return Some(NimbusPreDigest::V2 {
+			author: author.clone(),
+			slot: slot_number,
+			extension: None,
+		});
+	}
+
+	match author.as_sr25519() {
+		Some(nimbus_id) => Some(NimbusPreDigest::V1(nimbus_id.clone())),
+		None => {
+			warn!(
+				target: LOG_TARGET,
+				"🔏 The runtime does not support non-sr25519 nimbus key {:?}", author
+			);
+			None
 		}
-		_ => None,
 	}
 }
 
-/// Grab the first eligible nimbus key from the keystore
-/// If multiple keys are eligible this function still only returns one
-/// and makes no guarantees which one as that depends on the keystore's iterator behavior.
+/// Grab an eligible nimbus key among the available keys.
+/// If multiple keys are eligible, the key selector chooses which one authors.
 /// This is the standard way of determining which key to author with.
-pub(crate) fn first_eligible_key<B: BlockT, C>(
+pub(crate) fn eligible_key<B: BlockT, C, KS: KeySelector>(
 	client: Arc<C>,
-	keystore: &dyn Keystore,
+	available_keys: Vec<MultiNimbusId>,
 	parent: &B::Header,
 	slot_number: u32,
-) -> Option<Vec<u8>>
+	key_selector: &KS,
+) -> Option<MultiNimbusId>
 where
 	C: ProvideRuntimeApi<B>,
 	C::Api: NimbusApi<B>,
 {
-	// Get all the available keys
-	let available_keys = Keystore::keys(keystore, NIMBUS_KEY_ID).ok()?;
-
 	// Print a more helpful message than "not eligible" when there are no keys at all.
 	if available_keys.is_empty() {
 		warn!(
@@ -215,71 +419,240 @@ where
 		return None;
 	}
 
-	// Iterate keys until we find an eligible one, or run out of candidates.
-	// If we are skipping prediction, then we author with the first key we find.
-	// prediction skipping only really makes sense when there is a single key in the keystore.
-	let maybe_key = available_keys.into_iter().find(|type_public_pair| {
-		// Have to convert to a typed NimbusId to pass to the runtime API. Maybe this is a clue
-		// That I should be passing Vec<u8> across the wasm boundary?
-		if let Ok(nimbus_id) = NimbusId::from_slice(&type_public_pair) {
-			NimbusApi::can_author(
-				&*client.runtime_api(),
-				parent.hash(),
-				nimbus_id,
-				slot_number,
-				parent,
-			)
-			.unwrap_or_default()
-		} else {
-			false
-		}
-	});
+	let eligible_keys: Vec<_> = available_keys
+		.into_iter()
+		.filter(|author| can_author::<B, C>(&*client, author, parent, slot_number) == Some(true))
+		.collect();
 
 	// If there are no eligible keys, print the log, and exit early.
-	if maybe_key.is_none() {
+	if eligible_keys.is_empty() {
 		info!(
 			target: LOG_TARGET,
 			"🔮 Skipping candidate production because we are not eligible for slot {}", slot_number
 		);
+		return None;
 	}
 
-	maybe_key
+	choose_key(key_selector, &eligible_keys, slot_number)
 }
 
+/// Whether the runtime was upgraded in the parent block.
+///
+/// The runtime version at the parent is compared with the one at the grandparent, both read
+/// through `version_at`. Returns `None` if either version cannot be read.
+pub(crate) fn runtime_upgraded<B: BlockT>(
+	parent: &B::Header,
+	version_at: impl Fn(B::Hash) -> Option<RuntimeVersion>,
+) -> Option<bool> {
+	if parent.number().is_zero() {
+		return Some(false);
+	}
+
+	let parent_version = version_at(parent.hash())?;
+	let grandparent_version = version_at(*parent.parent_hash())?;
+
+	Some(parent_version != grandparent_version)
+}
+
+/// The slot a header was authored in, as reported by its pre-runtime digest. Version 1
+/// pre-runtime digests, which runtimes without version 3 of `NimbusApi` still receive, do not
+/// report the slot, so their headers have none.
+pub(crate) fn slot_of<H: HeaderT>(header: &H) -> Option<u32> {
+	header
+		.digest()
+		.logs
+		.iter()
+		.find_map(|digest| digest.as_nimbus_versioned_pre_digest())
+		.and_then(|pre_digest| pre_digest.slot())
+}
+
+/// Choose the key to author with on top of `parent`, among the keys available in the keystore.
+///
+/// Normally the key selector chooses among the keys the runtime predicts to be eligible. When
+/// prediction is skipped, or the runtime was upgraded in the parent block, it chooses among all
+/// available keys instead. A freshly upgraded runtime has not run its migrations yet, so its
+/// prediction cannot be trusted. If the runtime versions cannot be read, for example because the
+/// grandparent's state was pruned, the runtime is assumed not to have been upgraded.
+pub(crate) fn select_author_key<B: BlockT, C, KS: KeySelector>(
+	client: Arc<C>,
+	available_keys: Vec<MultiNimbusId>,
+	parent: &B::Header,
+	slot_number: u32,
+	skip_prediction: bool,
+	key_selector: &KS,
+	version_at: impl Fn(B::Hash) -> Option<RuntimeVersion>,
+) -> Option<MultiNimbusId>
+where
+	C: ProvideRuntimeApi<B>,
+	C::Api: NimbusApi<B>,
+{
+	if skip_prediction {
+		return available_key(available_keys, slot_number, key_selector);
+	}
+
+	match runtime_upgraded::<B>(parent, version_at) {
+		Some(true) => {
+			info!(
+				target: LOG_TARGET,
+				"🔮 The runtime was upgraded in the parent block, authoring without prediction"
+			);
+			return available_key(available_keys, slot_number, key_selector);
+		}
+		Some(false) => (),
+		None => warn!(
+			target: LOG_TARGET,
+			"🔮 Could not read the runtime versions at {:?} and its parent, assuming the runtime \
+			was not upgraded",
+			parent.hash()
+		),
+	}
+
+	eligible_key::<B, C, KS>(client, available_keys, parent, slot_number, key_selector)
+}
+
+/// Run `f`, which may block, on a thread of its own, so that it does not hold up the executor
+/// the worker runs on. A remote signer, for example, blocks until it answers or its retries run
+/// out. Returns `None` if the thread could not be spawned or `f` panicked.
+pub(crate) async fn off_executor<R, F>(f: F) -> Option<R>
+where
+	F: FnOnce() -> R + Send + 'static,
+	R: Send + 'static,
+{
+	let (sender, receiver) = futures::channel::oneshot::channel();
+	let spawned = std::thread::Builder::new()
+		.name("nimbus-keystore".into())
+		.spawn(move || {
+			let _ = sender.send(f());
+		});
+	if let Err(e) = spawned {
+		error!(target: LOG_TARGET, error = ?e, "Could not spawn a keystore thread.");
+		return None;
+	}
+
+	receiver.await.ok()
+}
+
+/// The nimbus keys in the keystore, listed off the executor.
+pub(crate) async fn nimbus_keys_off_executor(keystore: KeystorePtr) -> Vec<MultiNimbusId> {
+	off_executor(move || nimbus_keys(&*keystore))
+		.await
+		.unwrap_or_default()
+}
+
+/// Sign the header, authored in `slot`, with the author's key, in the author's signature scheme,
+/// and return the seal digest. The block is first recorded in `slashing_protection`. The slot is
+/// passed in because version 1 pre-runtime digests do not report it. Returns `None` if the
+/// block conflicts with one the author already signed, or if the keystore could not sign, for
+/// example because a remote signer is unreachable.
 pub(crate) fn seal_header<B>(
 	header: &B::Header,
+	slot: u32,
 	keystore: &dyn Keystore,
-	public_pair: &Vec<u8>,
-	crypto_id: &CryptoTypeId,
-) -> DigestItem
+	author: &MultiNimbusId,
+	slashing_protection: &dyn SlashingProtector,
+) -> Option<DigestItem>
 where
 	B: BlockT,
 {
 	let pre_hash = header.hash();
+	record_signature(author, slot, pre_hash.as_ref(), slashing_protection)?;
 
-	let raw_sig = Keystore::sign_with(
-		&*keystore,
-		NIMBUS_KEY_ID,
-		*crypto_id,
-		public_pair,
-		pre_hash.as_ref(),
-	)
-	.expect("Keystore should be able to sign")
-	.expect("We already checked that the key was present");
+	sign_seal(keystore, author, pre_hash.as_ref())
+}
 
-	debug!(target: LOG_TARGET, "The signature is \n{:?}", raw_sig);
+/// Like `seal_header`, but the keystore signs off the executor. The block is recorded in
+/// `slashing_protection` before this returns, so the returned future borrows nothing.
+pub(crate) fn seal_header_off_executor<B>(
+	header: &B::Header,
+	slot: u32,
+	keystore: KeystorePtr,
+	author: &MultiNimbusId,
+	slashing_protection: &dyn SlashingProtector,
+) -> impl std::future::Future<Output = Option<DigestItem>> + Send + 'static
+where
+	B: BlockT,
+{
+	let pre_hash = header.hash();
+	let recorded = record_signature(author, slot, pre_hash.as_ref(), slashing_protection);
+	let author = author.clone();
 
-	let signature = raw_sig
-		.clone()
-		.try_into()
-		.expect("signature bytes produced by keystore should be right length");
+	async move {
+		recorded?;
+		off_executor(move || sign_seal(&*keystore, &author, pre_hash.as_ref()))
+			.await
+			.flatten()
+	}
+}
+
+/// Record in `slashing_protection` that `author` is about to sign `message` in `slot`. Returns
+/// `None` if it conflicts with a block the author already signed.
+fn record_signature(
+	author: &MultiNimbusId,
+	slot: u32,
+	message: &[u8],
+	slashing_protection: &dyn SlashingProtector,
+) -> Option<()> {
+	if let Err(e) = slashing_protection.check_and_record(author, slot, message) {
+		error!(target: LOG_TARGET, author = ?author, error = %e, "Refusing to sign the block.");
+		return None;
+	}
 
-	<DigestItem as CompatibleDigestItem>::nimbus_seal(signature)
+	Some(())
+}
+
+/// Sign `message` with the author's key, in the author's signature scheme, and return the seal
+/// digest.
+fn sign_seal(
+	keystore: &dyn Keystore,
+	author: &MultiNimbusId,
+	message: &[u8],
+) -> Option<DigestItem> {
+	let signature: Result<Option<MultiNimbusSignature>, _> = match author {
+		MultiNimbusId::Sr25519(public) => Keystore::sr25519_sign(
+			keystore,
+			NIMBUS_KEY_ID,
+			&sr25519::Public::from(public.clone()),
+			message,
+		)
+		.map(|signature| signature.map(|signature| NimbusSignature::from(signature).into())),
+		MultiNimbusId::Ed25519(public) => Keystore::ed25519_sign(
+			keystore,
+			NIMBUS_KEY_ID,
+			&ed25519::Public::from(public.clone()),
+			message,
+		)
+		.map(|signature| signature.map(|signature| NimbusEd25519Signature::from(signature).into())),
+		MultiNimbusId::Ecdsa(public) => Keystore::ecdsa_sign(
+			keystore,
+			NIMBUS_KEY_ID,
+			&ecdsa::Public::from(public.clone()),
+			message,
+		)
+		.map(|signature| signature.map(|signature| NimbusEcdsaSignature::from(signature).into())),
+	};
+
+	let signature = match signature {
+		Ok(Some(signature)) => signature,
+		Ok(None) => {
+			error!(target: LOG_TARGET, author = ?author, "The keystore does not hold the author's key.");
+			return None;
+		}
+		Err(e) => {
+			error!(target: LOG_TARGET, error = ?e, "Could not sign the block.");
+			return None;
+		}
+	};
+
+	debug!(target: LOG_TARGET, "The signature is \n{:?}", signature);
+
+	Some(<DigestItem as CompatibleDigestItem>::nimbus_multi_seal(
+		signature,
+	))
 }
 
 #[async_trait::async_trait]
-impl<B, PF, BI, BE, ParaClient, CIDP, DP> ParachainConsensus<B>
-	for NimbusConsensus<B, PF, BI, BE, ParaClient, CIDP, DP>
+impl<B, PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP> ParachainConsensus<B>
+	for NimbusConsensus<B, PF, BI, BE, ParaClient, CIDP, DP, EP, BP, KS, SP>
 where
 	B: BlockT,
 	BI: BlockImport<B> + Send + Sync + 'static,
@@ -291,10 +664,14 @@ where
 		ProofRecording = EnableProofRecording,
 		Proof = <EnableProofRecording as ProofRecording>::Proof,
 	>,
-	ParaClient: ProvideRuntimeApi<B> + Send + Sync + 'static,
+	ParaClient: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
 	ParaClient::Api: NimbusApi<B>,
-	CIDP: CreateInherentDataProviders<B, (PHash, PersistedValidationData, NimbusId)> + 'static,
-	DP: DigestsProvider<NimbusId, <B as BlockT>::Hash> + 'static + Send + Sync,
+	CIDP: CreateInherentDataProviders<B, (PHash, PersistedValidationData, MultiNimbusId)> + 'static,
+	DP: DigestsProvider<MultiNimbusId, <B as BlockT>::Hash> + 'static + Send + Sync,
+	EP: EligibilityProver<B> + 'static + Send + Sync,
+	BP: AuthoringBudgetProvider<B> + 'static + Send + Sync,
+	KS: KeySelector + 'static + Send + Sync,
+	SP: SlashingProtector + 'static + Send + Sync,
 {
 	async fn produce_candidate(
 		&mut self,
@@ -302,40 +679,47 @@ where
 		relay_parent: PHash,
 		validation_data: &PersistedValidationData,
 	) -> Option<ParachainCandidate<B>> {
-		// Determine if runtime change
-		let runtime_upgraded = if *parent.number() > sp_runtime::traits::Zero::zero() {
-			use sp_api::Core as _;
-			let previous_runtime_version: sp_api::RuntimeVersion = self
-				.parachain_client
-				.runtime_api()
-				.version(parent.hash())
-				.ok()?;
-			let runtime_version: sp_api::RuntimeVersion = self
-				.parachain_client
-				.runtime_api()
-				.version(parent.hash())
-				.ok()?;
-
-			previous_runtime_version != runtime_version
-		} else {
-			false
-		};
+		if let Some(metrics) = &self.metrics {
+			metrics.slots_checked.inc();
+		}
 
-		let maybe_key = if self.skip_prediction || runtime_upgraded {
-			first_available_key(&*self.keystore)
-		} else {
-			first_eligible_key::<B, ParaClient>(
-				self.parachain_client.clone(),
-				&*self.keystore,
-				parent,
-				validation_data.relay_parent_number,
-			)
-		};
+		// Cumulus asks for one block per relay parent, so leave the slot alone if the parent was
+		// already authored in it, for example on a relay chain fork at the same height. Parents
+		// with a version 1 pre-runtime digest report no slot, so the runtime's own slot check
+		// decides for them.
+		let slot = validation_data.relay_parent_number;
+		if slot_of(parent) == Some(slot) {
+			debug!(
+				target: LOG_TARGET,
+				"Slot {} already holds the parent block, not authoring another one", slot
+			);
+			if let Some(metrics) = &self.metrics {
+				metrics.skipped_slots.inc();
+			}
+			return None;
+		}
+
+		let available_keys = nimbus_keys_off_executor(self.keystore.clone()).await;
+		let maybe_key = select_author_key::<B, ParaClient, KS>(
+			self.parachain_client.clone(),
+			available_keys,
+			parent,
+			validation_data.relay_parent_number,
+			self.skip_prediction,
+			&*self.key_selector,
+			|at| {
+				use sp_api::Core as _;
+				self.parachain_client.runtime_api().version(at).ok()
+			},
+		);
 
 		// If there are no eligible keys, print the log, and exit early.
-		let type_public_pair = match maybe_key {
+		let nimbus_id = match maybe_key {
 			Some(p) => p,
 			None => {
+				if let Some(metrics) = &self.metrics {
+					metrics.skipped_slots.inc();
+				}
 				return None;
 			}
 		};
@@ -347,11 +731,25 @@ where
 			.map_err(|e| error!(target: LOG_TARGET, error = ?e, "Could not create proposer."))
 			.ok()?;
 
-		let nimbus_id = NimbusId::from_slice(&type_public_pair)
-			.map_err(
-				|e| error!(target: LOG_TARGET, error = ?e, "Invalid Nimbus ID (wrong length)."),
-			)
-			.ok()?;
+		// Some filters need a proof of eligibility that only we can produce.
+		let eligibility_digests = match self.eligibility_prover.prove_eligibility(
+			&*self.keystore,
+			&nimbus_id,
+			validation_data.relay_parent_number,
+			parent,
+		) {
+			Some(digests) => digests,
+			None => {
+				if let Some(metrics) = &self.metrics {
+					metrics.skipped_slots.inc();
+				}
+				return None;
+			}
+		};
+
+		if let Some(metrics) = &self.metrics {
+			metrics.eligible_slots.inc();
+		}
 
 		let inherent_data = self
 			.inherent_data(
@@ -362,13 +760,32 @@ where
 			)
 			.await?;
 
-		let mut logs = vec![CompatibleDigestItem::nimbus_pre_digest(nimbus_id.clone())];
+		let pre_digest = pre_digest::<B, ParaClient>(
+			&*self.parachain_client,
+			&nimbus_id,
+			parent,
+			validation_data.relay_parent_number,
+		)?;
+		let mut logs = vec![CompatibleDigestItem::nimbus_versioned_pre_digest(
+			pre_digest,
+		)];
+		logs.extend(eligibility_digests);
 		logs.extend(
 			self.additional_digests_provider
-				.provide_digests(nimbus_id, parent.hash()),
+				.provide_digests(nimbus_id.clone(), parent.hash()),
 		);
 		let inherent_digests = sp_runtime::generic::Digest { logs };
 
+		let budget = self.authoring_budget_provider.authoring_budget(
+			&self.authoring_config,
+			parent,
+			validation_data,
+		);
+
+		let proposal_timer = self
+			.metrics
+			.as_ref()
+			.map(|metrics| metrics.proposal_duration.start_timer());
 		let Proposal {
 			block,
 			storage_changes,
@@ -377,26 +794,30 @@ where
 			.propose(
 				inherent_data,
 				inherent_digests,
-				//TODO: Fix this.
-				Duration::from_millis(500),
-				// Set the block limit to 50% of the maximum PoV size.
-				//
-				// TODO: If we got benchmarking that includes that encapsulates the proof size,
-				// we should be able to use the maximum pov size.
-				Some((validation_data.max_pov_size / 2) as usize),
+				budget.proposal_duration,
+				budget.block_size_limit,
 			)
 			.await
 			.map_err(|e| error!(target: LOG_TARGET, error = ?e, "Proposing failed."))
 			.ok()?;
 
+		drop(proposal_timer);
+
 		let (header, extrinsics) = block.clone().deconstruct();
 
-		let sig_digest = seal_header::<B>(
+		let seal_timer = self
+			.metrics
+			.as_ref()
+			.map(|metrics| metrics.seal_duration.start_timer());
+		let seal = seal_header_off_executor::<B>(
 			&header,
-			&*self.keystore,
-			&type_public_pair,
-			&sr25519::CRYPTO_ID,
+			validation_data.relay_parent_number,
+			self.keystore.clone(),
+			&nimbus_id,
+			&*self.slashing_protection,
 		);
+		let sig_digest = seal.await?;
+		drop(seal_timer);
 
 		let mut block_import_params = BlockImportParams::new(BlockOrigin::Own, header.clone());
 		block_import_params.post_digests.push(sig_digest.clone());
@@ -426,6 +847,9 @@ where
 				error = ?err,
 				"Error importing built block.",
 			);
+			if let Some(metrics) = &self.metrics {
+				metrics.import_failures.inc();
+			}
 
 			return None;
 		}
@@ -447,7 +871,18 @@ where
 ///
 /// I briefly tried the async keystore approach, but decided to go sync so I can copy
 /// code from Aura. Maybe after it is working, Jeremy can help me go async.
-pub struct BuildNimbusConsensusParams<PF, BI, BE, ParaClient, CIDP, DP> {
+pub struct BuildNimbusConsensusParams<
+	PF,
+	BI,
+	BE,
+	ParaClient,
+	CIDP,
+	DP,
+	EP = (),
+	BP = (),
+	KS = (),
+	SP = (),
+> {
 	pub para_id: ParaId,
 	pub proposer_factory: PF,
 	pub create_inherent_data_providers: CIDP,
@@ -457,4 +892,441 @@ pub struct BuildNimbusConsensusParams<PF, BI, BE, ParaClient, CIDP, DP> {
 	pub keystore: KeystorePtr,
 	pub skip_prediction: bool,
 	pub additional_digests_provider: DP,
+	/// Proves eligibility for filters that cannot be checked through `NimbusApi::can_author`.
+	/// Use `()` when the runtime has no such filter.
+	pub eligibility_prover: EP,
+	/// The proposal duration and block size limit used when authoring.
+	pub authoring_config: AuthoringConfig,
+	/// Computes the budget of each block. Use `()` to apply `authoring_config` to every block.
+	pub authoring_budget_provider: BP,
+	/// Chooses which key authors when several keys may author. Use `()` to author with the
+	/// first eligible key, or see `RoundRobin` and `PriorityList`.
+	pub key_selector: KS,
+	/// Refuses to seal blocks that conflict with blocks already signed. Use `()` for no
+	/// protection, or see `SlashingProtectionDb`.
+	pub slashing_protection: SP,
+	/// The registry to export the worker's metrics to, if any.
+	pub prometheus_registry: Option<Registry>,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use sp_api::ApiRef;
+	use sp_core::H256;
+	use sp_keystore::testing::MemoryKeystore;
+	use sp_runtime::testing::{Block as TestBlock, ExtrinsicWrapper, Header};
+	use std::collections::HashMap;
+
+	pub(crate) type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+	/// A client whose runtime has a given spec version at each block, and considers a fixed
+	/// set of authors eligible. With `api_fails`, asking the runtime for eligibility fails. With
+	/// `unchecked_slots`, the runtime does not check the slot of pre-runtime digests.
+	#[derive(Default)]
+	pub(crate) struct MockClient {
+		pub spec_versions: HashMap<H256, u32>,
+		pub eligible: Vec<NimbusId>,
+		pub api_fails: bool,
+		pub unchecked_slots: bool,
+	}
+
+	impl MockClient {
+		pub fn version_at(&self, at: H256) -> Option<RuntimeVersion> {
+			self.spec_versions
+				.get(&at)
+				.map(|spec_version| RuntimeVersion {
+					spec_version: *spec_version,
+					..Default::default()
+				})
+		}
+	}
+
+	pub(crate) struct MockRuntimeApi {
+		eligible: Vec<NimbusId>,
+		api_fails: bool,
+		unchecked_slots: bool,
+	}
+
+	impl ProvideRuntimeApi<Block> for MockClient {
+		type Api = MockRuntimeApi;
+
+		fn runtime_api(&self) -> ApiRef<'_, Self::Api> {
+			MockRuntimeApi {
+				eligible: self.eligible.clone(),
+				api_fails: self.api_fails,
+				unchecked_slots: self.unchecked_slots,
+			}
+			.into()
+		}
+	}
+
+	sp_api::mock_impl_runtime_apis! {
+		impl NimbusApi<Block> for MockRuntimeApi {
+			#[advanced]
+			fn can_author(
+				&self,
+				_at: H256,
+				author: NimbusId,
+				_relay_parent: u32,
+				_parent_header: &Header,
+			) -> Result<bool, sp_api::ApiError> {
+				if self.api_fails {
+					return Err(sp_api::ApiError::Application(Box::from("The state was pruned")));
+				}
+
+				Ok(self.eligible.contains(&author))
+			}
+
+			fn checks_pre_digest_slot(&self) -> bool {
+				!self.unchecked_slots
+			}
+		}
+	}
+
+	pub(crate) fn header(number: u64, parent_hash: H256) -> Header {
+		Header {
+			parent_hash,
+			number,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		}
+	}
+
+	/// Builds the chain genesis <- block 1 <- block 2, with the given runtime spec version at
+	/// each block, and returns its headers.
+	pub(crate) fn chain(client: &mut MockClient, spec_versions: [u32; 3]) -> Vec<Header> {
+		let mut headers = vec![header(0, Default::default())];
+		for number in 1..3 {
+			let parent_hash = headers[number - 1].hash();
+			headers.push(header(number as u64, parent_hash));
+		}
+		for (header, spec_version) in headers.iter().zip(spec_versions) {
+			client.spec_versions.insert(header.hash(), spec_version);
+		}
+
+		headers
+	}
+
+	pub(crate) fn nimbus_key(keystore: &MemoryKeystore, seed: &str) -> NimbusId {
+		Keystore::sr25519_generate_new(keystore, NIMBUS_KEY_ID, Some(seed))
+			.expect("Can generate keys in the memory keystore")
+			.into()
+	}
+
+	#[test]
+	fn header_is_sealed_with_the_authors_key() {
+		let keystore = MemoryKeystore::new();
+		let alice: MultiNimbusId = nimbus_key(&keystore, "//Alice").into();
+		let mut header = header(1, Default::default());
+		header
+			.digest
+			.push(CompatibleDigestItem::nimbus_versioned_pre_digest(
+				NimbusPreDigest::V2 {
+					author: alice.clone(),
+					slot: 10,
+					extension: None,
+				},
+			));
+
+		let seal = seal_header::<Block>(&header, 10, &keystore, &alice, &())
+			.expect("Alice's key is in the keystore");
+		header.digest.push(seal);
+
+		assert_eq!(nimbus_primitives::verify_seal(&header).ok(), Some(alice));
+	}
+
+	#[test]
+	fn header_is_sealed_off_the_executor() {
+		let keystore = MemoryKeystore::new();
+		let alice: MultiNimbusId = nimbus_key(&keystore, "//Alice").into();
+		let mut header = header(1, Default::default());
+		header
+			.digest
+			.push(CompatibleDigestItem::nimbus_versioned_pre_digest(
+				NimbusPreDigest::V2 {
+					author: alice.clone(),
+					slot: 10,
+					extension: None,
+				},
+			));
+
+		let seal = seal_header_off_executor::<Block>(&header, 10, Arc::new(keystore), &alice, &());
+		header
+			.digest
+			.push(futures::executor::block_on(seal).expect("Alice's key is in the keystore"));
+
+		assert_eq!(nimbus_primitives::verify_seal(&header).ok(), Some(alice));
+	}
+
+	#[test]
+	fn current_runtimes_get_version_2_pre_digests() {
+		let client = MockClient::default();
+		let alice: MultiNimbusId = NimbusId::from(sr25519::Public::from_raw([1; 32])).into();
+
+		assert_eq!(
+			pre_digest::<Block, _>(&client, &alice, &header(0, Default::default()), 10),
+			Some(NimbusPreDigest::V2 {
+				author: alice,
+				slot: 10,
+				extension: None,
+			})
+		);
+	}
+
+	#[test]
+	fn sealing_without_the_authors_key_fails_gracefully() {
+		let keystore = MemoryKeystore::new();
+		let alice = nimbus_key(&MemoryKeystore::new(), "//Alice");
+
+		assert_eq!(
+			seal_header::<Block>(
+				&header_in_slot(1, Default::default(), 10),
+				10,
+				&keystore,
+				&alice.into(),
+				&()
+			),
+			None
+		);
+	}
+
+	/// A `SlashingProtector` that refuses every signature.
+	struct RefuseAll;
+
+	impl SlashingProtector for RefuseAll {
+		fn check_and_record(
+			&self,
+			_author: &MultiNimbusId,
+			slot: u32,
+			block_hash: &[u8],
+		) -> Result<(), SlashingProtectionError> {
+			Err(SlashingProtectionError::Conflict {
+				slot,
+				signed: block_hash.to_vec(),
+			})
+		}
+	}
+
+	#[test]
+	fn blocks_refused_by_the_slashing_protection_are_not_sealed() {
+		let keystore = MemoryKeystore::new();
+		let alice: MultiNimbusId = nimbus_key(&keystore, "//Alice").into();
+
+		assert_eq!(
+			seal_header::<Block>(
+				&header_in_slot(1, Default::default(), 10),
+				10,
+				&keystore,
+				&alice,
+				&RefuseAll
+			),
+			None
+		);
+	}
+
+	pub(crate) fn header_in_slot(number: u64, parent_hash: H256, slot: u32) -> Header {
+		let mut header = header(number, parent_hash);
+		header
+			.digest
+			.push(CompatibleDigestItem::nimbus_versioned_pre_digest(
+				NimbusPreDigest::V2 {
+					author: NimbusId::from(sr25519::Public::from_raw([1; 32])).into(),
+					slot,
+					extension: None,
+				},
+			));
+		header
+	}
+
+	#[test]
+	fn headers_report_their_slot_only_with_version_2_pre_digests() {
+		assert_eq!(slot_of(&header_in_slot(1, Default::default(), 6)), Some(6));
+
+		let mut v1_header = header(1, Default::default());
+		v1_header
+			.digest
+			.push(CompatibleDigestItem::nimbus_versioned_pre_digest(
+				NimbusPreDigest::V1(NimbusId::from(sr25519::Public::from_raw([1; 32]))),
+			));
+		assert_eq!(slot_of(&v1_header), None);
+	}
+
+	#[test]
+	fn unchanged_runtime_is_not_an_upgrade() {
+		let mut client = MockClient::default();
+		let headers = chain(&mut client, [1, 1, 1]);
+
+		assert_eq!(
+			runtime_upgraded::<Block>(&headers[2], |at| client.version_at(at)),
+			Some(false)
+		);
+	}
+
+	#[test]
+	fn runtime_changed_in_parent_is_an_upgrade() {
+		let mut client = MockClient::default();
+		let headers = chain(&mut client, [1, 1, 2]);
+
+		assert_eq!(
+			runtime_upgraded::<Block>(&headers[2], |at| client.version_at(at)),
+			Some(true)
+		);
+		// The upgrade is only reported for the block that enacted it.
+		let headers = chain(&mut client, [1, 2, 2]);
+		assert_eq!(
+			runtime_upgraded::<Block>(&headers[2], |at| client.version_at(at)),
+			Some(false)
+		);
+	}
+
+	#[test]
+	fn genesis_is_not_an_upgrade() {
+		let mut client = MockClient::default();
+		let headers = chain(&mut client, [1, 2, 3]);
+
+		assert_eq!(
+			runtime_upgraded::<Block>(&headers[0], |_| None),
+			Some(false)
+		);
+	}
+
+	#[test]
+	fn unknown_runtime_version_is_reported() {
+		let client = MockClient::default();
+		let parent = header(1, Default::default());
+
+		assert_eq!(
+			runtime_upgraded::<Block>(&parent, |at| client.version_at(at)),
+			None
+		);
+	}
+
+	#[test]
+	fn eligible_key_is_selected_without_upgrade() {
+		let keystore = MemoryKeystore::new();
+		nimbus_key(&keystore, "//Alice");
+		let bob = nimbus_key(&keystore, "//Bob");
+
+		let mut client = MockClient {
+			eligible: vec![bob.clone()],
+			..Default::default()
+		};
+		let headers = chain(&mut client, [1, 1, 1]);
+		let client = Arc::new(client);
+
+		let key = select_author_key::<Block, MockClient, ()>(
+			client.clone(),
+			nimbus_keys(&keystore),
+			&headers[2],
+			10,
+			false,
+			&(),
+			|at| client.version_at(at),
+		);
+
+		assert_eq!(key, Some(bob.into()));
+	}
+
+	#[test]
+	fn key_selector_chooses_among_eligible_keys() {
+		let keystore = MemoryKeystore::new();
+		let alice = nimbus_key(&keystore, "//Alice");
+		let bob = nimbus_key(&keystore, "//Bob");
+		let charlie = nimbus_key(&keystore, "//Charlie");
+
+		let mut client = MockClient {
+			eligible: vec![bob.clone(), charlie.clone()],
+			..Default::default()
+		};
+		let headers = chain(&mut client, [1, 1, 1]);
+		let client = Arc::new(client);
+
+		// Alice has the highest priority, but she is not eligible.
+		let key_selector = PriorityList(vec![alice.into(), charlie.clone().into(), bob.into()]);
+		let key = select_author_key::<Block, MockClient, PriorityList>(
+			client.clone(),
+			nimbus_keys(&keystore),
+			&headers[2],
+			10,
+			false,
+			&key_selector,
+			|at| client.version_at(at),
+		);
+
+		assert_eq!(key, Some(charlie.into()));
+	}
+
+	#[test]
+	fn ineligible_key_is_not_selected_without_upgrade() {
+		let keystore = MemoryKeystore::new();
+		nimbus_key(&keystore, "//Alice");
+
+		let mut client = MockClient::default();
+		let headers = chain(&mut client, [1, 1, 1]);
+		let client = Arc::new(client);
+
+		let key = select_author_key::<Block, MockClient, ()>(
+			client.clone(),
+			nimbus_keys(&keystore),
+			&headers[2],
+			10,
+			false,
+			&(),
+			|at| client.version_at(at),
+		);
+
+		assert_eq!(key, None);
+	}
+
+	#[test]
+	fn available_key_is_selected_after_upgrade() {
+		let keystore = MemoryKeystore::new();
+		let alice = nimbus_key(&keystore, "//Alice");
+
+		// Alice is not eligible, but the runtime that says so was just upgraded.
+		let mut client = MockClient::default();
+		let headers = chain(&mut client, [1, 1, 2]);
+		let client = Arc::new(client);
+
+		let key = select_author_key::<Block, MockClient, ()>(
+			client.clone(),
+			nimbus_keys(&keystore),
+			&headers[2],
+			10,
+			false,
+			&(),
+			|at| client.version_at(at),
+		);
+
+		assert_eq!(key, Some(alice.into()));
+	}
+
+	#[test]
+	fn eligible_key_is_selected_when_runtime_versions_are_unknown() {
+		let keystore = MemoryKeystore::new();
+		let alice = nimbus_key(&keystore, "//Alice");
+
+		// The grandparent's state was pruned, so its runtime version cannot be read.
+		let mut client = MockClient {
+			eligible: vec![alice.clone()],
+			..Default::default()
+		};
+		let headers = chain(&mut client, [1, 1, 1]);
+		client.spec_versions.remove(&headers[1].hash());
+		let client = Arc::new(client);
+
+		let key = select_author_key::<Block, MockClient, ()>(
+			client.clone(),
+			nimbus_keys(&keystore),
+			&headers[2],
+			10,
+			false,
+			&(),
+			|at| client.version_at(at),
+		);
+
+		assert_eq!(key, Some(alice.into()));
+	}
 }
@@ -14,24 +14,23 @@
 // You should have received a copy of the GNU General Public License
 // along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::{EligibilityProver, KeySelector, SlashingProtector};
 use cumulus_primitives_parachain_inherent::{
 	ParachainInherentData, INHERENT_IDENTIFIER as PARACHAIN_INHERENT_IDENTIFIER,
 };
 use nimbus_primitives::{
-	CompatibleDigestItem, DigestsProvider, NimbusApi, NimbusId, NIMBUS_ENGINE_ID,
+	claimed_author, CompatibleDigestItem, DigestsProvider, MultiNimbusId, NimbusApi,
 };
 use sc_consensus::BlockImportParams;
 use sc_consensus_manual_seal::{ConsensusDataProvider, Error};
 use sp_api::{BlockT, HeaderT, ProvideRuntimeApi, TransactionFor};
-use sp_application_crypto::ByteArray;
-use sp_core::sr25519;
 use sp_inherents::InherentData;
 use sp_keystore::KeystorePtr;
-use sp_runtime::{Digest, DigestItem};
+use sp_runtime::Digest;
 use std::{marker::PhantomData, sync::Arc};
 
 /// Provides nimbus-compatible pre-runtime digests for use with manual seal consensus
-pub struct NimbusManualSealConsensusDataProvider<C, DP = (), P = ()> {
+pub struct NimbusManualSealConsensusDataProvider<C, DP = (), P = (), EP = (), KS = (), SP = ()> {
 	/// Shared reference to keystore
 	pub keystore: KeystorePtr,
 
@@ -41,47 +40,80 @@ pub struct NimbusManualSealConsensusDataProvider<C, DP = (), P = ()> {
 	/// Additional digests provider
 	pub additional_digests_provider: DP,
 
+	/// Proves eligibility for filters that cannot be checked through `NimbusApi::can_author`
+	pub eligibility_prover: EP,
+
+	/// Chooses which key authors when several keys are eligible
+	pub key_selector: KS,
+
+	/// Refuses to seal blocks that conflict with blocks already signed
+	pub slashing_protection: SP,
+
 	pub _phantom: PhantomData<P>,
 }
 
-impl<B, C, DP, P> ConsensusDataProvider<B> for NimbusManualSealConsensusDataProvider<C, DP, P>
+/// The relay chain block number, which is used as the slot number, from the mocked parachain
+/// inherent.
+fn slot_number(inherents: &InherentData) -> u32 {
+	inherents
+		.get_data::<ParachainInherentData>(&PARACHAIN_INHERENT_IDENTIFIER)
+		.expect("Parachain inherent should decode correctly")
+		.expect("Parachain inherent should be present because we are mocking it")
+		.validation_data
+		.relay_parent_number
+}
+
+impl<B, C, DP, P, EP, KS, SP> ConsensusDataProvider<B>
+	for NimbusManualSealConsensusDataProvider<C, DP, P, EP, KS, SP>
 where
 	B: BlockT,
 	C: ProvideRuntimeApi<B> + Send + Sync,
 	C::Api: NimbusApi<B>,
-	DP: DigestsProvider<NimbusId, <B as BlockT>::Hash> + Send + Sync,
+	DP: DigestsProvider<MultiNimbusId, <B as BlockT>::Hash> + Send + Sync,
 	P: Send + Sync,
+	EP: EligibilityProver<B> + Send + Sync,
+	KS: KeySelector + Send + Sync,
+	SP: SlashingProtector + Send + Sync,
 {
 	type Transaction = TransactionFor<C, B>;
 	type Proof = P;
 
 	fn create_digest(&self, parent: &B::Header, inherents: &InherentData) -> Result<Digest, Error> {
 		// Retrieve the relay chain block number to use as the slot number from the parachain inherent
-		let slot_number = inherents
-			.get_data::<ParachainInherentData>(&PARACHAIN_INHERENT_IDENTIFIER)
-			.expect("Parachain inherent should decode correctly")
-			.expect("Parachain inherent should be present because we are mocking it")
-			.validation_data
-			.relay_parent_number;
-
-		// Fetch first eligible key from keystore
-		let maybe_key = crate::first_eligible_key::<B, C>(
+		let slot_number = slot_number(inherents);
+
+		// Let the key selector choose among the eligible keys in the keystore
+		let maybe_key = crate::eligible_key::<B, C, KS>(
 			self.client.clone(),
-			&*self.keystore,
+			crate::nimbus_keys(&*self.keystore),
 			parent,
 			// For now we author all blocks in slot zero, which is consistent with  how we are
 			// mocking the relay chain height which the runtime uses for slot beacon.
 			// This should improve. See https://github.com/PureStake/nimbus/issues/3
 			slot_number,
+			&self.key_selector,
 		);
 
 		// If we aren't eligible, return an appropriate error
 		match maybe_key {
-			Some(key) => {
-				let nimbus_id = NimbusId::from_slice(&key).map_err(|_| {
-					Error::StringError(String::from("invalid nimbus id (wrong length)"))
-				})?;
-				let mut logs = vec![CompatibleDigestItem::nimbus_pre_digest(nimbus_id.clone())];
+			Some(nimbus_id) => {
+				let eligibility_digests = self
+					.eligibility_prover
+					.prove_eligibility(&*self.keystore, &nimbus_id, slot_number, parent)
+					.ok_or_else(|| {
+						Error::StringError(String::from("could not prove nimbus eligibility"))
+					})?;
+				let pre_digest =
+					crate::pre_digest::<B, C>(&*self.client, &nimbus_id, parent, slot_number)
+						.ok_or_else(|| {
+							Error::StringError(String::from(
+								"the runtime does not support the nimbus key",
+							))
+						})?;
+				let mut logs = vec![CompatibleDigestItem::nimbus_versioned_pre_digest(
+					pre_digest,
+				)];
+				logs.extend(eligibility_digests);
 				logs.extend(
 					self.additional_digests_provider
 						.provide_digests(nimbus_id, parent.hash()),
@@ -99,35 +131,22 @@ where
 		&self,
 		_parent: &B::Header,
 		params: &mut BlockImportParams<B, Self::Transaction>,
-		_inherents: &InherentData,
+		inherents: &InherentData,
 		_proof: Self::Proof,
 	) -> Result<(), Error> {
-		// We have to reconstruct the type-public pair which is only communicated through the pre-runtime digest
-		let claimed_author = params
-			.header
-			.digest()
-			.logs
-			.iter()
-			.find_map(|digest| {
-				match *digest {
-					// We do not support the older author inherent in manual seal
-					DigestItem::PreRuntime(id, ref author_id) if id == NIMBUS_ENGINE_ID => {
-						Some(author_id.clone())
-					}
-					_ => None,
-				}
-			})
-			.expect("Expected one pre-runtime digest that contains author id bytes");
-
-		let nimbus_public = NimbusId::from_slice(&claimed_author)
-			.map_err(|_| Error::StringError(String::from("invalid nimbus id (wrong length)")))?;
+		// The author's key is only communicated through the pre-runtime digest
+		let nimbus_id = claimed_author(params.header.digest()).map_err(|e| {
+			Error::StringError(format!("could not find the nimbus author: {:?}", e))
+		})?;
 
 		let sig_digest = crate::seal_header::<B>(
 			&params.header,
+			slot_number(inherents),
 			&*self.keystore,
-			&nimbus_public.to_raw_vec(),
-			&sr25519::CRYPTO_ID,
-		);
+			&nimbus_id,
+			&self.slashing_protection,
+		)
+		.ok_or_else(|| Error::StringError(String::from("could not seal the block")))?;
 
 		params.post_digests.push(sig_digest);
 
@@ -0,0 +1,171 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the nimbus worker and import queue.
+
+use crate::NimbusVerificationError;
+use nimbus_primitives::MultiNimbusId;
+use sp_core::{crypto::ByteArray, hexdisplay::HexDisplay};
+use substrate_prometheus_endpoint::{
+	register, Counter, CounterVec, Histogram, HistogramOpts, Opts, PrometheusError, Registry, U64,
+};
+
+/// Metrics of the nimbus consensus worker.
+#[derive(Clone)]
+pub(crate) struct WorkerMetrics {
+	pub slots_checked: Counter<U64>,
+	pub eligible_slots: Counter<U64>,
+	pub skipped_slots: Counter<U64>,
+	pub proposal_duration: Histogram,
+	pub seal_duration: Histogram,
+	pub import_failures: Counter<U64>,
+}
+
+impl WorkerMetrics {
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			slots_checked: register(
+				Counter::new(
+					"nimbus_worker_slots_checked_total",
+					"Number of slots in which the worker checked whether it may author",
+				)?,
+				registry,
+			)?,
+			eligible_slots: register(
+				Counter::new(
+					"nimbus_worker_eligible_slots_total",
+					"Number of slots in which one of the worker's keys was chosen to author",
+				)?,
+				registry,
+			)?,
+			skipped_slots: register(
+				Counter::new(
+					"nimbus_worker_skipped_slots_total",
+					"Number of slots skipped because none of the worker's keys could author",
+				)?,
+				registry,
+			)?,
+			proposal_duration: register(
+				Histogram::with_opts(HistogramOpts::new(
+					"nimbus_worker_proposal_duration_seconds",
+					"Time taken to propose a block",
+				))?,
+				registry,
+			)?,
+			seal_duration: register(
+				Histogram::with_opts(HistogramOpts::new(
+					"nimbus_worker_seal_duration_seconds",
+					"Time taken to seal a block",
+				))?,
+				registry,
+			)?,
+			import_failures: register(
+				Counter::new(
+					"nimbus_worker_import_failures_total",
+					"Number of authored blocks that could not be imported",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Why the verifier rejected a block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RejectionReason {
+	Unsealed,
+	BadSignature,
+	BadAuthorId,
+	InherentFailure,
+	Ineligible,
+}
+
+impl RejectionReason {
+	fn label(&self) -> &'static str {
+		match self {
+			Self::Unsealed => "unsealed",
+			Self::BadSignature => "bad_signature",
+			Self::BadAuthorId => "bad_author_id",
+			Self::InherentFailure => "inherent_failure",
+			Self::Ineligible => "ineligible",
+		}
+	}
+}
+
+impl From<&NimbusVerificationError> for RejectionReason {
+	fn from(e: &NimbusVerificationError) -> Self {
+		match e {
+			NimbusVerificationError::MissingSeal | NimbusVerificationError::ForeignSeal(_) => {
+				Self::Unsealed
+			}
+			NimbusVerificationError::MissingAuthor
+			| NimbusVerificationError::DuplicateAuthor
+			| NimbusVerificationError::MalformedAuthorId => Self::BadAuthorId,
+			NimbusVerificationError::BadSignature => Self::BadSignature,
+			NimbusVerificationError::IneligibleAuthor(_) => Self::Ineligible,
+			NimbusVerificationError::InherentFailure(_) => Self::InherentFailure,
+		}
+	}
+}
+
+/// Metrics of the nimbus import queue.
+#[derive(Clone)]
+pub(crate) struct ImportQueueMetrics {
+	rejections: CounterVec<U64>,
+	imported_blocks: CounterVec<U64>,
+}
+
+impl ImportQueueMetrics {
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			rejections: register(
+				CounterVec::new(
+					Opts::new(
+						"nimbus_verifier_rejections_total",
+						"Number of blocks rejected by the nimbus verifier",
+					),
+					&["reason"],
+				)?,
+				registry,
+			)?,
+			imported_blocks: register(
+				CounterVec::new(
+					Opts::new(
+						"nimbus_imported_blocks_total",
+						"Number of nimbus blocks imported, by author",
+					),
+					&["author"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	pub fn rejected(&self, reason: RejectionReason) {
+		self.rejections.with_label_values(&[reason.label()]).inc();
+	}
+
+	pub fn imported(&self, author: &MultiNimbusId) {
+		let public = match author {
+			MultiNimbusId::Sr25519(public) => public.to_raw_vec(),
+			MultiNimbusId::Ed25519(public) => public.to_raw_vec(),
+			MultiNimbusId::Ecdsa(public) => public.to_raw_vec(),
+		};
+		let label = format!("0x{}", HexDisplay::from(&public));
+
+		self.imported_blocks.with_label_values(&[&label]).inc();
+	}
+}
@@ -0,0 +1,348 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reporting the peers that send blocks which fail nimbus verification.
+//!
+//! The verifier only sees the blocks, not the peers they came from. `NimbusImportQueue` wraps
+//! the import queue to remember which peer sent each block until it is verified, so that the
+//! verifier can turn its errors into reputation changes for the right peer. The changes are
+//! sent on a channel, because the network is built after the import queue. Pass the receiver
+//! to `report_peers` once the network is running.
+//!
+//! The same wrapper verifies the seals of initial sync blocks in parallel when they are queued,
+//! if asked to, and tells the verifier which seals it already verified. The seals are verified on
+//! a dedicated thread, so that queueing blocks never waits for them; the verifier waits for the
+//! result of each block instead.
+
+use crate::seal_verification::{verify_seals, SealVerification};
+use futures::{
+	channel::{
+		mpsc::{UnboundedReceiver, UnboundedSender},
+		oneshot,
+	},
+	StreamExt,
+};
+use log::warn;
+use parking_lot::Mutex;
+use sc_consensus::{
+	import_queue::{
+		BasicQueue, ImportQueue, ImportQueueService, IncomingBlock, Link, RuntimeOrigin,
+	},
+	BlockOrigin,
+};
+use sc_network::{NetworkPeers, ReputationChange};
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT, NumberFor},
+	Justifications,
+};
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{mpsc, Arc},
+	thread,
+};
+
+/// The number of queued blocks about which something is remembered.
+const MAX_TRACKED_BLOCKS: usize = 2048;
+
+/// Something remembered about each of the blocks waiting in the import queue, oldest first.
+pub(crate) struct QueuedBlocks<B: BlockT, V> {
+	values: HashMap<B::Hash, V>,
+	order: VecDeque<B::Hash>,
+}
+
+impl<B: BlockT, V> Default for QueuedBlocks<B, V> {
+	fn default() -> Self {
+		Self {
+			values: HashMap::new(),
+			order: VecDeque::new(),
+		}
+	}
+}
+
+impl<B: BlockT, V> QueuedBlocks<B, V> {
+	/// Remember `value` for the block `hash`, forgetting the oldest blocks if too many are
+	/// remembered.
+	pub fn record(&mut self, hash: B::Hash, value: V) {
+		if self.values.insert(hash, value).is_none() {
+			self.order.push_back(hash);
+		}
+		while self.order.len() > MAX_TRACKED_BLOCKS {
+			if let Some(oldest) = self.order.pop_front() {
+				self.values.remove(&oldest);
+			}
+		}
+	}
+
+	/// The value remembered for the block `hash`, which is forgotten.
+	pub fn take(&mut self, hash: &B::Hash) -> Option<V> {
+		let value = self.values.remove(hash)?;
+		self.order.retain(|queued| queued != hash);
+		Some(value)
+	}
+}
+
+/// The peers that sent the queued blocks, shared between the import queue services and the
+/// verifier.
+pub(crate) type SharedBlockOrigins<B> = Arc<Mutex<QueuedBlocks<B, RuntimeOrigin>>>;
+
+/// Whether the seals of the queued blocks are valid, once the seal verifier thread has checked
+/// them, shared between the import queue services and the verifier.
+pub(crate) type SharedVerifiedSeals<B> = Arc<Mutex<QueuedBlocks<B, oneshot::Receiver<bool>>>>;
+
+/// The headers of a chunk of initial sync blocks, each with where to send whether its seal is
+/// valid.
+type SealBatch<B> = Vec<(<B as BlockT>::Header, oneshot::Sender<bool>)>;
+
+/// Start a thread that verifies the seals of the batches it receives on up to `threads` threads.
+/// It stops once all the senders are dropped.
+fn spawn_seal_verifier<B: BlockT>(threads: usize) -> Option<mpsc::Sender<SealBatch<B>>> {
+	let (sender, batches) = mpsc::channel::<SealBatch<B>>();
+	let spawned = thread::Builder::new()
+		.name("nimbus-seal-verifier".into())
+		.spawn(move || {
+			for batch in batches {
+				let (headers, senders): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+				for (sender, result) in senders.into_iter().zip(verify_seals(&headers, threads)) {
+					// The block may have been forgotten already.
+					let _ = sender.send(result.is_ok());
+				}
+			}
+		});
+
+	match spawned {
+		Ok(_) => Some(sender),
+		Err(e) => {
+			warn!(
+				target: crate::LOG_TARGET,
+				"Failed to start the seal verifier thread, seals are verified sequentially: {:?}",
+				e
+			);
+			None
+		}
+	}
+}
+
+/// The reputation changes of the peers that sent bad blocks.
+pub(crate) type ReputationSink = UnboundedSender<(RuntimeOrigin, ReputationChange)>;
+
+/// An import queue service that remembers who sent each block, and has the seals of initial sync
+/// blocks verified in parallel if asked to.
+struct PeerTrackingService<B: BlockT> {
+	inner: Box<dyn ImportQueueService<B>>,
+	origins: SharedBlockOrigins<B>,
+	seal_verifier: Option<mpsc::Sender<SealBatch<B>>>,
+	verified_seals: SharedVerifiedSeals<B>,
+}
+
+impl<B: BlockT> PeerTrackingService<B> {
+	/// Hand the seals of the blocks to the seal verifier thread, and remember where the verifier
+	/// will find the result of each block. The verifier checks the seals that are not valid
+	/// again, to reject their blocks.
+	fn pre_verify(&self, blocks: &[IncomingBlock<B>], seal_verifier: &mpsc::Sender<SealBatch<B>>) {
+		let mut batch = Vec::with_capacity(blocks.len());
+		{
+			let mut verified_seals = self.verified_seals.lock();
+			for header in blocks.iter().filter_map(|block| block.header.clone()) {
+				let (sender, receiver) = oneshot::channel();
+				verified_seals.record(header.hash(), receiver);
+				batch.push((header, sender));
+			}
+		}
+
+		// If the thread is gone, the receivers are cancelled and the verifier checks the seals.
+		let _ = seal_verifier.send(batch);
+	}
+}
+
+impl<B: BlockT> ImportQueueService<B> for PeerTrackingService<B> {
+	fn import_blocks(&mut self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
+		{
+			let mut origins = self.origins.lock();
+			for block in &blocks {
+				if let Some(peer) = block.origin {
+					origins.record(block.hash, peer);
+				}
+			}
+		}
+
+		if let Some(seal_verifier) = &self.seal_verifier {
+			if origin == BlockOrigin::NetworkInitialSync {
+				self.pre_verify(&blocks, seal_verifier);
+			}
+		}
+
+		self.inner.import_blocks(origin, blocks)
+	}
+
+	fn import_justifications(
+		&mut self,
+		who: RuntimeOrigin,
+		hash: B::Hash,
+		number: NumberFor<B>,
+		justifications: Justifications,
+	) {
+		self.inner
+			.import_justifications(who, hash, number, justifications)
+	}
+}
+
+/// The nimbus import queue: a `BasicQueue` that lets the verifier report the peers that sent
+/// bad blocks.
+pub struct NimbusImportQueue<B: BlockT, Transaction> {
+	inner: BasicQueue<B, Transaction>,
+	service: PeerTrackingService<B>,
+	origins: SharedBlockOrigins<B>,
+	seal_verifier: Option<mpsc::Sender<SealBatch<B>>>,
+	verified_seals: SharedVerifiedSeals<B>,
+	reputation_changes: Option<UnboundedReceiver<(RuntimeOrigin, ReputationChange)>>,
+}
+
+impl<B: BlockT, Transaction: Send + 'static> NimbusImportQueue<B, Transaction> {
+	pub(crate) fn new(
+		inner: BasicQueue<B, Transaction>,
+		origins: SharedBlockOrigins<B>,
+		seal_verification: SealVerification,
+		verified_seals: SharedVerifiedSeals<B>,
+		reputation_changes: UnboundedReceiver<(RuntimeOrigin, ReputationChange)>,
+	) -> Self {
+		let seal_verifier = match seal_verification {
+			SealVerification::Sequential => None,
+			SealVerification::Parallel { threads } => spawn_seal_verifier(threads),
+		};
+
+		Self {
+			service: PeerTrackingService {
+				inner: inner.service(),
+				origins: origins.clone(),
+				seal_verifier: seal_verifier.clone(),
+				verified_seals: verified_seals.clone(),
+			},
+			inner,
+			origins,
+			seal_verifier,
+			verified_seals,
+			reputation_changes: Some(reputation_changes),
+		}
+	}
+
+	/// The reputation changes of the peers that sent blocks which failed verification. They can
+	/// only be taken once, and should be passed to `report_peers`.
+	pub fn take_reputation_changes(
+		&mut self,
+	) -> Option<UnboundedReceiver<(RuntimeOrigin, ReputationChange)>> {
+		self.reputation_changes.take()
+	}
+}
+
+#[async_trait::async_trait]
+impl<B: BlockT, Transaction: Send + 'static> ImportQueue<B> for NimbusImportQueue<B, Transaction> {
+	fn service(&self) -> Box<dyn ImportQueueService<B>> {
+		Box::new(PeerTrackingService {
+			inner: self.inner.service(),
+			origins: self.origins.clone(),
+			seal_verifier: self.seal_verifier.clone(),
+			verified_seals: self.verified_seals.clone(),
+		})
+	}
+
+	fn service_ref(&mut self) -> &mut dyn ImportQueueService<B> {
+		&mut self.service
+	}
+
+	fn poll_actions(&mut self, cx: &mut futures::task::Context, link: &mut dyn Link<B>) {
+		self.inner.poll_actions(cx, link)
+	}
+
+	async fn run(self, link: Box<dyn Link<B>>) {
+		self.inner.run(link).await
+	}
+}
+
+/// Apply the reputation changes taken from a `NimbusImportQueue` to the network. The returned
+/// future completes when the import queue is dropped.
+pub async fn report_peers<N: NetworkPeers>(
+	reputation_changes: UnboundedReceiver<(RuntimeOrigin, ReputationChange)>,
+	network: Arc<N>,
+) {
+	reputation_changes
+		.for_each(|(peer, change)| {
+			network.report_peer(peer, change);
+			futures::future::ready(())
+		})
+		.await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::header;
+	use nimbus_primitives::{CompatibleDigestItem, NimbusPair};
+	use sp_application_crypto::Pair;
+	use sp_core::H256;
+	use sp_runtime::{
+		generic::DigestItem,
+		testing::{Block as TestBlock, ExtrinsicWrapper},
+	};
+
+	type Block = TestBlock<ExtrinsicWrapper<u64>>;
+
+	#[test]
+	fn origins_are_taken_once() {
+		let mut origins = QueuedBlocks::<Block, RuntimeOrigin>::default();
+		let peer = RuntimeOrigin::random();
+		origins.record(H256::repeat_byte(1), peer);
+
+		assert_eq!(origins.take(&H256::repeat_byte(1)), Some(peer));
+		assert_eq!(origins.take(&H256::repeat_byte(1)), None);
+	}
+
+	#[test]
+	fn oldest_origins_are_forgotten() {
+		let mut origins = QueuedBlocks::<Block, RuntimeOrigin>::default();
+		let peer = RuntimeOrigin::random();
+		for i in 0..=MAX_TRACKED_BLOCKS as u64 {
+			origins.record(H256::from_low_u64_be(i), peer);
+		}
+
+		assert_eq!(origins.take(&H256::from_low_u64_be(0)), None);
+		assert_eq!(
+			origins.take(&H256::from_low_u64_be(MAX_TRACKED_BLOCKS as u64)),
+			Some(peer)
+		);
+	}
+
+	#[test]
+	fn seal_verifier_reports_which_seals_are_valid() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let mut sealed = header(1, H256::zero());
+		sealed
+			.digest_mut()
+			.push(DigestItem::nimbus_pre_digest(alice.public()));
+		let signature = alice.sign(sealed.hash().as_ref());
+		sealed.digest_mut().push(DigestItem::nimbus_seal(signature));
+		let unsealed = header(2, H256::zero());
+
+		let seal_verifier = spawn_seal_verifier::<Block>(2).expect("The thread can be spawned");
+		let (sealed_result, sealed_receiver) = oneshot::channel();
+		let (unsealed_result, unsealed_receiver) = oneshot::channel();
+		seal_verifier
+			.send(vec![(sealed, sealed_result), (unsealed, unsealed_result)])
+			.unwrap();
+
+		assert_eq!(futures::executor::block_on(sealed_receiver), Ok(true));
+		assert_eq!(futures::executor::block_on(unsealed_receiver), Ok(false));
+	}
+}
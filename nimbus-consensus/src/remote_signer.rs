@@ -0,0 +1,583 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sealing blocks with keys held by an external signer.
+//!
+//! `RemoteSigner` implements `Keystore` for the nimbus key type by forwarding every request to a
+//! signer listening on a local Unix socket or over HTTP, so the nimbus private keys never have
+//! to be in the node's keystore. Pass it to the worker wherever a `KeystorePtr` is expected.
+//!
+//! The signer speaks a small JSON protocol: each connection carries one `SignerRequest` and
+//! one `SignerResponse`. Over a Unix socket, both are sent as a single line. Over HTTP, the
+//! request is the body of a `POST /` and the response is the body of the reply. Keys and
+//! signatures are SCALE-encoded `MultiNimbusId`s and `MultiNimbusSignature`s, in hex.
+//!
+//! Every request blocks until the signer answers or the retries run out, so the workers make
+//! them on a thread of their own rather than on the executor. The list of keys is cached for
+//! `RemoteSignerConfig::key_cache_duration`, so that listing the keys of each signature scheme
+//! when choosing the author of a slot costs a single round trip.
+//!
+//! The `nimbus-test-signer` binary is a stand-in signer for tests.
+
+use crate::LOG_TARGET;
+use codec::{Decode, Encode};
+use log::{debug, warn};
+use nimbus_primitives::{MultiNimbusId, MultiNimbusSignature, NimbusId, NIMBUS_KEY_ID};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sp_core::{
+	crypto::{ByteArray, KeyTypeId},
+	ecdsa, ed25519, sr25519, Bytes,
+};
+use sp_keystore::{Error as KeystoreError, Keystore};
+use std::{
+	fmt,
+	io::{self, BufRead, BufReader, Read, Write},
+	net::{SocketAddr, TcpStream},
+	os::unix::net::UnixStream,
+	path::{Path, PathBuf},
+	str::FromStr,
+	thread,
+	time::{Duration, Instant},
+};
+
+/// A request to the remote signer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "camelCase")]
+pub enum SignerRequest {
+	/// List the nimbus keys the signer holds.
+	PublicKeys,
+	/// Sign `message` with the key `public`.
+	Sign {
+		/// The SCALE-encoded `MultiNimbusId` to sign with
+		public: Bytes,
+		/// The message to sign
+		message: Bytes,
+	},
+}
+
+/// The remote signer's answer to a `SignerRequest`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignerResponse {
+	/// The SCALE-encoded `MultiNimbusId`s the signer holds
+	PublicKeys(Vec<Bytes>),
+	/// The SCALE-encoded `MultiNimbusSignature`
+	Signature(Bytes),
+	/// The signer does not hold the requested key
+	UnknownKey,
+	/// The signer could not handle the request
+	Error(String),
+}
+
+/// Where the remote signer listens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignerEndpoint {
+	/// A Unix socket at the given path
+	Unix(PathBuf),
+	/// An HTTP server at the given address
+	Http(SocketAddr),
+}
+
+impl FromStr for SignerEndpoint {
+	type Err = String;
+
+	/// Parses `unix:<path>` or `http://<ip>:<port>`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some(path) = s.strip_prefix("unix:") {
+			return Ok(Self::Unix(path.into()));
+		}
+		if let Some(address) = s.strip_prefix("http://") {
+			let address = address.trim_end_matches('/');
+			return address
+				.parse()
+				.map(Self::Http)
+				.map_err(|e| format!("invalid signer address {}: {}", address, e));
+		}
+
+		Err(format!(
+			"invalid signer endpoint {}, expected unix:<path> or http://<ip>:<port>",
+			s
+		))
+	}
+}
+
+impl fmt::Display for SignerEndpoint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Unix(path) => write!(f, "unix:{}", path.display()),
+			Self::Http(address) => write!(f, "http://{}", address),
+		}
+	}
+}
+
+/// How to reach the remote signer.
+#[derive(Clone, Debug)]
+pub struct RemoteSignerConfig {
+	/// Where the signer listens
+	pub endpoint: SignerEndpoint,
+	/// How long a single attempt may take to connect, send the request or read the response
+	pub timeout: Duration,
+	/// How many times a failed request is retried
+	pub retries: u32,
+	/// How long to wait before retrying
+	pub retry_delay: Duration,
+	/// How long the list of keys held by the signer is reused before it is requested again
+	pub key_cache_duration: Duration,
+}
+
+impl RemoteSignerConfig {
+	/// A config for the given endpoint, with a 1 second timeout and 2 retries 100ms apart. The
+	/// list of keys is cached for 1 second, which is shorter than a slot.
+	pub fn new(endpoint: SignerEndpoint) -> Self {
+		Self {
+			endpoint,
+			timeout: Duration::from_secs(1),
+			retries: 2,
+			retry_delay: Duration::from_millis(100),
+			key_cache_duration: Duration::from_secs(1),
+		}
+	}
+}
+
+/// Why a request to the remote signer failed.
+#[derive(Debug)]
+pub enum RemoteSignerError {
+	/// The signer could not be reached, or did not answer in time
+	Io(io::Error),
+	/// The signer answered with something other than a `SignerResponse`
+	InvalidResponse(String),
+	/// The signer could not handle the request
+	Signer(String),
+}
+
+impl fmt::Display for RemoteSignerError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "could not reach the remote signer: {}", e),
+			Self::InvalidResponse(e) => write!(f, "invalid response from the remote signer: {}", e),
+			Self::Signer(e) => write!(f, "the remote signer failed: {}", e),
+		}
+	}
+}
+
+impl From<io::Error> for RemoteSignerError {
+	fn from(e: io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+/// A `Keystore` whose nimbus keys are held by a remote signer.
+///
+/// Only the nimbus key type is supported: other key types have no keys, and keys cannot be
+/// generated or inserted. VRFs cannot be signed remotely, so the `VrfEligibilityProver` still
+/// requires a local keystore.
+pub struct RemoteSigner {
+	config: RemoteSignerConfig,
+	/// The keys last listed by the signer, and when they were listed
+	cached_keys: Mutex<Option<(Instant, Vec<MultiNimbusId>)>>,
+}
+
+impl RemoteSigner {
+	pub fn new(config: RemoteSignerConfig) -> Self {
+		Self {
+			config,
+			cached_keys: Mutex::new(None),
+		}
+	}
+
+	/// Send a request to the signer, retrying when it cannot be reached.
+	pub fn request(&self, request: &SignerRequest) -> Result<SignerResponse, RemoteSignerError> {
+		let mut attempt = 0;
+		loop {
+			match self.try_request(request) {
+				Ok(SignerResponse::Error(e)) => return Err(RemoteSignerError::Signer(e)),
+				Ok(response) => return Ok(response),
+				Err(e) if attempt < self.config.retries => {
+					attempt += 1;
+					debug!(
+						target: LOG_TARGET,
+						"🔏 Retrying remote signer request ({} of {}): {}",
+						attempt,
+						self.config.retries,
+						e
+					);
+					thread::sleep(self.config.retry_delay);
+				}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	fn try_request(&self, request: &SignerRequest) -> Result<SignerResponse, RemoteSignerError> {
+		let body = serde_json::to_vec(request).expect("Signer requests can always be serialized");
+		let response = match &self.config.endpoint {
+			SignerEndpoint::Unix(path) => self.unix_request(path, &body)?,
+			SignerEndpoint::Http(address) => self.http_request(address, &body)?,
+		};
+
+		serde_json::from_slice(&response)
+			.map_err(|e| RemoteSignerError::InvalidResponse(e.to_string()))
+	}
+
+	fn unix_request(&self, path: &Path, body: &[u8]) -> Result<Vec<u8>, RemoteSignerError> {
+		let mut stream = UnixStream::connect(path)?;
+		stream.set_read_timeout(Some(self.config.timeout))?;
+		stream.set_write_timeout(Some(self.config.timeout))?;
+
+		stream.write_all(body)?;
+		stream.write_all(b"\n")?;
+
+		let mut response = Vec::new();
+		BufReader::new(stream).read_until(b'\n', &mut response)?;
+
+		Ok(response)
+	}
+
+	fn http_request(
+		&self,
+		address: &SocketAddr,
+		body: &[u8],
+	) -> Result<Vec<u8>, RemoteSignerError> {
+		let mut stream = TcpStream::connect_timeout(address, self.config.timeout)?;
+		stream.set_read_timeout(Some(self.config.timeout))?;
+		stream.set_write_timeout(Some(self.config.timeout))?;
+
+		write!(
+			stream,
+			"POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+			Content-Length: {}\r\nConnection: close\r\n\r\n",
+			address,
+			body.len()
+		)?;
+		stream.write_all(body)?;
+
+		let mut response = Vec::new();
+		stream.read_to_end(&mut response)?;
+
+		let header_end = response
+			.windows(4)
+			.position(|window| window == b"\r\n\r\n")
+			.ok_or_else(|| RemoteSignerError::InvalidResponse("incomplete HTTP response".into()))?;
+		let status_line = response
+			.split(|byte| *byte == b'\n')
+			.next()
+			.map(String::from_utf8_lossy)
+			.unwrap_or_default();
+		if status_line.split_whitespace().nth(1) != Some("200") {
+			return Err(RemoteSignerError::InvalidResponse(format!(
+				"unexpected HTTP status: {}",
+				status_line.trim()
+			)));
+		}
+
+		Ok(response.split_off(header_end + 4))
+	}
+
+	/// The nimbus keys held by the signer, or none if it cannot be reached.
+	/// The keys are only requested again once the cached list is older than the configured
+	/// duration. Failures are not cached.
+	fn public_keys(&self) -> Vec<MultiNimbusId> {
+		let mut cached_keys = self.cached_keys.lock();
+		if let Some((listed_at, keys)) = &*cached_keys {
+			if listed_at.elapsed() < self.config.key_cache_duration {
+				return keys.clone();
+			}
+		}
+
+		let keys = match self.request(&SignerRequest::PublicKeys) {
+			Ok(SignerResponse::PublicKeys(keys)) => keys,
+			Ok(response) => {
+				warn!(
+					target: LOG_TARGET,
+					"🔏 Unexpected answer to a public keys request from the remote signer: {:?}",
+					response
+				);
+				return Vec::new();
+			}
+			Err(e) => {
+				warn!(
+					target: LOG_TARGET,
+					"🔏 Could not list the remote nimbus keys: {}", e
+				);
+				return Vec::new();
+			}
+		};
+
+		let keys: Vec<_> = keys
+			.into_iter()
+			.filter_map(|key| MultiNimbusId::decode(&mut &key[..]).ok())
+			.collect();
+		*cached_keys = Some((Instant::now(), keys.clone()));
+
+		keys
+	}
+
+	/// Sign `message` with the remote key `public`.
+	fn sign(
+		&self,
+		public: MultiNimbusId,
+		message: &[u8],
+	) -> Result<Option<MultiNimbusSignature>, KeystoreError> {
+		let request = SignerRequest::Sign {
+			public: public.encode().into(),
+			message: message.to_vec().into(),
+		};
+
+		match self.request(&request) {
+			Ok(SignerResponse::Signature(signature)) => {
+				MultiNimbusSignature::decode(&mut &signature[..])
+					.map(Some)
+					.map_err(|e| KeystoreError::Other(format!("invalid remote signature: {}", e)))
+			}
+			Ok(SignerResponse::UnknownKey) => Ok(None),
+			Ok(response) => Err(KeystoreError::Other(format!(
+				"unexpected answer to a signing request from the remote signer: {:?}",
+				response
+			))),
+			Err(e) => Err(KeystoreError::Other(e.to_string())),
+		}
+	}
+}
+
+impl Keystore for RemoteSigner {
+	fn sr25519_public_keys(&self, key_type: KeyTypeId) -> Vec<sr25519::Public> {
+		if key_type != NIMBUS_KEY_ID {
+			return Vec::new();
+		}
+
+		self.public_keys()
+			.into_iter()
+			.filter_map(|key| match key {
+				MultiNimbusId::Sr25519(public) => Some(public.into()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn sr25519_generate_new(
+		&self,
+		_key_type: KeyTypeId,
+		_seed: Option<&str>,
+	) -> Result<sr25519::Public, KeystoreError> {
+		Err(KeystoreError::Unavailable)
+	}
+
+	fn sr25519_sign(
+		&self,
+		key_type: KeyTypeId,
+		public: &sr25519::Public,
+		msg: &[u8],
+	) -> Result<Option<sr25519::Signature>, KeystoreError> {
+		if key_type != NIMBUS_KEY_ID {
+			return Ok(None);
+		}
+
+		match self.sign(NimbusId::from(*public).into(), msg)? {
+			Some(MultiNimbusSignature::Sr25519(signature)) => Ok(Some(signature.into())),
+			Some(_) => Err(KeystoreError::Other(
+				"remote signature in the wrong scheme".into(),
+			)),
+			None => Ok(None),
+		}
+	}
+
+	fn sr25519_vrf_sign(
+		&self,
+		_key_type: KeyTypeId,
+		_public: &sr25519::Public,
+		_data: &sr25519::vrf::VrfSignData,
+	) -> Result<Option<sr25519::vrf::VrfSignature>, KeystoreError> {
+		Ok(None)
+	}
+
+	fn ed25519_public_keys(&self, key_type: KeyTypeId) -> Vec<ed25519::Public> {
+		if key_type != NIMBUS_KEY_ID {
+			return Vec::new();
+		}
+
+		self.public_keys()
+			.into_iter()
+			.filter_map(|key| match key {
+				MultiNimbusId::Ed25519(public) => Some(public.into()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn ed25519_generate_new(
+		&self,
+		_key_type: KeyTypeId,
+		_seed: Option<&str>,
+	) -> Result<ed25519::Public, KeystoreError> {
+		Err(KeystoreError::Unavailable)
+	}
+
+	fn ed25519_sign(
+		&self,
+		key_type: KeyTypeId,
+		public: &ed25519::Public,
+		msg: &[u8],
+	) -> Result<Option<ed25519::Signature>, KeystoreError> {
+		if key_type != NIMBUS_KEY_ID {
+			return Ok(None);
+		}
+
+		match self.sign(MultiNimbusId::Ed25519((*public).into()), msg)? {
+			Some(MultiNimbusSignature::Ed25519(signature)) => Ok(Some(signature.into())),
+			Some(_) => Err(KeystoreError::Other(
+				"remote signature in the wrong scheme".into(),
+			)),
+			None => Ok(None),
+		}
+	}
+
+	fn ecdsa_public_keys(&self, key_type: KeyTypeId) -> Vec<ecdsa::Public> {
+		if key_type != NIMBUS_KEY_ID {
+			return Vec::new();
+		}
+
+		self.public_keys()
+			.into_iter()
+			.filter_map(|key| match key {
+				MultiNimbusId::Ecdsa(public) => Some(public.into()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn ecdsa_generate_new(
+		&self,
+		_key_type: KeyTypeId,
+		_seed: Option<&str>,
+	) -> Result<ecdsa::Public, KeystoreError> {
+		Err(KeystoreError::Unavailable)
+	}
+
+	fn ecdsa_sign(
+		&self,
+		key_type: KeyTypeId,
+		public: &ecdsa::Public,
+		msg: &[u8],
+	) -> Result<Option<ecdsa::Signature>, KeystoreError> {
+		if key_type != NIMBUS_KEY_ID {
+			return Ok(None);
+		}
+
+		match self.sign(MultiNimbusId::Ecdsa((*public).into()), msg)? {
+			Some(MultiNimbusSignature::Ecdsa(signature)) => Ok(Some(signature.into())),
+			Some(_) => Err(KeystoreError::Other(
+				"remote signature in the wrong scheme".into(),
+			)),
+			None => Ok(None),
+		}
+	}
+
+	fn ecdsa_sign_prehashed(
+		&self,
+		_key_type: KeyTypeId,
+		_public: &ecdsa::Public,
+		_msg: &[u8; 32],
+	) -> Result<Option<ecdsa::Signature>, KeystoreError> {
+		Ok(None)
+	}
+
+	fn insert(&self, _key_type: KeyTypeId, _suri: &str, _public: &[u8]) -> Result<(), ()> {
+		Err(())
+	}
+
+	fn keys(&self, key_type: KeyTypeId) -> Result<Vec<Vec<u8>>, KeystoreError> {
+		if key_type != NIMBUS_KEY_ID {
+			return Ok(Vec::new());
+		}
+
+		Ok(self
+			.public_keys()
+			.into_iter()
+			.map(|key| match key {
+				MultiNimbusId::Sr25519(public) => public.to_raw_vec(),
+				MultiNimbusId::Ed25519(public) => public.to_raw_vec(),
+				MultiNimbusId::Ecdsa(public) => public.to_raw_vec(),
+			})
+			.collect())
+	}
+
+	fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
+		let keys = match self.keys(NIMBUS_KEY_ID) {
+			Ok(keys) => keys,
+			Err(_) => return false,
+		};
+
+		public_keys
+			.iter()
+			.all(|(public, key_type)| *key_type == NIMBUS_KEY_ID && keys.contains(public))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn endpoints_round_trip() {
+		for endpoint in ["unix:/tmp/nimbus-signer.sock", "http://127.0.0.1:9955"] {
+			assert_eq!(
+				endpoint.parse::<SignerEndpoint>().unwrap().to_string(),
+				endpoint
+			);
+		}
+	}
+
+	#[test]
+	fn invalid_endpoints_are_rejected() {
+		assert!("/tmp/nimbus-signer.sock".parse::<SignerEndpoint>().is_err());
+		assert!("http://localhost".parse::<SignerEndpoint>().is_err());
+	}
+
+	#[test]
+	fn requests_use_the_documented_encoding() {
+		let request = SignerRequest::Sign {
+			public: vec![1].into(),
+			message: vec![2, 3].into(),
+		};
+
+		assert_eq!(
+			serde_json::to_string(&request).unwrap(),
+			r#"{"method":"sign","public":"0x01","message":"0x0203"}"#
+		);
+		assert_eq!(
+			serde_json::to_string(&SignerRequest::PublicKeys).unwrap(),
+			r#"{"method":"publicKeys"}"#
+		);
+	}
+
+	#[test]
+	fn unreachable_signer_fails_after_retries() {
+		let signer = RemoteSigner::new(RemoteSignerConfig {
+			endpoint: SignerEndpoint::Unix("/nonexistent/nimbus-signer.sock".into()),
+			timeout: Duration::from_millis(10),
+			retries: 2,
+			retry_delay: Duration::from_millis(1),
+			key_cache_duration: Duration::from_secs(1),
+		});
+
+		assert!(matches!(
+			signer.request(&SignerRequest::PublicKeys),
+			Err(RemoteSignerError::Io(_))
+		));
+		assert!(Keystore::sr25519_public_keys(&signer, NIMBUS_KEY_ID).is_empty());
+	}
+}
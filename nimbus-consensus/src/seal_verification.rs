@@ -0,0 +1,126 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Verifying the seals of many headers at once.
+//!
+//! During initial sync the import queue receives blocks in chunks, and checking each seal in the
+//! verifier, one block after the other, leaves all but one core idle. In the `Parallel` mode the
+//! seals of each chunk of initial sync blocks are handed to a dedicated thread as soon as the chunk
+//! is queued, which checks them on several threads. The verifier waits for the result of each
+//! block and only strips the seals that passed. Seals that fail,
+//! and blocks from any other origin, are verified in the verifier as usual, so the errors and
+//! reputation changes are the same in both modes.
+
+use nimbus_primitives::{verify_seal, MultiNimbusId, SealError};
+use sp_runtime::traits::Header as HeaderT;
+use std::{num::NonZeroUsize, thread};
+
+/// How the import queue verifies the seals of initial sync blocks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SealVerification {
+	/// Each seal is verified by the verifier, one block after the other.
+	#[default]
+	Sequential,
+	/// The seals of each chunk of initial sync blocks are verified on up to `threads` threads by a
+	/// dedicated thread, while the blocks wait for the verifier.
+	Parallel { threads: usize },
+}
+
+impl SealVerification {
+	/// Verify seals in parallel on as many threads as the machine can run at once.
+	pub fn parallel() -> Self {
+		Self::Parallel {
+			threads: thread::available_parallelism()
+				.map(NonZeroUsize::get)
+				.unwrap_or(1),
+		}
+	}
+}
+
+/// Verify the seals of `headers` on up to `threads` threads, and return the author of each header
+/// or the reason its seal is invalid, in the order of the headers.
+pub fn verify_seals<H: HeaderT>(
+	headers: &[H],
+	threads: usize,
+) -> Vec<Result<MultiNimbusId, SealError>> {
+	if threads <= 1 || headers.len() < 2 {
+		return headers.iter().map(verify_seal).collect();
+	}
+
+	let chunk_size = (headers.len() + threads - 1) / threads;
+	thread::scope(|scope| {
+		let workers: Vec<_> = headers
+			.chunks(chunk_size)
+			.map(|chunk| scope.spawn(move || chunk.iter().map(verify_seal).collect::<Vec<_>>()))
+			.collect();
+
+		workers
+			.into_iter()
+			.flat_map(|worker| worker.join().expect("Verifying a seal does not panic; qed"))
+			.collect()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::header;
+	use nimbus_primitives::{CompatibleDigestItem, NimbusPair};
+	use sp_application_crypto::Pair;
+	use sp_core::H256;
+	use sp_runtime::{generic::DigestItem, testing::Header};
+
+	fn sealed_headers(count: u64) -> Vec<Header> {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		(0..count)
+			.map(|number| {
+				let mut header = header(number, H256::zero());
+				header
+					.digest_mut()
+					.push(DigestItem::nimbus_pre_digest(alice.public()));
+				let signature = alice.sign(header.hash().as_ref());
+				header.digest_mut().push(DigestItem::nimbus_seal(signature));
+				header
+			})
+			.collect()
+	}
+
+	#[test]
+	fn parallel_verification_matches_sequential_verification() {
+		let mut headers = sealed_headers(10);
+		// Forge the seal of one header, and leave another one unsealed.
+		headers[3].digest_mut().pop();
+		let forged = NimbusPair::from_seed(&[2; 32]).sign(headers[3].hash().as_ref());
+		headers[3]
+			.digest_mut()
+			.push(DigestItem::nimbus_seal(forged));
+		headers[7].digest_mut().pop();
+
+		let sequential = verify_seals(&headers, 1);
+		assert_eq!(sequential[3], Err(SealError::BadSignature));
+		assert_eq!(sequential[7], Err(SealError::HeaderUnsealed));
+		assert_eq!(sequential.iter().filter(|result| result.is_ok()).count(), 8);
+
+		for threads in [2, 3, 4, 16] {
+			assert_eq!(verify_seals(&headers, threads), sequential);
+		}
+	}
+
+	#[test]
+	fn no_headers_need_no_threads() {
+		assert!(verify_seals::<Header>(&[], 4).is_empty());
+	}
+}
@@ -0,0 +1,558 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Protection against signing two conflicting blocks in the same slot with the same key.
+//!
+//! Before the worker seals a block, it records the author's key, the slot and the block's
+//! pre-hash in a `SlashingProtector`. A block that conflicts with one already signed is refused,
+//! so a restarted collator, or a second collator sharing the same database, cannot equivocate.
+//! `SlashingProtectionDb` keeps these records in the client's aux storage.
+//!
+//! To move a key to another machine, export the records of the old node and import them into
+//! the new one before it starts authoring. The records are exchanged as JSON:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "keys": [
+//!     {
+//!       "key": "0x00d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d",
+//!       "lowWatermark": 1200,
+//!       "signedBlocks": [
+//!         { "slot": 1201, "blockHash": "0x3f9a…" },
+//!         { "slot": 1202, "blockHash": "0x84c1…" }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `key` is the SCALE-encoded `MultiNimbusId` in hex, and `blockHash` is the pre-hash of the
+//! header that was signed, that is its hash without the seal. Slots up to and including
+//! `lowWatermark` are refused altogether, because their records have been pruned. It is `null`
+//! when nothing was pruned. Importing merges the records with the existing ones, so it never
+//! allows a signature that was refused before.
+
+use codec::{Decode, Encode};
+use nimbus_primitives::MultiNimbusId;
+use parking_lot::Mutex;
+use sc_client_api::backend::AuxStore;
+use serde::{Deserialize, Serialize};
+use sp_blockchain::Error as ClientError;
+use sp_core::Bytes;
+use std::{fmt, sync::Arc};
+
+/// The key under which the list of protected keys is stored.
+const PROTECTED_KEYS_KEY: &[u8] = b"nimbus_slashing_protection_keys";
+/// The prefix of the keys under which the signing history of each key is stored.
+const KEY_HISTORY_PREFIX: &[u8] = b"nimbus_slashing_protection_history";
+
+/// The version of the JSON interchange format.
+pub const INTERCHANGE_VERSION: u32 = 1;
+/// The number of most recent signed blocks that are kept for each key.
+pub const MAX_SIGNED_BLOCKS_PER_KEY: usize = 1000;
+
+/// Why a signature was refused, or the records could not be read or written.
+#[derive(Debug)]
+pub enum SlashingProtectionError {
+	/// The key already signed as many other blocks in this slot as it may
+	Conflict {
+		slot: u32,
+		/// The pre-hash of a block already signed in the slot
+		signed: Vec<u8>,
+	},
+	/// The records of this slot have been pruned, so it cannot be signed in safely
+	SlotPruned { slot: u32, low_watermark: u32 },
+	/// The records could not be read or written
+	Client(ClientError),
+	/// The interchange data is malformed, or of an unsupported version
+	InvalidInterchange(String),
+}
+
+impl fmt::Display for SlashingProtectionError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Conflict { slot, signed } => write!(
+				f,
+				"a conflicting block 0x{} was already signed in slot {}",
+				sp_core::hexdisplay::HexDisplay::from(signed),
+				slot
+			),
+			Self::SlotPruned {
+				slot,
+				low_watermark,
+			} => write!(
+				f,
+				"slot {} is at or below the low watermark {}",
+				slot, low_watermark
+			),
+			Self::Client(e) => write!(f, "could not access the slashing protection records: {}", e),
+			Self::InvalidInterchange(e) => {
+				write!(f, "invalid slashing protection interchange: {}", e)
+			}
+		}
+	}
+}
+
+impl std::error::Error for SlashingProtectionError {}
+
+impl From<ClientError> for SlashingProtectionError {
+	fn from(e: ClientError) -> Self {
+		Self::Client(e)
+	}
+}
+
+/// Checks and records the blocks an author signs.
+pub trait SlashingProtector {
+	/// Record that `author` is about to sign the block with pre-hash `block_hash` in `slot`, or
+	/// refuse if that would conflict with the blocks it already signed. Signing the same block
+	/// again is allowed.
+	fn check_and_record(
+		&self,
+		author: &MultiNimbusId,
+		slot: u32,
+		block_hash: &[u8],
+	) -> Result<(), SlashingProtectionError>;
+}
+
+/// No protection: every signature is allowed.
+impl SlashingProtector for () {
+	fn check_and_record(
+		&self,
+		_author: &MultiNimbusId,
+		_slot: u32,
+		_block_hash: &[u8],
+	) -> Result<(), SlashingProtectionError> {
+		Ok(())
+	}
+}
+
+/// A block signed by a key.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+struct SignedBlock {
+	slot: u32,
+	block_hash: Vec<u8>,
+}
+
+/// The blocks signed by a key, sorted by slot.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode)]
+struct KeyHistory {
+	low_watermark: Option<u32>,
+	signed_blocks: Vec<SignedBlock>,
+}
+
+impl KeyHistory {
+	/// Add a block unless it is already recorded, and prune the oldest blocks.
+	fn insert(&mut self, block: SignedBlock) {
+		if self.signed_blocks.contains(&block) {
+			return;
+		}
+		let position = self
+			.signed_blocks
+			.partition_point(|signed| signed.slot <= block.slot);
+		self.signed_blocks.insert(position, block);
+
+		let excess = self
+			.signed_blocks
+			.len()
+			.saturating_sub(MAX_SIGNED_BLOCKS_PER_KEY);
+		if excess > 0 {
+			let pruned_slot = self.signed_blocks[excess - 1].slot;
+			self.signed_blocks.drain(..excess);
+			self.low_watermark = self.low_watermark.max(Some(pruned_slot));
+		}
+	}
+}
+
+/// A `SlashingProtector` that keeps its records in the client's aux storage.
+pub struct SlashingProtectionDb<C> {
+	backend: Arc<C>,
+	blocks_per_slot: u32,
+	lock: Mutex<()>,
+}
+
+impl<C: AuxStore> SlashingProtectionDb<C> {
+	/// Protect the keys with records stored in `backend`. A key may sign up to
+	/// `blocks_per_slot` different blocks in the same slot, which should be 1 for the nimbus
+	/// workers, as they author at most one block per slot.
+	pub fn new(backend: Arc<C>, blocks_per_slot: u32) -> Self {
+		Self {
+			backend,
+			blocks_per_slot: blocks_per_slot.max(1),
+			lock: Mutex::new(()),
+		}
+	}
+
+	fn load<T: Decode>(&self, key: &[u8]) -> Result<Option<T>, SlashingProtectionError> {
+		match self.backend.get_aux(key)? {
+			None => Ok(None),
+			Some(t) => T::decode(&mut &t[..])
+				.map_err(|e| {
+					ClientError::Backend(format!(
+						"Nimbus slashing protection DB is corrupted. Decode error: {}",
+						e
+					))
+					.into()
+				})
+				.map(Some),
+		}
+	}
+
+	fn protected_keys(&self) -> Result<Vec<MultiNimbusId>, SlashingProtectionError> {
+		Ok(self.load(PROTECTED_KEYS_KEY)?.unwrap_or_default())
+	}
+
+	fn history(&self, author: &MultiNimbusId) -> Result<KeyHistory, SlashingProtectionError> {
+		Ok(self.load(&history_key(author))?.unwrap_or_default())
+	}
+
+	/// Store the history of `author`, and add it to the protected keys if needed.
+	fn store(
+		&self,
+		author: &MultiNimbusId,
+		history: &KeyHistory,
+	) -> Result<(), SlashingProtectionError> {
+		let mut keys = self.protected_keys()?;
+		let history_key = history_key(author);
+		let encoded_history = history.encode();
+		if keys.contains(author) {
+			self.backend
+				.insert_aux(&[(&history_key[..], &encoded_history[..])], &[])?;
+		} else {
+			keys.push(author.clone());
+			self.backend.insert_aux(
+				&[
+					(&history_key[..], &encoded_history[..]),
+					(PROTECTED_KEYS_KEY, &keys.encode()[..]),
+				],
+				&[],
+			)?;
+		}
+
+		Ok(())
+	}
+
+	/// All the records, in the interchange format.
+	pub fn export(&self) -> Result<Interchange, SlashingProtectionError> {
+		let _guard = self.lock.lock();
+		let keys = self
+			.protected_keys()?
+			.into_iter()
+			.map(|author| {
+				let history = self.history(&author)?;
+				Ok(InterchangeKey {
+					key: author.encode().into(),
+					low_watermark: history.low_watermark,
+					signed_blocks: history
+						.signed_blocks
+						.into_iter()
+						.map(|block| InterchangeBlock {
+							slot: block.slot,
+							block_hash: block.block_hash.into(),
+						})
+						.collect(),
+				})
+			})
+			.collect::<Result<_, SlashingProtectionError>>()?;
+
+		Ok(Interchange {
+			version: INTERCHANGE_VERSION,
+			keys,
+		})
+	}
+
+	/// Merge records exported from another node into the existing ones.
+	pub fn import(&self, interchange: &Interchange) -> Result<(), SlashingProtectionError> {
+		if interchange.version != INTERCHANGE_VERSION {
+			return Err(SlashingProtectionError::InvalidInterchange(format!(
+				"unsupported version {}, expected {}",
+				interchange.version, INTERCHANGE_VERSION
+			)));
+		}
+
+		let _guard = self.lock.lock();
+		for imported in &interchange.keys {
+			let author = MultiNimbusId::decode(&mut &imported.key[..]).map_err(|e| {
+				SlashingProtectionError::InvalidInterchange(format!(
+					"invalid key {}: {}",
+					sp_core::hexdisplay::HexDisplay::from(&imported.key.0),
+					e
+				))
+			})?;
+
+			let mut history = self.history(&author)?;
+			history.low_watermark = history.low_watermark.max(imported.low_watermark);
+			for block in &imported.signed_blocks {
+				history.insert(SignedBlock {
+					slot: block.slot,
+					block_hash: block.block_hash.to_vec(),
+				});
+			}
+			self.store(&author, &history)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl<C: AuxStore> SlashingProtector for SlashingProtectionDb<C> {
+	fn check_and_record(
+		&self,
+		author: &MultiNimbusId,
+		slot: u32,
+		block_hash: &[u8],
+	) -> Result<(), SlashingProtectionError> {
+		let _guard = self.lock.lock();
+		let mut history = self.history(author)?;
+
+		if let Some(low_watermark) = history.low_watermark.filter(|w| slot <= *w) {
+			return Err(SlashingProtectionError::SlotPruned {
+				slot,
+				low_watermark,
+			});
+		}
+
+		let mut signed_in_slot = history
+			.signed_blocks
+			.iter()
+			.filter(|signed| signed.slot == slot);
+		if signed_in_slot
+			.clone()
+			.any(|signed| signed.block_hash == block_hash)
+		{
+			return Ok(());
+		}
+		if signed_in_slot.clone().count() >= self.blocks_per_slot as usize {
+			return Err(SlashingProtectionError::Conflict {
+				slot,
+				signed: signed_in_slot
+					.next()
+					.map(|signed| signed.block_hash.clone())
+					.unwrap_or_default(),
+			});
+		}
+
+		history.insert(SignedBlock {
+			slot,
+			block_hash: block_hash.to_vec(),
+		});
+		self.store(author, &history)
+	}
+}
+
+fn history_key(author: &MultiNimbusId) -> Vec<u8> {
+	let mut key = KEY_HISTORY_PREFIX.to_vec();
+	author.using_encoded(|a| key.extend(a));
+	key
+}
+
+/// The slashing protection records of several keys, as exchanged between nodes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interchange {
+	/// The version of the format, currently `INTERCHANGE_VERSION`
+	pub version: u32,
+	/// The records of each key
+	pub keys: Vec<InterchangeKey>,
+}
+
+impl Interchange {
+	/// Parse the interchange from its JSON form.
+	pub fn from_json(json: &str) -> Result<Self, SlashingProtectionError> {
+		serde_json::from_str(json)
+			.map_err(|e| SlashingProtectionError::InvalidInterchange(e.to_string()))
+	}
+
+	/// The JSON form of the interchange.
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("the interchange can always be serialized; qed")
+	}
+}
+
+/// The slashing protection records of one key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterchangeKey {
+	/// The SCALE-encoded `MultiNimbusId`
+	pub key: Bytes,
+	/// Slots up to and including this one must not be signed in
+	pub low_watermark: Option<u32>,
+	/// The blocks the key signed
+	pub signed_blocks: Vec<InterchangeBlock>,
+}
+
+/// A block signed by a key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterchangeBlock {
+	/// The slot the block was authored in
+	pub slot: u32,
+	/// The pre-hash of the block's header
+	pub block_hash: Bytes,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use nimbus_primitives::NimbusId;
+	use sp_core::sr25519;
+	use std::collections::HashMap;
+
+	/// Aux storage held in memory.
+	#[derive(Default)]
+	pub(crate) struct MemoryAuxStore(Mutex<HashMap<Vec<u8>, Vec<u8>>>);
+
+	impl AuxStore for MemoryAuxStore {
+		fn insert_aux<
+			'a,
+			'b: 'a,
+			'c: 'a,
+			I: IntoIterator<Item = &'a (&'c [u8], &'c [u8])>,
+			D: IntoIterator<Item = &'a &'b [u8]>,
+		>(
+			&self,
+			insert: I,
+			delete: D,
+		) -> sp_blockchain::Result<()> {
+			let mut storage = self.0.lock();
+			for (key, value) in insert {
+				storage.insert(key.to_vec(), value.to_vec());
+			}
+			for key in delete {
+				storage.remove(*key);
+			}
+			Ok(())
+		}
+
+		fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+			Ok(self.0.lock().get(key).cloned())
+		}
+	}
+
+	fn author(seed: u8) -> MultiNimbusId {
+		NimbusId::from(sr25519::Public::from_raw([seed; 32])).into()
+	}
+
+	fn db(blocks_per_slot: u32) -> SlashingProtectionDb<MemoryAuxStore> {
+		SlashingProtectionDb::new(Arc::new(MemoryAuxStore::default()), blocks_per_slot)
+	}
+
+	#[test]
+	fn conflicting_blocks_are_refused() {
+		let db = db(1);
+
+		assert!(db.check_and_record(&author(1), 10, &[1]).is_ok());
+		assert!(matches!(
+			db.check_and_record(&author(1), 10, &[2]),
+			Err(SlashingProtectionError::Conflict { slot: 10, signed }) if signed == vec![1]
+		));
+	}
+
+	#[test]
+	fn the_same_block_may_be_signed_again() {
+		let db = db(1);
+
+		assert!(db.check_and_record(&author(1), 10, &[1]).is_ok());
+		assert!(db.check_and_record(&author(1), 10, &[1]).is_ok());
+	}
+
+	#[test]
+	fn other_keys_and_slots_do_not_conflict() {
+		let db = db(1);
+
+		assert!(db.check_and_record(&author(1), 10, &[1]).is_ok());
+		assert!(db.check_and_record(&author(2), 10, &[2]).is_ok());
+		assert!(db.check_and_record(&author(1), 11, &[3]).is_ok());
+	}
+
+	#[test]
+	fn several_blocks_per_slot_are_allowed_up_to_the_limit() {
+		let db = db(2);
+
+		assert!(db.check_and_record(&author(1), 10, &[1]).is_ok());
+		assert!(db.check_and_record(&author(1), 10, &[2]).is_ok());
+		assert!(db.check_and_record(&author(1), 10, &[3]).is_err());
+	}
+
+	#[test]
+	fn pruned_slots_are_refused() {
+		let db = db(1);
+		for slot in 0..=MAX_SIGNED_BLOCKS_PER_KEY as u32 {
+			assert!(db.check_and_record(&author(1), slot, &[1]).is_ok());
+		}
+
+		assert!(matches!(
+			db.check_and_record(&author(1), 0, &[1]),
+			Err(SlashingProtectionError::SlotPruned {
+				slot: 0,
+				low_watermark: 0
+			})
+		));
+		assert!(db
+			.check_and_record(&author(1), MAX_SIGNED_BLOCKS_PER_KEY as u32 + 1, &[1])
+			.is_ok());
+	}
+
+	#[test]
+	fn exported_records_protect_the_importing_node() {
+		let old_node = db(1);
+		assert!(old_node.check_and_record(&author(1), 10, &[1]).is_ok());
+		let json = old_node.export().unwrap().to_json();
+
+		let new_node = db(1);
+		assert!(new_node.check_and_record(&author(1), 11, &[2]).is_ok());
+		new_node
+			.import(&Interchange::from_json(&json).unwrap())
+			.unwrap();
+
+		assert!(new_node.check_and_record(&author(1), 10, &[3]).is_err());
+		assert!(new_node.check_and_record(&author(1), 11, &[4]).is_err());
+		assert_eq!(new_node.export().unwrap().keys[0].signed_blocks.len(), 2);
+	}
+
+	#[test]
+	fn interchange_uses_the_documented_encoding() {
+		let interchange = Interchange {
+			version: INTERCHANGE_VERSION,
+			keys: vec![InterchangeKey {
+				key: vec![0, 1].into(),
+				low_watermark: None,
+				signed_blocks: vec![InterchangeBlock {
+					slot: 5,
+					block_hash: vec![2].into(),
+				}],
+			}],
+		};
+
+		assert_eq!(
+			serde_json::to_string(&interchange).unwrap(),
+			r#"{"version":1,"keys":[{"key":"0x0001","lowWatermark":null,"signedBlocks":[{"slot":5,"blockHash":"0x02"}]}]}"#
+		);
+	}
+
+	#[test]
+	fn unsupported_versions_are_rejected() {
+		let interchange = Interchange {
+			version: 2,
+			keys: Vec::new(),
+		};
+
+		assert!(matches!(
+			db(1).import(&interchange),
+			Err(SlashingProtectionError::InvalidInterchange(_))
+		));
+	}
+}
@@ -0,0 +1,355 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A timer-driven nimbus worker for chains that are not parachains.
+//!
+//! Solochains and test networks have no relay chain to drive authoring. This worker divides time
+//! into slots of a fixed duration instead, and in each slot it authors on top of the best block
+//! if one of its keys is eligible, exactly like the parachain worker does in each relay block.
+//! The slot number is the number of whole slots since a genesis time, so the runtime's
+//! `SlotBeacon` must derive the same number. `IntervalBeacon` with the same slot duration and
+//! genesis time does.
+
+use crate::{
+	nimbus_keys_off_executor, pre_digest, seal_header_off_executor, select_author_key,
+	EligibilityProver, KeySelector, SlashingProtector, LOG_TARGET,
+};
+use futures_timer::Delay;
+use log::{debug, error, info, warn};
+use nimbus_primitives::{CompatibleDigestItem, DigestsProvider, MultiNimbusId, NimbusApi};
+use sc_consensus::{
+	BlockImport, BlockImportParams, ForkChoiceStrategy, StateAction, StorageChanges,
+};
+use sp_api::ProvideRuntimeApi;
+use sp_consensus::{BlockOrigin, Environment, Proposal, Proposer, SelectChain, SyncOracle};
+use sp_inherents::{CreateInherentDataProviders, InherentDataProvider};
+use sp_keystore::KeystorePtr;
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT},
+	Digest,
+};
+use std::{
+	sync::Arc,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Parameters of `start_nimbus_standalone`.
+pub struct StandaloneNimbusParams<C, SC, PF, BI, CIDP, SO, DP = (), EP = (), KS = (), SP = ()> {
+	/// The client, to ask the runtime whether our keys may author
+	pub client: Arc<C>,
+	/// Chooses the block to author on top of
+	pub select_chain: SC,
+	pub proposer_factory: PF,
+	/// Imports the authored blocks with the longest chain rule, unless it is a `NimbusBlockImport`
+	/// with another fork choice rule
+	pub block_import: BI,
+	/// Creates the inherent data providers for a block, given its slot and author
+	pub create_inherent_data_providers: CIDP,
+	/// No blocks are authored while the oracle reports a major sync
+	pub sync_oracle: SO,
+	pub keystore: KeystorePtr,
+	/// The duration of a slot, at least one millisecond
+	pub slot_duration: Duration,
+	/// When slot zero started, as a duration since the unix epoch
+	pub genesis_time: Duration,
+	/// How long the proposer may spend building a block. It should be well below the slot duration.
+	pub proposal_duration: Duration,
+	/// The maximum size of a block in bytes, or `None` for no limit
+	pub block_size_limit: Option<usize>,
+	/// Author with the first available key, without asking the runtime whether it is eligible
+	pub skip_prediction: bool,
+	pub additional_digests_provider: DP,
+	/// Proves eligibility for filters that cannot be checked through `NimbusApi::can_author`.
+	/// Use `()` when the runtime has no such filter.
+	pub eligibility_prover: EP,
+	/// Chooses which key authors when several keys may author. Use `()` to author with the
+	/// first eligible key.
+	pub key_selector: KS,
+	/// Refuses to seal blocks that conflict with blocks already signed. Use `()` for no
+	/// protection.
+	pub slashing_protection: SP,
+}
+
+/// The slot in progress at `now`, a duration since the genesis time, or `None` once the slot
+/// number no longer fits in a `u32`. With one millisecond slots, that is about 50 days after the
+/// genesis time. The slot duration must be at least one millisecond.
+fn slot_at(now: Duration, slot_duration: Duration) -> Option<u32> {
+	u32::try_from(now.as_millis() / slot_duration.as_millis()).ok()
+}
+
+/// How long after `now`, a duration since the genesis time, the next slot starts. The slot
+/// duration must be at least one millisecond.
+fn time_until_next_slot(now: Duration, slot_duration: Duration) -> Duration {
+	let slot_duration = slot_duration.as_millis();
+	let remaining = slot_duration - now.as_millis() % slot_duration;
+	Duration::from_millis(u64::try_from(remaining).unwrap_or(u64::MAX))
+}
+
+/// The time elapsed since the genesis time, or zero before it.
+fn since_genesis(genesis_time: Duration) -> Duration {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.expect("The current time is after the unix epoch")
+		.saturating_sub(genesis_time)
+}
+
+/// Run the standalone nimbus worker. The returned future never completes, and is usually
+/// spawned as an essential task.
+pub async fn start_nimbus_standalone<B, C, SC, PF, BI, CIDP, SO, DP, EP, KS, SP>(
+	params: StandaloneNimbusParams<C, SC, PF, BI, CIDP, SO, DP, EP, KS, SP>,
+) where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + Send + Sync + 'static,
+	C::Api: NimbusApi<B>,
+	SC: SelectChain<B>,
+	PF: Environment<B> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Transaction = BI::Transaction>,
+	BI: BlockImport<B> + Send + Sync + 'static,
+	CIDP: CreateInherentDataProviders<B, (u32, MultiNimbusId)> + 'static,
+	SO: SyncOracle + Send + Sync,
+	DP: DigestsProvider<MultiNimbusId, B::Hash>,
+	EP: EligibilityProver<B>,
+	KS: KeySelector,
+	SP: SlashingProtector,
+{
+	assert!(
+		params.slot_duration.as_millis() > 0,
+		"The nimbus slot duration must be at least one millisecond"
+	);
+
+	let mut params = params;
+	let mut last_slot = None;
+	loop {
+		let now = since_genesis(params.genesis_time);
+		Delay::new(time_until_next_slot(now, params.slot_duration)).await;
+
+		let slot = match slot_at(since_genesis(params.genesis_time), params.slot_duration) {
+			Some(slot) => slot,
+			None => {
+				error!(
+					target: LOG_TARGET,
+					"The slot number no longer fits in a u32, stopping the nimbus worker"
+				);
+				return;
+			}
+		};
+		// The timer may wake up early or late; author at most once per slot.
+		if last_slot.map_or(false, |last_slot| slot <= last_slot) {
+			continue;
+		}
+		last_slot = Some(slot);
+
+		if params.sync_oracle.is_major_syncing() {
+			debug!(target: LOG_TARGET, "Skipping slot {} while syncing", slot);
+			continue;
+		}
+
+		author_in_slot(&mut params, slot).await;
+	}
+}
+
+/// Author a block in the given slot on top of the best block, if one of our keys may.
+async fn author_in_slot<B, C, SC, PF, BI, CIDP, SO, DP, EP, KS, SP>(
+	params: &mut StandaloneNimbusParams<C, SC, PF, BI, CIDP, SO, DP, EP, KS, SP>,
+	slot: u32,
+) -> Option<()>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + Send + Sync + 'static,
+	C::Api: NimbusApi<B>,
+	SC: SelectChain<B>,
+	PF: Environment<B> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Transaction = BI::Transaction>,
+	BI: BlockImport<B> + Send + Sync + 'static,
+	CIDP: CreateInherentDataProviders<B, (u32, MultiNimbusId)> + 'static,
+	DP: DigestsProvider<MultiNimbusId, B::Hash>,
+	EP: EligibilityProver<B>,
+	KS: KeySelector,
+	SP: SlashingProtector,
+{
+	let parent = params
+		.select_chain
+		.best_chain()
+		.await
+		.map_err(|e| {
+			warn!(
+				target: LOG_TARGET,
+				"Could not select the best block: {:?}", e
+			)
+		})
+		.ok()?;
+
+	let available_keys = nimbus_keys_off_executor(params.keystore.clone()).await;
+	let nimbus_id = select_author_key::<B, C, KS>(
+		params.client.clone(),
+		available_keys,
+		&parent,
+		slot,
+		params.skip_prediction,
+		&params.key_selector,
+		|at| {
+			use sp_api::Core as _;
+			params.client.runtime_api().version(at).ok()
+		},
+	)?;
+
+	let eligibility_digests = params.eligibility_prover.prove_eligibility(
+		&*params.keystore,
+		&nimbus_id,
+		slot,
+		&parent,
+	)?;
+
+	let inherent_data = params
+		.create_inherent_data_providers
+		.create_inherent_data_providers(parent.hash(), (slot, nimbus_id.clone()))
+		.await
+		.map_err(|e| {
+			warn!(
+				target: LOG_TARGET,
+				"Failed to create inherent data providers: {:?}", e
+			)
+		})
+		.ok()?
+		.create_inherent_data()
+		.await
+		.map_err(|e| {
+			warn!(
+				target: LOG_TARGET,
+				"Failed to create inherent data: {:?}", e
+			)
+		})
+		.ok()?;
+
+	let pre_digest = pre_digest::<B, C>(&*params.client, &nimbus_id, &parent, slot)?;
+	let mut logs = vec![CompatibleDigestItem::nimbus_versioned_pre_digest(
+		pre_digest,
+	)];
+	logs.extend(eligibility_digests);
+	logs.extend(
+		params
+			.additional_digests_provider
+			.provide_digests(nimbus_id.clone(), parent.hash()),
+	);
+
+	let proposer = params
+		.proposer_factory
+		.init(&parent)
+		.await
+		.map_err(|e| warn!(target: LOG_TARGET, "Could not create proposer: {:?}", e))
+		.ok()?;
+
+	let Proposal {
+		block,
+		storage_changes,
+		..
+	} = proposer
+		.propose(
+			inherent_data,
+			Digest { logs },
+			params.proposal_duration,
+			params.block_size_limit,
+		)
+		.await
+		.map_err(|e| warn!(target: LOG_TARGET, "Proposing failed: {:?}", e))
+		.ok()?;
+
+	let (header, extrinsics) = block.deconstruct();
+	let seal = seal_header_off_executor::<B>(
+		&header,
+		slot,
+		params.keystore.clone(),
+		&nimbus_id,
+		&params.slashing_protection,
+	);
+	let seal = seal.await?;
+
+	let mut block_import_params = BlockImportParams::new(BlockOrigin::Own, header.clone());
+	block_import_params.post_digests.push(seal);
+	block_import_params.body = Some(extrinsics);
+	block_import_params.state_action =
+		StateAction::ApplyChanges(StorageChanges::Changes(storage_changes));
+	block_import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+
+	let post_hash = block_import_params.post_hash();
+	if let Err(e) = params.block_import.import_block(block_import_params).await {
+		warn!(
+			target: LOG_TARGET,
+			"Error importing block authored in slot {}: {:?}", slot, e
+		);
+		return None;
+	}
+
+	info!(
+		target: LOG_TARGET,
+		"🔖 Authored block #{} ({:?}) in slot {}",
+		header.number(),
+		post_hash,
+		slot
+	);
+
+	Some(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slots_are_counted_from_the_genesis_time() {
+		let slot_duration = Duration::from_secs(6);
+
+		assert_eq!(slot_at(Duration::from_secs(0), slot_duration), Some(0));
+		assert_eq!(
+			slot_at(Duration::from_millis(5_999), slot_duration),
+			Some(0)
+		);
+		assert_eq!(slot_at(Duration::from_secs(6), slot_duration), Some(1));
+		assert_eq!(slot_at(Duration::from_secs(60), slot_duration), Some(10));
+	}
+
+	#[test]
+	fn slot_numbers_past_u32_are_not_truncated() {
+		let slot_duration = Duration::from_millis(1);
+
+		assert_eq!(
+			slot_at(Duration::from_millis(u32::MAX.into()), slot_duration),
+			Some(u32::MAX)
+		);
+		assert_eq!(
+			slot_at(
+				Duration::from_millis(u64::from(u32::MAX) + 1),
+				slot_duration
+			),
+			None
+		);
+	}
+
+	#[test]
+	fn next_slot_starts_at_the_slot_boundary() {
+		let slot_duration = Duration::from_secs(6);
+
+		assert_eq!(
+			time_until_next_slot(Duration::from_millis(61_500), slot_duration),
+			Duration::from_millis(4_500)
+		);
+		// At the very start of a slot, the next one is a full slot away.
+		assert_eq!(
+			time_until_next_slot(Duration::from_secs(60), slot_duration),
+			slot_duration
+		);
+	}
+}
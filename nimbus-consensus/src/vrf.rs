@@ -0,0 +1,163 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Client-side support for filters that require the author to prove its eligibility.
+//!
+//! Some filters, like the VRF threshold filter, cannot be evaluated through
+//! `NimbusApi::can_author` because they check a proof that only the author can produce.
+//! After the worker has chosen a key, it asks an `EligibilityProver` for the digests that carry
+//! that proof, and skips the slot if none can be made.
+
+use crate::LOG_TARGET;
+use log::{info, warn};
+use nimbus_primitives::{
+	vrf::{self, VrfOutput},
+	CompatibleDigestItem, MultiNimbusId, VrfApi, NIMBUS_KEY_ID,
+};
+use sp_api::ProvideRuntimeApi;
+use sp_application_crypto::ByteArray;
+use sp_core::sr25519;
+use sp_keystore::Keystore;
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT},
+	DigestItem,
+};
+use std::sync::Arc;
+
+/// Proves that an author is eligible in a slot, in a way the runtime can check.
+pub trait EligibilityProver<B: BlockT> {
+	/// Returns the digests that prove `author`'s eligibility in `slot` on top of `parent`,
+	/// or `None` if the author is not eligible and should not author.
+	fn prove_eligibility(
+		&self,
+		keystore: &dyn Keystore,
+		author: &MultiNimbusId,
+		slot: u32,
+		parent: &B::Header,
+	) -> Option<Vec<DigestItem>>;
+}
+
+/// No proof is required, so every author chosen by the worker is eligible.
+impl<B: BlockT> EligibilityProver<B> for () {
+	fn prove_eligibility(
+		&self,
+		_keystore: &dyn Keystore,
+		_author: &MultiNimbusId,
+		_slot: u32,
+		_parent: &B::Header,
+	) -> Option<Vec<DigestItem>> {
+		Some(Vec::new())
+	}
+}
+
+/// Proves eligibility under the VRF threshold filter.
+///
+/// It signs a VRF with the author's key, checks the output against the runtime's threshold,
+/// and returns the VRF pre-runtime digest when the author is eligible. Only sr25519 authors
+/// can prove their eligibility this way.
+pub struct VrfEligibilityProver<C> {
+	client: Arc<C>,
+}
+
+impl<C> VrfEligibilityProver<C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client }
+	}
+}
+
+impl<C> Clone for VrfEligibilityProver<C> {
+	fn clone(&self) -> Self {
+		Self {
+			client: self.client.clone(),
+		}
+	}
+}
+
+impl<B, C> EligibilityProver<B> for VrfEligibilityProver<C>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: VrfApi<B>,
+{
+	fn prove_eligibility(
+		&self,
+		keystore: &dyn Keystore,
+		author: &MultiNimbusId,
+		slot: u32,
+		parent: &B::Header,
+	) -> Option<Vec<DigestItem>> {
+		// VRFs are only defined for sr25519 keys.
+		let author = match author.as_sr25519() {
+			Some(author) => author,
+			None => {
+				warn!(
+					target: LOG_TARGET,
+					"🔏 VRF eligibility requires an sr25519 nimbus key, but {:?} is not one",
+					author
+				);
+				return None;
+			}
+		};
+
+		// This mirrors how the runtime derives the randomness from the previous block.
+		let parent_output: Option<VrfOutput> = parent
+			.digest()
+			.logs
+			.iter()
+			.find_map(|digest| digest.as_nimbus_vrf_pre_digest())
+			.map(|signature| signature.output);
+		let randomness = vrf::make_vrf_randomness(parent_output.as_ref(), parent.hash().as_ref());
+
+		let public = sr25519::Public::from_slice(author.as_slice()).ok()?;
+		let signature = match Keystore::sr25519_vrf_sign(
+			keystore,
+			NIMBUS_KEY_ID,
+			&public,
+			&vrf::make_vrf_sign_data(slot, &randomness),
+		) {
+			Ok(Some(signature)) => signature,
+			_ => {
+				warn!(
+					target: LOG_TARGET,
+					"🔏 Could not sign VRF for slot {}", slot
+				);
+				return None;
+			}
+		};
+
+		let threshold = match VrfApi::vrf_threshold(&*self.client.runtime_api(), parent.hash()) {
+			Ok(threshold) => threshold,
+			Err(e) => {
+				warn!(target: LOG_TARGET, "Could not fetch VRF threshold: {:?}", e);
+				return None;
+			}
+		};
+
+		// Verifying our own signature is the simplest way to get the output in the exact
+		// form the runtime compares against the threshold.
+		let output = vrf::verify_vrf(author, slot, &randomness, &signature)?;
+		if !vrf::is_below_threshold(output, threshold) {
+			info!(
+				target: LOG_TARGET,
+				"🔮 Skipping candidate production because our VRF output is above the threshold for slot {}",
+				slot
+			);
+			return None;
+		}
+
+		Some(vec![CompatibleDigestItem::nimbus_vrf_pre_digest(signature)])
+	}
+}
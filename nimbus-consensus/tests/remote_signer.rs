@@ -0,0 +1,234 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests of the remote signer against the `nimbus-test-signer` stand-in.
+
+use nimbus_consensus::{RemoteSigner, RemoteSignerConfig, SignerEndpoint};
+use nimbus_primitives::{
+	multi_signer::{NimbusEcdsaId, NimbusEcdsaSignature, NimbusEd25519Id, NimbusEd25519Signature},
+	MultiNimbusId, MultiNimbusSignature, NimbusId, NimbusSignature, NIMBUS_KEY_ID,
+};
+use sp_core::{ecdsa, ed25519, sr25519, Pair};
+use sp_keystore::Keystore;
+use std::{
+	io::{BufRead, BufReader},
+	process::{Child, Command, Stdio},
+	time::Duration,
+};
+
+/// A running `nimbus-test-signer`, killed when dropped.
+struct TestSigner {
+	process: Child,
+	endpoint: SignerEndpoint,
+}
+
+impl TestSigner {
+	fn spawn(args: &[&str]) -> Self {
+		let mut process = Command::new(env!("CARGO_BIN_EXE_nimbus-test-signer"))
+			.args(args)
+			.stdout(Stdio::piped())
+			.spawn()
+			.expect("The test signer can be started");
+
+		let mut line = String::new();
+		BufReader::new(process.stdout.as_mut().expect("stdout is piped"))
+			.read_line(&mut line)
+			.expect("The test signer reports where it listens");
+		let endpoint = line
+			.trim()
+			.strip_prefix("listening on ")
+			.expect("The test signer reports where it listens")
+			.parse()
+			.expect("The test signer reports a valid endpoint");
+
+		Self { process, endpoint }
+	}
+
+	fn spawn_unix(name: &str, args: &[&str]) -> Self {
+		let path = std::env::temp_dir().join(format!(
+			"nimbus-test-signer-{}-{}.sock",
+			std::process::id(),
+			name
+		));
+		let _ = std::fs::remove_file(&path);
+		let path = path
+			.to_str()
+			.expect("The temporary directory is valid UTF-8");
+
+		Self::spawn(&[&["--unix", path][..], args].concat())
+	}
+
+	fn spawn_http(args: &[&str]) -> Self {
+		Self::spawn(&[&["--http", "127.0.0.1:0"][..], args].concat())
+	}
+
+	fn remote(&self, retries: u32) -> RemoteSigner {
+		RemoteSigner::new(RemoteSignerConfig {
+			retries,
+			retry_delay: Duration::from_millis(10),
+			..RemoteSignerConfig::new(self.endpoint.clone())
+		})
+	}
+}
+
+impl Drop for TestSigner {
+	fn drop(&mut self) {
+		let _ = self.process.kill();
+		let _ = self.process.wait();
+		if let SignerEndpoint::Unix(path) = &self.endpoint {
+			let _ = std::fs::remove_file(path);
+		}
+	}
+}
+
+#[test]
+fn signs_over_unix_socket() {
+	let signer = TestSigner::spawn_unix("sign", &["--sr25519", "//Alice"]);
+	let remote = signer.remote(0);
+	let alice = sr25519::Pair::from_string("//Alice", None).unwrap();
+
+	assert_eq!(
+		Keystore::sr25519_public_keys(&remote, NIMBUS_KEY_ID),
+		vec![alice.public()]
+	);
+
+	let signature = Keystore::sr25519_sign(&remote, NIMBUS_KEY_ID, &alice.public(), b"block")
+		.expect("The signer is reachable")
+		.expect("The signer holds Alice's key");
+	assert!(sr25519::Pair::verify(&signature, b"block", &alice.public()));
+}
+
+#[test]
+fn signs_in_every_scheme_over_http() {
+	let signer = TestSigner::spawn_http(&[
+		"--sr25519",
+		"//Alice",
+		"--ed25519",
+		"//Bob",
+		"--ecdsa",
+		"//Charlie",
+	]);
+	let remote = signer.remote(0);
+
+	let alice = sr25519::Pair::from_string("//Alice", None)
+		.unwrap()
+		.public();
+	let bob = ed25519::Pair::from_string("//Bob", None).unwrap().public();
+	let charlie = ecdsa::Pair::from_string("//Charlie", None)
+		.unwrap()
+		.public();
+	assert_eq!(
+		Keystore::sr25519_public_keys(&remote, NIMBUS_KEY_ID),
+		vec![alice]
+	);
+	assert_eq!(
+		Keystore::ed25519_public_keys(&remote, NIMBUS_KEY_ID),
+		vec![bob]
+	);
+	assert_eq!(
+		Keystore::ecdsa_public_keys(&remote, NIMBUS_KEY_ID),
+		vec![charlie]
+	);
+
+	let signature: MultiNimbusSignature = NimbusSignature::from(
+		Keystore::sr25519_sign(&remote, NIMBUS_KEY_ID, &alice, b"block")
+			.unwrap()
+			.unwrap(),
+	)
+	.into();
+	assert!(MultiNimbusId::from(NimbusId::from(alice)).verify(b"block", &signature));
+
+	let signature: MultiNimbusSignature = NimbusEd25519Signature::from(
+		Keystore::ed25519_sign(&remote, NIMBUS_KEY_ID, &bob, b"block")
+			.unwrap()
+			.unwrap(),
+	)
+	.into();
+	assert!(MultiNimbusId::from(NimbusEd25519Id::from(bob)).verify(b"block", &signature));
+
+	let signature: MultiNimbusSignature = NimbusEcdsaSignature::from(
+		Keystore::ecdsa_sign(&remote, NIMBUS_KEY_ID, &charlie, b"block")
+			.unwrap()
+			.unwrap(),
+	)
+	.into();
+	assert!(MultiNimbusId::from(NimbusEcdsaId::from(charlie)).verify(b"block", &signature));
+}
+
+#[test]
+fn unknown_keys_are_not_signed() {
+	let signer = TestSigner::spawn_unix("unknown", &["--sr25519", "//Alice"]);
+	let remote = signer.remote(0);
+	let bob = sr25519::Pair::from_string("//Bob", None).unwrap().public();
+
+	assert_eq!(
+		Keystore::sr25519_sign(&remote, NIMBUS_KEY_ID, &bob, b"block").unwrap(),
+		None
+	);
+}
+
+#[test]
+fn other_key_types_are_not_forwarded() {
+	let signer = TestSigner::spawn_unix("key-types", &["--sr25519", "//Alice"]);
+	let remote = signer.remote(0);
+
+	assert!(Keystore::sr25519_public_keys(&remote, sp_core::crypto::key_types::BABE).is_empty());
+}
+
+#[test]
+fn dropped_requests_are_retried() {
+	let signer = TestSigner::spawn_unix("retry", &["--sr25519", "//Alice", "--drop-first", "2"]);
+	let alice = sr25519::Pair::from_string("//Alice", None)
+		.unwrap()
+		.public();
+
+	assert_eq!(
+		Keystore::sr25519_public_keys(&signer.remote(2), NIMBUS_KEY_ID),
+		vec![alice]
+	);
+}
+
+#[test]
+fn signing_fails_when_retries_are_exhausted() {
+	let signer = TestSigner::spawn_http(&["--sr25519", "//Alice", "--drop-first", "2"]);
+	let alice = sr25519::Pair::from_string("//Alice", None)
+		.unwrap()
+		.public();
+
+	assert!(Keystore::sr25519_sign(&signer.remote(1), NIMBUS_KEY_ID, &alice, b"block").is_err());
+}
+
+#[test]
+fn key_list_is_requested_once_per_cache_duration() {
+	let signer = TestSigner::spawn_unix("cache", &["--sr25519", "//Alice", "--ed25519", "//Bob"]);
+	let remote = signer.remote(0);
+	let alice = sr25519::Pair::from_string("//Alice", None)
+		.unwrap()
+		.public();
+	let bob = ed25519::Pair::from_string("//Bob", None).unwrap().public();
+
+	assert_eq!(
+		Keystore::sr25519_public_keys(&remote, NIMBUS_KEY_ID),
+		vec![alice]
+	);
+
+	// The signer is gone, but the keys it listed are still cached
+	drop(signer);
+	assert_eq!(
+		Keystore::ed25519_public_keys(&remote, NIMBUS_KEY_ID),
+		vec![bob]
+	);
+}
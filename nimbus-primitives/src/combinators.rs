@@ -0,0 +1,316 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Combinators for composing nimbus author filters.
+//!
+//! Each combinator wraps one or more existing filters and is itself a filter, so they can be
+//! nested to express a consensus policy entirely in the runtime configuration. For example,
+//! `SlotRange<AuraFilter, And<AccountSet, SlotFilter>, Boundary>` runs aura until the boundary
+//! slot and a pseudo-random subset of the account set afterwards.
+//!
+//! Where all the wrapped filters also implement `EligibleAuthors`, so does the combinator.
+//! The one exception is `Not`, whose eligible set is unbounded.
+
+use crate::{CanAuthor, EligibleAuthors};
+use frame_support::traits::Get;
+use sp_std::marker::PhantomData;
+use sp_std::vec::Vec;
+
+/// An author is eligible if it is eligible according to both `A` and `B`.
+pub struct And<A, B>(PhantomData<(A, B)>);
+
+impl<AuthorId, A, B> CanAuthor<AuthorId> for And<A, B>
+where
+	A: CanAuthor<AuthorId>,
+	B: CanAuthor<AuthorId>,
+{
+	#[cfg(not(feature = "try-runtime"))]
+	fn can_author(author: &AuthorId, slot: &u32) -> bool {
+		A::can_author(author, slot) && B::can_author(author, slot)
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn get_authors(slot: &u32) -> Vec<AuthorId> {
+		A::get_authors(slot)
+			.into_iter()
+			.filter(|author| B::can_author(author, slot))
+			.collect()
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn set_eligible_author(slot: &u32) {
+		A::set_eligible_author(slot);
+		B::set_eligible_author(slot);
+	}
+}
+
+// Only the first filter needs to be exhaustive. Its set is narrowed down by the second one.
+impl<AuthorId, A, B> EligibleAuthors<AuthorId> for And<A, B>
+where
+	A: EligibleAuthors<AuthorId>,
+	B: CanAuthor<AuthorId>,
+{
+	fn eligible_authors(slot: &u32) -> Vec<AuthorId> {
+		A::eligible_authors(slot)
+			.into_iter()
+			.filter(|author| B::can_author(author, slot))
+			.collect()
+	}
+}
+
+/// An author is eligible if it is eligible according to `A`, `B`, or both.
+pub struct Or<A, B>(PhantomData<(A, B)>);
+
+impl<AuthorId, A, B> CanAuthor<AuthorId> for Or<A, B>
+where
+	AuthorId: PartialEq,
+	A: CanAuthor<AuthorId>,
+	B: CanAuthor<AuthorId>,
+{
+	#[cfg(not(feature = "try-runtime"))]
+	fn can_author(author: &AuthorId, slot: &u32) -> bool {
+		A::can_author(author, slot) || B::can_author(author, slot)
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn get_authors(slot: &u32) -> Vec<AuthorId> {
+		union(A::get_authors(slot), B::get_authors(slot))
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn set_eligible_author(slot: &u32) {
+		A::set_eligible_author(slot);
+	}
+}
+
+impl<AuthorId, A, B> EligibleAuthors<AuthorId> for Or<A, B>
+where
+	AuthorId: PartialEq,
+	A: EligibleAuthors<AuthorId>,
+	B: EligibleAuthors<AuthorId>,
+{
+	fn eligible_authors(slot: &u32) -> Vec<AuthorId> {
+		union(A::eligible_authors(slot), B::eligible_authors(slot))
+	}
+}
+
+/// An author is eligible if it is _not_ eligible according to `A`.
+///
+/// This is mostly useful as the second argument to `And`, for example to exclude a set of
+/// authors that has been reported as offline.
+pub struct Not<A>(PhantomData<A>);
+
+impl<AuthorId, A> CanAuthor<AuthorId> for Not<A>
+where
+	A: CanAuthor<AuthorId>,
+{
+	#[cfg(not(feature = "try-runtime"))]
+	fn can_author(author: &AuthorId, slot: &u32) -> bool {
+		!A::can_author(author, slot)
+	}
+	// The complement of a set cannot be enumerated, so we keep the default (empty)
+	// benchmark hooks here.
+}
+
+/// Uses filter `A` in even slots and filter `B` in odd slots.
+pub struct AlternateSlots<A, B>(PhantomData<(A, B)>);
+
+impl<AuthorId, A, B> CanAuthor<AuthorId> for AlternateSlots<A, B>
+where
+	A: CanAuthor<AuthorId>,
+	B: CanAuthor<AuthorId>,
+{
+	#[cfg(not(feature = "try-runtime"))]
+	fn can_author(author: &AuthorId, slot: &u32) -> bool {
+		if slot % 2 == 0 {
+			A::can_author(author, slot)
+		} else {
+			B::can_author(author, slot)
+		}
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn get_authors(slot: &u32) -> Vec<AuthorId> {
+		if slot % 2 == 0 {
+			A::get_authors(slot)
+		} else {
+			B::get_authors(slot)
+		}
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn set_eligible_author(slot: &u32) {
+		if slot % 2 == 0 {
+			A::set_eligible_author(slot)
+		} else {
+			B::set_eligible_author(slot)
+		}
+	}
+}
+
+impl<AuthorId, A, B> EligibleAuthors<AuthorId> for AlternateSlots<A, B>
+where
+	A: EligibleAuthors<AuthorId>,
+	B: EligibleAuthors<AuthorId>,
+{
+	fn eligible_authors(slot: &u32) -> Vec<AuthorId> {
+		if slot % 2 == 0 {
+			A::eligible_authors(slot)
+		} else {
+			B::eligible_authors(slot)
+		}
+	}
+}
+
+/// Uses filter `A` in slots before `Boundary` and filter `B` from `Boundary` onwards.
+///
+/// This is useful to schedule a change of consensus policy at a known slot, without a
+/// runtime upgrade landing exactly at that slot.
+pub struct SlotRange<A, B, Boundary>(PhantomData<(A, B, Boundary)>);
+
+impl<AuthorId, A, B, Boundary> CanAuthor<AuthorId> for SlotRange<A, B, Boundary>
+where
+	A: CanAuthor<AuthorId>,
+	B: CanAuthor<AuthorId>,
+	Boundary: Get<u32>,
+{
+	#[cfg(not(feature = "try-runtime"))]
+	fn can_author(author: &AuthorId, slot: &u32) -> bool {
+		if *slot < Boundary::get() {
+			A::can_author(author, slot)
+		} else {
+			B::can_author(author, slot)
+		}
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn get_authors(slot: &u32) -> Vec<AuthorId> {
+		if *slot < Boundary::get() {
+			A::get_authors(slot)
+		} else {
+			B::get_authors(slot)
+		}
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn set_eligible_author(slot: &u32) {
+		if *slot < Boundary::get() {
+			A::set_eligible_author(slot)
+		} else {
+			B::set_eligible_author(slot)
+		}
+	}
+}
+
+impl<AuthorId, A, B, Boundary> EligibleAuthors<AuthorId> for SlotRange<A, B, Boundary>
+where
+	A: EligibleAuthors<AuthorId>,
+	B: EligibleAuthors<AuthorId>,
+	Boundary: Get<u32>,
+{
+	fn eligible_authors(slot: &u32) -> Vec<AuthorId> {
+		if *slot < Boundary::get() {
+			A::eligible_authors(slot)
+		} else {
+			B::eligible_authors(slot)
+		}
+	}
+}
+
+/// Appends the members of `second` that are not already in `first`.
+fn union<AuthorId: PartialEq>(mut first: Vec<AuthorId>, second: Vec<AuthorId>) -> Vec<AuthorId> {
+	for author in second {
+		if !first.contains(&author) {
+			first.push(author);
+		}
+	}
+	first
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::traits::ConstU32;
+
+	const AUTHORS: [u32; 6] = [0, 1, 2, 3, 4, 5];
+
+	/// Authors with an even id are eligible in every slot.
+	struct EvenAuthors;
+	impl CanAuthor<u32> for EvenAuthors {
+		fn can_author(author: &u32, _: &u32) -> bool {
+			author % 2 == 0
+		}
+	}
+	impl EligibleAuthors<u32> for EvenAuthors {
+		fn eligible_authors(_: &u32) -> Vec<u32> {
+			AUTHORS.into_iter().filter(|a| a % 2 == 0).collect()
+		}
+	}
+
+	/// Authors with an id below three are eligible in every slot.
+	struct LowAuthors;
+	impl CanAuthor<u32> for LowAuthors {
+		fn can_author(author: &u32, _: &u32) -> bool {
+			*author < 3
+		}
+	}
+	impl EligibleAuthors<u32> for LowAuthors {
+		fn eligible_authors(_: &u32) -> Vec<u32> {
+			AUTHORS.into_iter().filter(|a| *a < 3).collect()
+		}
+	}
+
+	fn eligible<F: CanAuthor<u32>>(slot: u32) -> Vec<u32> {
+		AUTHORS
+			.into_iter()
+			.filter(|a| F::can_author(a, &slot))
+			.collect()
+	}
+
+	#[test]
+	fn and_requires_both_filters() {
+		assert_eq!(eligible::<And<EvenAuthors, LowAuthors>>(0), vec![0, 2]);
+		assert_eq!(
+			<And<EvenAuthors, LowAuthors>>::eligible_authors(&0),
+			vec![0, 2]
+		);
+	}
+
+	#[test]
+	fn or_accepts_either_filter() {
+		assert_eq!(eligible::<Or<EvenAuthors, LowAuthors>>(0), vec![0, 1, 2, 4]);
+		assert_eq!(
+			<Or<EvenAuthors, LowAuthors>>::eligible_authors(&0),
+			vec![0, 2, 4, 1]
+		);
+	}
+
+	#[test]
+	fn not_inverts_filter() {
+		assert_eq!(eligible::<Not<EvenAuthors>>(0), vec![1, 3, 5]);
+		assert_eq!(eligible::<And<LowAuthors, Not<EvenAuthors>>>(0), vec![1]);
+	}
+
+	#[test]
+	fn alternate_slots_switches_on_parity() {
+		type Filter = AlternateSlots<EvenAuthors, LowAuthors>;
+		assert_eq!(eligible::<Filter>(4), vec![0, 2, 4]);
+		assert_eq!(eligible::<Filter>(5), vec![0, 1, 2]);
+		assert_eq!(Filter::eligible_authors(&4), vec![0, 2, 4]);
+		assert_eq!(Filter::eligible_authors(&5), vec![0, 1, 2]);
+	}
+
+	#[test]
+	fn slot_range_switches_at_boundary() {
+		type Filter = SlotRange<EvenAuthors, LowAuthors, ConstU32<10>>;
+		assert_eq!(eligible::<Filter>(9), vec![0, 2, 4]);
+		assert_eq!(eligible::<Filter>(10), vec![0, 1, 2]);
+		assert_eq!(Filter::eligible_authors(&9), vec![0, 2, 4]);
+		assert_eq!(Filter::eligible_authors(&10), vec![0, 1, 2]);
+	}
+}
@@ -22,25 +22,162 @@
 //!    This may be replaced with a pre-runtime digest in the future.
 //! 2. A seal digest that contains a signature over the rest of the
 //!    block including the first digest.
+//!
+//! Runtimes that use the VRF threshold filter additionally expect a pre-runtime
+//! digest carrying the author's VRF signature. See the `vrf` module.
+//!
+//! The nimbus pre-runtime digest is versioned. Version 1 contains only the encoded author.
+//! Version 2 is a `NimbusPreDigest` that also carries the slot the block was authored in, and
+//! optional extension data, so that the slot can be learned from the header alone.
+//! Both versions are accepted everywhere the author is read.
 
-use crate::{NimbusId, NimbusSignature, NIMBUS_ENGINE_ID};
-use parity_scale_codec::Encode;
+use crate::vrf::{VrfSignature, VRF_ENGINE_ID};
+use crate::{MultiNimbusId, MultiNimbusSignature, NimbusId, NimbusSignature, NIMBUS_ENGINE_ID};
+use parity_scale_codec::{Decode, Encode, Error, Input, Output};
+use sp_application_crypto::ByteArray;
 use sp_runtime::generic::DigestItem;
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// The version byte that prefixes version 2 pre-runtime digests.
+pub const NIMBUS_PRE_DIGEST_V2: u8 = 2;
+
+/// The length of an sr25519 seal, which is the bare encoded signature. Seals in other schemes are
+/// encoded as a `MultiNimbusSignature`, which is always longer.
+const SR25519_SEAL_LEN: usize = 64;
+
+/// The contents of the nimbus pre-runtime digest.
+///
+/// A version 1 digest is the bare encoded sr25519 `NimbusId`, which is exactly 32 bytes long.
+/// Authors using other signature schemes must use a later version. Later
+/// versions start with a version byte and are always longer, so the two cannot be confused.
+/// Decoding relies on this, so a `NimbusPreDigest` must be decoded from the digest's entire data.
+#[derive(Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum NimbusPreDigest {
+	/// The original digest, which only reports the author.
+	V1(NimbusId),
+	/// Reports the author, in any signature scheme, and the slot, plus opaque data for future
+	/// extensions.
+	V2 {
+		author: MultiNimbusId,
+		slot: u32,
+		extension: Option<Vec<u8>>,
+	},
+}
+
+impl NimbusPreDigest {
+	/// The version of this digest.
+	pub fn version(&self) -> u8 {
+		match self {
+			Self::V1(_) => 1,
+			Self::V2 { .. } => NIMBUS_PRE_DIGEST_V2,
+		}
+	}
+
+	/// The author of the block.
+	pub fn author(&self) -> MultiNimbusId {
+		match self {
+			Self::V1(author) => author.clone().into(),
+			Self::V2 { author, .. } => author.clone(),
+		}
+	}
+
+	/// The slot the block was authored in. Version 1 digests do not report it.
+	pub fn slot(&self) -> Option<u32> {
+		match self {
+			Self::V1(_) => None,
+			Self::V2 { slot, .. } => Some(*slot),
+		}
+	}
+
+	/// The extension data, if any.
+	pub fn extension(&self) -> Option<&[u8]> {
+		match self {
+			Self::V1(_) => None,
+			Self::V2 { extension, .. } => extension.as_deref(),
+		}
+	}
+}
+
+impl Encode for NimbusPreDigest {
+	fn size_hint(&self) -> usize {
+		match self {
+			Self::V1(author) => author.size_hint(),
+			Self::V2 {
+				author,
+				slot,
+				extension,
+			} => 1 + author.size_hint() + slot.size_hint() + extension.size_hint(),
+		}
+	}
+
+	fn encode_to<O: Output + ?Sized>(&self, dest: &mut O) {
+		match self {
+			Self::V1(author) => author.encode_to(dest),
+			Self::V2 {
+				author,
+				slot,
+				extension,
+			} => {
+				dest.push_byte(NIMBUS_PRE_DIGEST_V2);
+				author.encode_to(dest);
+				slot.encode_to(dest);
+				extension.encode_to(dest);
+			}
+		}
+	}
+}
+
+impl Decode for NimbusPreDigest {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+		if input.remaining_len()? == Some(NimbusId::LEN) {
+			return Ok(Self::V1(NimbusId::decode(input)?));
+		}
+
+		match input.read_byte()? {
+			NIMBUS_PRE_DIGEST_V2 => Ok(Self::V2 {
+				author: MultiNimbusId::decode(input)?,
+				slot: u32::decode(input)?,
+				extension: Option::<Vec<u8>>::decode(input)?,
+			}),
+			_ => Err("Unknown nimbus pre-runtime digest version".into()),
+		}
+	}
+}
 
 /// A digest item which is usable with aura consensus.
 pub trait CompatibleDigestItem: Sized {
 	/// Construct a pre-runtime digest from the given AuthorId
 	fn nimbus_pre_digest(author: NimbusId) -> Self;
 
-	/// If this item is a nimbus pre-runtime digest, return the author
+	/// If this item is a nimbus pre-runtime digest of any version, and the author uses sr25519,
+	/// return the author
 	fn as_nimbus_pre_digest(&self) -> Option<NimbusId>;
 
+	/// Construct a versioned pre-runtime digest
+	fn nimbus_versioned_pre_digest(pre_digest: NimbusPreDigest) -> Self;
+
+	/// If this item is a nimbus pre-runtime digest of any version, return its contents
+	fn as_nimbus_versioned_pre_digest(&self) -> Option<NimbusPreDigest>;
+
 	/// Construct a seal digest item from the given signature
 	fn nimbus_seal(signature: NimbusSignature) -> Self;
 
-	/// If this item is a nimbus seal, return the signature.
+	/// If this item is a nimbus seal using sr25519, return the signature.
 	fn as_nimbus_seal(&self) -> Option<NimbusSignature>;
 
+	/// Construct a seal digest item from a signature in any scheme
+	fn nimbus_multi_seal(signature: MultiNimbusSignature) -> Self;
+
+	/// If this item is a nimbus seal, return the signature, whatever its scheme.
+	fn as_nimbus_multi_seal(&self) -> Option<MultiNimbusSignature>;
+
+	/// Construct a pre-runtime digest from the given VRF signature
+	fn nimbus_vrf_pre_digest(signature: VrfSignature) -> Self;
+
+	/// If this item is a nimbus VRF pre-runtime digest, return the VRF signature
+	fn as_nimbus_vrf_pre_digest(&self) -> Option<VrfSignature>;
+
 	/// This will be deprecated in the future
 	/// Construct a consensus digest from the given AuthorId
 	fn nimbus_consensus_digest(author: NimbusId) -> Self;
@@ -56,6 +193,15 @@ impl CompatibleDigestItem for DigestItem {
 	}
 
 	fn as_nimbus_pre_digest(&self) -> Option<NimbusId> {
+		self.as_nimbus_versioned_pre_digest()
+			.and_then(|pre_digest| pre_digest.author().as_sr25519().cloned())
+	}
+
+	fn nimbus_versioned_pre_digest(pre_digest: NimbusPreDigest) -> Self {
+		DigestItem::PreRuntime(NIMBUS_ENGINE_ID, pre_digest.encode())
+	}
+
+	fn as_nimbus_versioned_pre_digest(&self) -> Option<NimbusPreDigest> {
 		self.pre_runtime_try_to(&NIMBUS_ENGINE_ID)
 	}
 
@@ -64,7 +210,39 @@ impl CompatibleDigestItem for DigestItem {
 	}
 
 	fn as_nimbus_seal(&self) -> Option<NimbusSignature> {
-		self.seal_try_to(&NIMBUS_ENGINE_ID)
+		match self.as_nimbus_multi_seal()? {
+			MultiNimbusSignature::Sr25519(signature) => Some(signature),
+			_ => None,
+		}
+	}
+
+	fn nimbus_multi_seal(signature: MultiNimbusSignature) -> Self {
+		match signature {
+			// sr25519 seals keep their original encoding
+			MultiNimbusSignature::Sr25519(signature) => Self::nimbus_seal(signature),
+			signature => DigestItem::Seal(NIMBUS_ENGINE_ID, signature.encode()),
+		}
+	}
+
+	fn as_nimbus_multi_seal(&self) -> Option<MultiNimbusSignature> {
+		match self {
+			DigestItem::Seal(id, data) if *id == NIMBUS_ENGINE_ID => {
+				if data.len() == SR25519_SEAL_LEN {
+					NimbusSignature::decode(&mut &data[..]).ok().map(Into::into)
+				} else {
+					MultiNimbusSignature::decode(&mut &data[..]).ok()
+				}
+			}
+			_ => None,
+		}
+	}
+
+	fn nimbus_vrf_pre_digest(signature: VrfSignature) -> Self {
+		DigestItem::PreRuntime(VRF_ENGINE_ID, signature.encode())
+	}
+
+	fn as_nimbus_vrf_pre_digest(&self) -> Option<VrfSignature> {
+		self.pre_runtime_try_to(&VRF_ENGINE_ID)
 	}
 
 	// Remove this once deprecated
@@ -78,3 +256,94 @@ impl CompatibleDigestItem for DigestItem {
 		self.pre_runtime_try_to(&NIMBUS_ENGINE_ID)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::NimbusPair;
+	use sp_application_crypto::Pair;
+
+	fn alice() -> NimbusId {
+		NimbusPair::from_seed(&[1; 32]).public()
+	}
+
+	#[test]
+	fn v1_pre_digest_is_the_bare_author() {
+		let item = DigestItem::nimbus_pre_digest(alice());
+
+		assert_eq!(
+			item,
+			DigestItem::PreRuntime(NIMBUS_ENGINE_ID, alice().encode())
+		);
+		assert_eq!(
+			item.as_nimbus_versioned_pre_digest(),
+			Some(NimbusPreDigest::V1(alice()))
+		);
+		assert_eq!(item.as_nimbus_pre_digest(), Some(alice()));
+	}
+
+	#[test]
+	fn v2_pre_digest_round_trips() {
+		let pre_digest = NimbusPreDigest::V2 {
+			author: alice().into(),
+			slot: 7,
+			extension: Some(vec![1, 2, 3]),
+		};
+		let item = DigestItem::nimbus_versioned_pre_digest(pre_digest.clone());
+
+		let decoded = item.as_nimbus_versioned_pre_digest().unwrap();
+		assert_eq!(decoded, pre_digest);
+		assert_eq!(decoded.version(), NIMBUS_PRE_DIGEST_V2);
+		assert_eq!(decoded.slot(), Some(7));
+		assert_eq!(decoded.extension(), Some(&[1u8, 2, 3][..]));
+		assert_eq!(item.as_nimbus_pre_digest(), Some(alice()));
+	}
+
+	#[test]
+	fn unknown_pre_digest_version_is_rejected() {
+		let mut data = vec![3u8];
+		data.extend((MultiNimbusId::from(alice()), 7u32, None::<Vec<u8>>).encode());
+		let item = DigestItem::PreRuntime(NIMBUS_ENGINE_ID, data);
+
+		assert_eq!(item.as_nimbus_versioned_pre_digest(), None);
+		assert_eq!(item.as_nimbus_pre_digest(), None);
+	}
+
+	#[test]
+	fn sr25519_seal_keeps_its_bare_encoding() {
+		let signature = NimbusPair::from_seed(&[1; 32]).sign(b"block");
+		let item = DigestItem::nimbus_multi_seal(signature.clone().into());
+
+		assert_eq!(item, DigestItem::nimbus_seal(signature.clone()));
+		assert_eq!(item.as_nimbus_seal(), Some(signature.clone()));
+		assert_eq!(item.as_nimbus_multi_seal(), Some(signature.into()));
+	}
+
+	#[test]
+	fn other_schemes_round_trip() {
+		use crate::multi_signer::{NimbusEd25519Id, NimbusEd25519Signature};
+		use sp_core::ed25519;
+
+		let pair = ed25519::Pair::from_seed(&[1; 32]);
+		let author = MultiNimbusId::from(NimbusEd25519Id::from(pair.public()));
+		let signature =
+			MultiNimbusSignature::from(NimbusEd25519Signature::from(pair.sign(b"block")));
+
+		let seal = DigestItem::nimbus_multi_seal(signature.clone());
+		assert_eq!(seal.as_nimbus_multi_seal(), Some(signature));
+		assert_eq!(seal.as_nimbus_seal(), None);
+
+		let pre_digest = DigestItem::nimbus_versioned_pre_digest(NimbusPreDigest::V2 {
+			author: author.clone(),
+			slot: 7,
+			extension: None,
+		});
+		assert_eq!(
+			pre_digest
+				.as_nimbus_versioned_pre_digest()
+				.map(|pre_digest| pre_digest.author()),
+			Some(author)
+		);
+		assert_eq!(pre_digest.as_nimbus_pre_digest(), None);
+	}
+}
@@ -0,0 +1,173 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Proofs that a nimbus author sealed two conflicting blocks in the same slot.
+//!
+//! Nimbus itself is permissive about authors producing competing blocks, but a runtime may
+//! want to punish it. Two blocks conflict if they are at the same height or have the same
+//! parent. An author may seal several blocks that build on each other in the same slot, when
+//! the runtime allows more than one block per slot, which is not an equivocation.
+//! Everything here is no_std so the same checks can be made on the client and in the runtime.
+
+use crate::{seal::verify_seal, CompatibleDigestItem, MultiNimbusId};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_runtime::traits::Header as HeaderT;
+use sp_runtime::RuntimeDebug;
+
+/// Two conflicting headers sealed by the same author for the same slot.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, TypeInfo, RuntimeDebug)]
+pub struct EquivocationProof<Header> {
+	/// The author that sealed both headers
+	pub offender: MultiNimbusId,
+	/// The slot both headers were authored in
+	pub slot: u32,
+	/// The first header, including its seal
+	pub first_header: Header,
+	/// The second header, including its seal
+	pub second_header: Header,
+}
+
+/// Returns the author and slot noted in a sealed header's pre-runtime digest, if the seal is a
+/// valid signature by that author. Headers with a version 1 pre-runtime digest have no slot.
+pub fn sealed_author_and_slot<H: HeaderT>(header: &H) -> Option<(MultiNimbusId, u32)> {
+	let author = verify_seal(header).ok()?;
+	let slot = header
+		.digest()
+		.logs
+		.iter()
+		.find_map(|digest| digest.as_nimbus_versioned_pre_digest())?
+		.slot()?;
+
+	Some((author, slot))
+}
+
+/// Whether two different headers compete with each other: they are at the same height, or
+/// build on the same parent. Otherwise one may extend the other.
+pub fn headers_conflict<H: HeaderT>(first: &H, second: &H) -> bool {
+	first.number() == second.number() || first.parent_hash() == second.parent_hash()
+}
+
+/// Check that both headers of the proof are different and conflicting, are validly sealed by
+/// the offender, and were authored in the slot of the proof.
+pub fn check_equivocation_proof<H: HeaderT>(proof: &EquivocationProof<H>) -> bool {
+	if proof.first_header.hash() == proof.second_header.hash()
+		|| !headers_conflict(&proof.first_header, &proof.second_header)
+	{
+		return false;
+	}
+
+	let expected = Some((proof.offender.clone(), proof.slot));
+	sealed_author_and_slot(&proof.first_header) == expected
+		&& sealed_author_and_slot(&proof.second_header) == expected
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{NimbusPair, NimbusPreDigest};
+	use sp_application_crypto::Pair;
+	use sp_core::H256;
+	use sp_runtime::{testing::Header, Digest, DigestItem};
+
+	fn sealed_header(pair: &NimbusPair, slot: u32, number: u64) -> Header {
+		sealed_child(pair, slot, number, Default::default())
+	}
+
+	fn sealed_child(pair: &NimbusPair, slot: u32, number: u64, parent_hash: H256) -> Header {
+		let mut header = Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			Digest {
+				logs: vec![DigestItem::nimbus_versioned_pre_digest(
+					NimbusPreDigest::V2 {
+						author: pair.public().into(),
+						slot,
+						extension: None,
+					},
+				)],
+			},
+		);
+		let signature = pair.sign(header.hash().as_ref());
+		header.digest_mut().push(DigestItem::nimbus_seal(signature));
+		header
+	}
+
+	fn proof(first_header: Header, second_header: Header) -> EquivocationProof<Header> {
+		EquivocationProof {
+			offender: NimbusPair::from_seed(&[1; 32]).public().into(),
+			slot: 7,
+			first_header,
+			second_header,
+		}
+	}
+
+	#[test]
+	fn two_headers_at_the_same_height_in_the_same_slot_are_an_equivocation() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		assert!(check_equivocation_proof(&proof(
+			sealed_child(&alice, 7, 1, H256::repeat_byte(1)),
+			sealed_child(&alice, 7, 1, H256::repeat_byte(2)),
+		)));
+	}
+
+	#[test]
+	fn a_parent_and_its_child_in_the_same_slot_are_not_an_equivocation() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let parent = sealed_header(&alice, 7, 1);
+		let child = sealed_child(&alice, 7, 2, parent.hash());
+		assert!(!check_equivocation_proof(&proof(parent, child)));
+	}
+
+	#[test]
+	fn the_same_header_twice_is_not_an_equivocation() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let header = sealed_header(&alice, 7, 1);
+		assert!(!check_equivocation_proof(&proof(header.clone(), header)));
+	}
+
+	#[test]
+	fn headers_in_different_slots_are_not_an_equivocation() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		assert!(!check_equivocation_proof(&proof(
+			sealed_header(&alice, 7, 1),
+			sealed_header(&alice, 8, 2),
+		)));
+	}
+
+	#[test]
+	fn headers_by_another_author_are_not_an_equivocation() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let bob = NimbusPair::from_seed(&[2; 32]);
+		assert!(!check_equivocation_proof(&proof(
+			sealed_header(&alice, 7, 1),
+			sealed_header(&bob, 7, 2),
+		)));
+	}
+
+	#[test]
+	fn tampered_headers_are_not_an_equivocation() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let mut tampered = sealed_header(&alice, 7, 2);
+		tampered.number = 3;
+		assert!(!check_equivocation_proof(&proof(
+			sealed_header(&alice, 7, 1),
+			tampered,
+		)));
+	}
+}
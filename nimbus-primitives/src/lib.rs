@@ -21,17 +21,33 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use frame_support::traits::{ConstU64, Get};
+use frame_support::weights::Weight;
+use parity_scale_codec::{Codec, Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
 use sp_application_crypto::KeyTypeId;
 use sp_runtime::generic::DigestItem;
-use sp_runtime::traits::BlockNumberProvider;
-use sp_runtime::ConsensusEngineId;
 #[cfg(feature = "runtime-benchmarks")]
-use sp_std::vec::{self, Vec};
+use sp_runtime::traits::UniqueSaturatedFrom;
+use sp_runtime::traits::{BlockNumberProvider, UniqueSaturatedInto};
+use sp_runtime::{ConsensusEngineId, RuntimeDebug};
+use sp_std::marker::PhantomData;
+#[cfg(feature = "runtime-benchmarks")]
+use sp_std::vec;
+use sp_std::vec::Vec;
 
+pub mod combinators;
 pub mod digests;
+pub mod equivocation;
 mod inherents;
+pub mod multi_signer;
+pub mod seal;
+pub mod vrf;
 
-pub use digests::CompatibleDigestItem;
+pub use digests::{CompatibleDigestItem, NimbusPreDigest};
+pub use equivocation::{check_equivocation_proof, headers_conflict, EquivocationProof};
+pub use multi_signer::{MultiNimbusId, MultiNimbusSignature};
+pub use seal::{claimed_author, strip_and_verify_seal, strip_seal, verify_seal, SealError};
 
 pub use inherents::{InherentDataProvider, INHERENT_IDENTIFIER};
 
@@ -63,6 +79,11 @@ where
 pub trait EventHandler<Author> {
 	//TODO should we be tking ownership here?
 	fn note_author(author: Author);
+
+	/// The worst case weight of `note_author`. It is charged as part of the author inherent.
+	fn note_author_weight() -> Weight {
+		Weight::zero()
+	}
 }
 
 impl<T> EventHandler<T> for () {
@@ -91,14 +112,38 @@ impl<T: BlockNumberProvider<BlockNumber = u32>> SlotBeacon for T {
 	}
 }
 
-/// PLANNED: A SlotBeacon that starts a new slot based on the timestamp. Behaviorally, this is
+/// A SlotBeacon that starts a new slot based on the timestamp. Behaviorally, this is
 /// similar to what aura, babe and company do. Implementation-wise it is different because it
 /// depends on the timestamp pallet for its notion of time.
-pub struct IntervalBeacon;
+///
+/// The slot is `(now - GenesisTime) / SlotDuration`, where all three values are expressed in the
+/// timestamp pallet's unit (milliseconds in most runtimes). `SlotDuration` must not be zero.
+/// The `GenesisTime` offset lets a chain count slots from its launch rather than from the unix
+/// epoch. Keep in mind that the author inherent never accepts slot zero, so the first block
+/// must be produced at least one full slot after `GenesisTime`.
+pub struct IntervalBeacon<T, SlotDuration, GenesisTime = ConstU64<0>>(
+	PhantomData<(T, SlotDuration, GenesisTime)>,
+);
 
-impl SlotBeacon for IntervalBeacon {
+impl<T, SlotDuration, GenesisTime> SlotBeacon for IntervalBeacon<T, SlotDuration, GenesisTime>
+where
+	T: pallet_timestamp::Config,
+	SlotDuration: Get<u64>,
+	GenesisTime: Get<u64>,
+{
 	fn slot() -> u32 {
-		todo!()
+		let now: u64 = pallet_timestamp::Pallet::<T>::get().unique_saturated_into();
+		let slot = now.saturating_sub(GenesisTime::get()) / SlotDuration::get();
+
+		slot.unique_saturated_into()
+	}
+	#[cfg(feature = "runtime-benchmarks")]
+	fn set_slot(slot: u32) {
+		let now = (slot as u64)
+			.saturating_mul(SlotDuration::get())
+			.saturating_add(GenesisTime::get());
+
+		pallet_timestamp::Pallet::<T>::set_timestamp(T::Moment::unique_saturated_from(now));
 	}
 }
 
@@ -109,7 +154,7 @@ impl SlotBeacon for IntervalBeacon {
 /// That is to say the caller specifies an author an author and the implementation
 /// replies whether that author is eligible. This is useful in many cases and is
 /// particularly useful when the active set is unbounded.
-/// There may be another variant where the caller only supplies a slot and the
+/// See `EligibleAuthors` for the variant where the caller only supplies a slot and the
 /// implementation replies with a complete set of eligible authors.
 pub trait CanAuthor<AuthorId> {
 	#[cfg(feature = "try-runtime")]
@@ -135,6 +180,70 @@ impl<T> CanAuthor<T> for () {
 	}
 }
 
+/// Trait to determine the complete set of authors that are eligible in a given slot.
+///
+/// This is the exhaustive-set variant of `CanAuthor`. The caller only supplies a slot and the
+/// implementation replies with every author that is eligible in it. It can only be implemented
+/// by filters whose active set is bounded, and implementations are expected to agree with their
+/// `CanAuthor` counterpart. That is, an author is in the returned set if and only if `can_author`
+/// returns true for it in the same slot.
+pub trait EligibleAuthors<AuthorId> {
+	fn eligible_authors(slot: &u32) -> Vec<AuthorId>;
+}
+
+/// No eligibility information is available. Anything that relies on the exhaustive set, like
+/// the eligibility counters in the author inherent pallet, will see nobody as eligible.
+impl<T> EligibleAuthors<T> for () {
+	fn eligible_authors(_: &u32) -> Vec<T> {
+		Vec::new()
+	}
+}
+
+/// Block production statistics of a single author in a single round.
+#[derive(
+	Clone, Copy, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug,
+)]
+pub struct AuthorRoundStats {
+	/// The number of blocks the author has authored in the round
+	pub blocks_authored: u32,
+	/// The number of slots of the round in which the author was eligible
+	pub slots_eligible: u32,
+	/// The number of slots of the round in which the author was eligible but no block was produced
+	pub slots_missed: u32,
+}
+
+/// Notified when an author has missed too many of the slots it was eligible in.
+/// This can feed an offences or slashing pallet, or remove the author from the active set.
+pub trait OnAuthorOffline<AuthorId> {
+	/// `author` has missed `missed_slots` eligible slots in a row.
+	fn on_author_offline(author: &AuthorId, missed_slots: u32);
+
+	/// The worst case weight of `on_author_offline`.
+	fn on_author_offline_weight() -> Weight {
+		Weight::zero()
+	}
+}
+
+impl<T> OnAuthorOffline<T> for () {
+	fn on_author_offline(_author: &T, _missed_slots: u32) {}
+}
+
+/// Something that should be done when an author is proven to have sealed two different blocks
+/// in the same slot. For example, the author may be slashed or removed from the active set.
+pub trait OnOffenceHandler<AuthorId> {
+	/// `offender` equivocated in `slot`.
+	fn on_equivocation(offender: &AuthorId, slot: u32);
+
+	/// The worst case weight of `on_equivocation`.
+	fn on_equivocation_weight() -> Weight {
+		Weight::zero()
+	}
+}
+
+impl<T> OnOffenceHandler<T> for () {
+	fn on_equivocation(_offender: &T, _slot: u32) {}
+}
+
 /// A Trait to lookup runtime AccountIds from AuthorIds (probably NimbusIds)
 /// The trait is generic over the AccountId, becuase different runtimes use
 /// different notions of AccoutId. It is also generic over the AuthorId to
@@ -142,6 +251,11 @@ impl<T> CanAuthor<T> for () {
 /// and contains an AccountId directly.
 pub trait AccountLookup<AccountId> {
 	fn lookup_account(author: &NimbusId) -> Option<AccountId>;
+
+	/// Lookup authors using any signature scheme. By default only sr25519 authors are mapped.
+	fn lookup_multi_account(author: &MultiNimbusId) -> Option<AccountId> {
+		author.as_sr25519().and_then(Self::lookup_account)
+	}
 }
 
 // A dummy impl used in simple tests
@@ -180,5 +294,37 @@ sp_api::decl_runtime_apis! {
 	/// The runtime api used to predict whether a Nimbus author will be eligible in the given slot
 	pub trait NimbusApi {
 		fn can_author(author: NimbusId, relay_parent: u32, parent_header: &Block::Header) -> bool;
+
+		/// The complete set of authors that will be eligible in the given slot
+		#[api_version(2)]
+		fn eligible_authors(slot: u32, parent_header: &Block::Header) -> Vec<NimbusId>;
+
+		/// Like `can_author`, for authors using any signature scheme
+		#[api_version(3)]
+		fn can_author_multi(author: MultiNimbusId, relay_parent: u32, parent_header: &Block::Header) -> bool;
+
+		/// Whether the runtime rejects blocks whose pre-runtime digest reports another slot than
+		/// its slot beacon, as the author inherent pallet does
+		#[api_version(4)]
+		fn checks_pre_digest_slot() -> bool;
+	}
+
+	/// The runtime api used by collators to check their VRF output against the VRF threshold
+	/// filter's threshold before authoring.
+	pub trait VrfApi {
+		fn vrf_threshold() -> sp_runtime::Perbill;
+	}
+
+	/// The runtime api used to query the block production statistics kept by the author inherent
+	/// pallet. Only the most recent rounds are available, see the pallet's `HistoryDepth`.
+	pub trait AuthorStatsApi<AuthorId: Codec> {
+		/// The round of the highest slot seen so far
+		fn current_round() -> u32;
+
+		/// The statistics of one author in the given round, if it has any
+		fn author_stats(round: u32, author: AuthorId) -> Option<AuthorRoundStats>;
+
+		/// The statistics of every author in the given round
+		fn round_stats(round: u32) -> Vec<(AuthorId, AuthorRoundStats)>;
 	}
 }
@@ -0,0 +1,126 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Author identities for every signature scheme nimbus supports.
+//!
+//! `NimbusId` is an sr25519 key, and remains the identity used by most of nimbus. Authors whose
+//! keys live in hardware that does not support sr25519 may use ed25519 or ecdsa (secp256k1) keys
+//! instead. `MultiNimbusId` and `MultiNimbusSignature` wrap the three schemes. All of them are
+//! stored in the keystore under `NIMBUS_KEY_ID`.
+//!
+//! Both types use the regular SCALE enum encoding. Blocks sealed with sr25519 keys keep using
+//! the bare encodings they always have; see the `digests` module.
+
+use crate::{NimbusId, NimbusSignature};
+use parity_scale_codec::{Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+use sp_runtime::{RuntimeAppPublic, RuntimeDebug};
+
+mod nimbus_ed25519_crypto {
+	use sp_application_crypto::{app_crypto, ed25519};
+	app_crypto!(ed25519, crate::NIMBUS_KEY_ID);
+}
+
+mod nimbus_ecdsa_crypto {
+	use sp_application_crypto::{app_crypto, ecdsa};
+	app_crypto!(ecdsa, crate::NIMBUS_KEY_ID);
+}
+
+/// A nimbus author identifier using the ed25519 scheme.
+pub type NimbusEd25519Id = nimbus_ed25519_crypto::Public;
+
+/// A nimbus signature using the ed25519 scheme.
+pub type NimbusEd25519Signature = nimbus_ed25519_crypto::Signature;
+
+/// A nimbus author identifier using the ecdsa (secp256k1) scheme.
+pub type NimbusEcdsaId = nimbus_ecdsa_crypto::Public;
+
+/// A nimbus signature using the ecdsa (secp256k1) scheme.
+pub type NimbusEcdsaSignature = nimbus_ecdsa_crypto::Signature;
+
+/// A nimbus author identifier in any of the supported signature schemes.
+#[derive(
+	Clone, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug,
+)]
+pub enum MultiNimbusId {
+	Sr25519(NimbusId),
+	Ed25519(NimbusEd25519Id),
+	Ecdsa(NimbusEcdsaId),
+}
+
+/// A nimbus signature in any of the supported signature schemes.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, TypeInfo, RuntimeDebug)]
+pub enum MultiNimbusSignature {
+	Sr25519(NimbusSignature),
+	Ed25519(NimbusEd25519Signature),
+	Ecdsa(NimbusEcdsaSignature),
+}
+
+impl MultiNimbusId {
+	/// The sr25519 identifier, if this is one.
+	pub fn as_sr25519(&self) -> Option<&NimbusId> {
+		match self {
+			Self::Sr25519(id) => Some(id),
+			_ => None,
+		}
+	}
+
+	/// Check that `signature` is a signature of `msg` by this author, in the same scheme.
+	pub fn verify<M: AsRef<[u8]>>(&self, msg: &M, signature: &MultiNimbusSignature) -> bool {
+		match (self, signature) {
+			(Self::Sr25519(id), MultiNimbusSignature::Sr25519(sig)) => id.verify(msg, sig),
+			(Self::Ed25519(id), MultiNimbusSignature::Ed25519(sig)) => id.verify(msg, sig),
+			(Self::Ecdsa(id), MultiNimbusSignature::Ecdsa(sig)) => id.verify(msg, sig),
+			_ => false,
+		}
+	}
+}
+
+impl From<NimbusId> for MultiNimbusId {
+	fn from(id: NimbusId) -> Self {
+		Self::Sr25519(id)
+	}
+}
+
+impl From<NimbusEd25519Id> for MultiNimbusId {
+	fn from(id: NimbusEd25519Id) -> Self {
+		Self::Ed25519(id)
+	}
+}
+
+impl From<NimbusEcdsaId> for MultiNimbusId {
+	fn from(id: NimbusEcdsaId) -> Self {
+		Self::Ecdsa(id)
+	}
+}
+
+impl From<NimbusSignature> for MultiNimbusSignature {
+	fn from(signature: NimbusSignature) -> Self {
+		Self::Sr25519(signature)
+	}
+}
+
+impl From<NimbusEd25519Signature> for MultiNimbusSignature {
+	fn from(signature: NimbusEd25519Signature) -> Self {
+		Self::Ed25519(signature)
+	}
+}
+
+impl From<NimbusEcdsaSignature> for MultiNimbusSignature {
+	fn from(signature: NimbusEcdsaSignature) -> Self {
+		Self::Ecdsa(signature)
+	}
+}
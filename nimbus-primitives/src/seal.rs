@@ -0,0 +1,313 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checking the seal of a nimbus block.
+//!
+//! The client-side verifier, the `BlockExecutor` run by relay chain validators, and manual seal
+//! must agree on which author a block claims and on whether its seal is valid. They all use the
+//! functions in this module, which also work in no_std.
+//!
+//! The author is read from the nimbus pre-runtime digest, of any version. Blocks built before
+//! that digest was introduced reported their author in a consensus digest instead, which is still
+//! accepted when no pre-runtime digest is present. Seals may use any scheme of `MultiNimbusId`,
+//! as long as it matches the author's.
+
+use crate::{
+	CompatibleDigestItem, MultiNimbusId, MultiNimbusSignature, NimbusId, NimbusPreDigest,
+	NIMBUS_ENGINE_ID,
+};
+use parity_scale_codec::Decode;
+use sp_application_crypto::ByteArray;
+use sp_runtime::generic::{Digest, DigestItem};
+use sp_runtime::traits::Header as HeaderT;
+use sp_runtime::{ConsensusEngineId, RuntimeDebug};
+
+/// The reasons a nimbus seal can be rejected.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum SealError {
+	/// The header has no digests, so it cannot be sealed.
+	NoDigests,
+	/// The last digest of the header is not a seal.
+	HeaderUnsealed,
+	/// The header is sealed by another consensus engine.
+	ForeignSeal(ConsensusEngineId),
+	/// No digest reports the author of the block.
+	MissingAuthor,
+	/// More than one digest reports the author of the block.
+	DuplicateAuthor,
+	/// The reported author is not a valid `NimbusId`.
+	InvalidAuthor,
+	/// The seal is not a valid signature by the reported author, or uses another scheme.
+	BadSignature,
+}
+
+/// The author reported in the given digest. A digest that reports its author more than once is
+/// rejected, as the reports could disagree.
+pub fn claimed_author(digest: &Digest) -> Result<MultiNimbusId, SealError> {
+	let mut pre_runtime = digest.logs.iter().filter_map(|item| match item {
+		DigestItem::PreRuntime(id, data) if *id == NIMBUS_ENGINE_ID => Some(data),
+		_ => None,
+	});
+	if let Some(data) = pre_runtime.next() {
+		if pre_runtime.next().is_some() {
+			return Err(SealError::DuplicateAuthor);
+		}
+		return NimbusPreDigest::decode(&mut &data[..])
+			.map(|pre_digest| pre_digest.author())
+			.map_err(|_| SealError::InvalidAuthor);
+	}
+
+	let mut consensus = digest.logs.iter().filter_map(|item| match item {
+		DigestItem::Consensus(id, author) if *id == NIMBUS_ENGINE_ID => Some(author),
+		_ => None,
+	});
+	let raw_author = consensus.next().ok_or(SealError::MissingAuthor)?;
+	if consensus.next().is_some() {
+		return Err(SealError::DuplicateAuthor);
+	}
+
+	// Legacy digests predate other signature schemes, so the author uses sr25519
+	NimbusId::from_slice(raw_author)
+		.map(Into::into)
+		.map_err(|_| SealError::InvalidAuthor)
+}
+
+/// Remove the seal from the header, and return the reported author, the seal digest and the
+/// signature it carries, without checking the signature. This is only safe for headers whose
+/// seal was already verified. The seal is removed even if it is malformed.
+pub fn strip_seal<H: HeaderT>(
+	header: &mut H,
+) -> Result<(MultiNimbusId, DigestItem, MultiNimbusSignature), SealError> {
+	// The seal is always the last digest
+	let seal = header.digest_mut().pop().ok_or(SealError::NoDigests)?;
+	let signature = match seal {
+		DigestItem::Seal(id, _) if id != NIMBUS_ENGINE_ID => Err(SealError::ForeignSeal(id)),
+		_ => seal.as_nimbus_multi_seal().ok_or(SealError::HeaderUnsealed),
+	}?;
+	let author = claimed_author(header.digest())?;
+
+	Ok((author, seal, signature))
+}
+
+/// Remove the seal from the header and check that it is a valid signature by the reported author.
+/// Returns the author and the seal digest. The seal is removed even if it is invalid.
+pub fn strip_and_verify_seal<H: HeaderT>(
+	header: &mut H,
+) -> Result<(MultiNimbusId, DigestItem), SealError> {
+	let (author, seal, signature) = strip_seal(header)?;
+
+	// The seal signs the hash of the header without the seal
+	if !author.verify(&header.hash(), &signature) {
+		return Err(SealError::BadSignature);
+	}
+
+	Ok((author, seal))
+}
+
+/// Check the seal of the header without modifying it, and return the author.
+pub fn verify_seal<H: HeaderT>(header: &H) -> Result<MultiNimbusId, SealError> {
+	strip_and_verify_seal(&mut header.clone()).map(|(author, _)| author)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::NimbusPair;
+	use sp_application_crypto::Pair;
+	use sp_runtime::testing::Header;
+
+	fn header_with(logs: Vec<DigestItem>) -> Header {
+		Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs },
+		)
+	}
+
+	fn seal(header: &mut Header, pair: &NimbusPair) {
+		let signature = pair.sign(header.hash().as_ref());
+		header.digest_mut().push(DigestItem::nimbus_seal(signature));
+	}
+
+	#[test]
+	fn valid_seal_is_stripped_and_verified() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let mut header = header_with(vec![DigestItem::nimbus_pre_digest(alice.public())]);
+		let pre_hash = header.hash();
+		seal(&mut header, &alice);
+
+		assert_eq!(verify_seal(&header), Ok(alice.public().into()));
+
+		let (author, seal) = strip_and_verify_seal(&mut header).unwrap();
+		assert_eq!(author, alice.public().into());
+		assert!(seal.as_nimbus_seal().is_some());
+		assert_eq!(header.hash(), pre_hash);
+	}
+
+	#[test]
+	fn seal_can_be_stripped_without_verification() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let bob = NimbusPair::from_seed(&[2; 32]);
+		let mut header = header_with(vec![DigestItem::nimbus_pre_digest(alice.public())]);
+		let pre_hash = header.hash();
+		seal(&mut header, &bob);
+
+		let (author, _, _) = strip_seal(&mut header).unwrap();
+		assert_eq!(author, alice.public().into());
+		assert_eq!(header.hash(), pre_hash);
+	}
+
+	#[test]
+	fn legacy_consensus_digest_is_accepted() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let mut header = header_with(vec![DigestItem::nimbus_consensus_digest(alice.public())]);
+		seal(&mut header, &alice);
+
+		assert_eq!(verify_seal(&header), Ok(alice.public().into()));
+	}
+
+	#[test]
+	fn pre_runtime_digest_takes_precedence() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let bob = NimbusPair::from_seed(&[2; 32]);
+		let mut header = header_with(vec![
+			DigestItem::nimbus_consensus_digest(bob.public()),
+			DigestItem::nimbus_pre_digest(alice.public()),
+		]);
+		seal(&mut header, &bob);
+
+		assert_eq!(verify_seal(&header), Err(SealError::BadSignature));
+	}
+
+	#[test]
+	fn headers_without_seal_are_rejected() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+
+		assert_eq!(verify_seal(&header_with(vec![])), Err(SealError::NoDigests));
+		assert_eq!(
+			verify_seal(&header_with(vec![DigestItem::nimbus_pre_digest(
+				alice.public()
+			)])),
+			Err(SealError::HeaderUnsealed)
+		);
+	}
+
+	#[test]
+	fn headers_without_valid_author_are_rejected() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+
+		let mut header = header_with(vec![]);
+		seal(&mut header, &alice);
+		assert_eq!(verify_seal(&header), Err(SealError::MissingAuthor));
+
+		let mut header = header_with(vec![DigestItem::PreRuntime(
+			NIMBUS_ENGINE_ID,
+			vec![1, 2, 3],
+		)]);
+		seal(&mut header, &alice);
+		assert_eq!(verify_seal(&header), Err(SealError::InvalidAuthor));
+	}
+
+	#[test]
+	fn duplicate_authors_are_rejected() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let bob = NimbusPair::from_seed(&[2; 32]);
+
+		let mut header = header_with(vec![
+			DigestItem::nimbus_pre_digest(alice.public()),
+			DigestItem::nimbus_pre_digest(bob.public()),
+		]);
+		seal(&mut header, &alice);
+		assert_eq!(verify_seal(&header), Err(SealError::DuplicateAuthor));
+
+		let mut header = header_with(vec![
+			DigestItem::nimbus_consensus_digest(alice.public()),
+			DigestItem::nimbus_consensus_digest(alice.public()),
+		]);
+		seal(&mut header, &alice);
+		assert_eq!(verify_seal(&header), Err(SealError::DuplicateAuthor));
+	}
+
+	#[test]
+	fn seals_of_other_engines_are_rejected() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let mut header = header_with(vec![DigestItem::nimbus_pre_digest(alice.public())]);
+		header
+			.digest_mut()
+			.push(DigestItem::Seal(*b"aura", vec![1, 2, 3]));
+
+		assert_eq!(verify_seal(&header), Err(SealError::ForeignSeal(*b"aura")));
+	}
+
+	#[test]
+	fn seal_by_someone_else_is_rejected() {
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let bob = NimbusPair::from_seed(&[2; 32]);
+		let mut header = header_with(vec![DigestItem::nimbus_pre_digest(alice.public())]);
+		seal(&mut header, &bob);
+
+		assert_eq!(verify_seal(&header), Err(SealError::BadSignature));
+	}
+
+	#[test]
+	fn ed25519_seal_is_verified() {
+		use crate::multi_signer::{NimbusEd25519Id, NimbusEd25519Signature};
+		use crate::MultiNimbusSignature;
+		use sp_core::ed25519;
+
+		let pair = ed25519::Pair::from_seed(&[1; 32]);
+		let author = MultiNimbusId::from(NimbusEd25519Id::from(pair.public()));
+		let mut header = header_with(vec![DigestItem::nimbus_versioned_pre_digest(
+			NimbusPreDigest::V2 {
+				author: author.clone(),
+				slot: 7,
+				extension: None,
+			},
+		)]);
+		let signature = NimbusEd25519Signature::from(pair.sign(header.hash().as_ref()));
+		header
+			.digest_mut()
+			.push(DigestItem::nimbus_multi_seal(MultiNimbusSignature::from(
+				signature,
+			)));
+
+		assert_eq!(verify_seal(&header), Ok(author));
+	}
+
+	#[test]
+	fn seal_in_another_scheme_is_rejected() {
+		use crate::multi_signer::NimbusEd25519Id;
+		use sp_core::ed25519;
+
+		// The same seed in another scheme is another key
+		let alice = NimbusPair::from_seed(&[1; 32]);
+		let author = MultiNimbusId::from(NimbusEd25519Id::from(
+			ed25519::Pair::from_seed(&[1; 32]).public(),
+		));
+		let mut header = header_with(vec![DigestItem::nimbus_versioned_pre_digest(
+			NimbusPreDigest::V2 {
+				author,
+				slot: 7,
+				extension: None,
+			},
+		)]);
+		seal(&mut header, &alice);
+
+		assert_eq!(verify_seal(&header), Err(SealError::BadSignature));
+	}
+}
@@ -0,0 +1,89 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Primitives shared by the VRF threshold filter and the client-side worker.
+//!
+//! An author proves its eligibility by including an sr25519 VRF signature, made with its
+//! `NimbusId` key, in a dedicated pre-runtime digest. The VRF input is a transcript over the
+//! slot and some on-chain randomness. The randomness for each block is derived from the VRF
+//! output of its parent, or from the parent hash if the parent carried no VRF digest. Because
+//! that only depends on the parent header, the client can compute it without a runtime call.
+
+use crate::NimbusId;
+use parity_scale_codec::Encode;
+use sp_application_crypto::ByteArray;
+use sp_core::crypto::VrfPublic;
+use sp_core::sr25519;
+use sp_runtime::{ConsensusEngineId, Perbill};
+
+pub use sp_core::sr25519::vrf::{VrfOutput, VrfSignData, VrfSignature, VrfTranscript};
+
+/// The ConsensusEngineId of the pre-runtime digest that carries the author's VRF signature.
+/// It is distinct from `NIMBUS_ENGINE_ID` so it is never confused with the author digest.
+pub const VRF_ENGINE_ID: ConsensusEngineId = *b"nvrf";
+
+/// The label of the VRF transcript.
+pub const VRF_TRANSCRIPT_LABEL: &[u8] = b"nimbus-vrf";
+
+/// The context used to turn the VRF in-out pair into output bytes.
+pub const VRF_INOUT_CONTEXT: &[u8] = b"nimbus-vrf-output";
+
+/// Derive the randomness that a block's VRF is computed over from its parent.
+pub fn make_vrf_randomness(parent_vrf_output: Option<&VrfOutput>, parent_hash: &[u8]) -> [u8; 32] {
+	match parent_vrf_output {
+		Some(output) => sp_core::blake2_256(&output.encode()),
+		None => sp_core::blake2_256(parent_hash),
+	}
+}
+
+/// Build the data that is signed by the author's VRF key for the given slot and randomness.
+pub fn make_vrf_sign_data(slot: u32, randomness: &[u8; 32]) -> VrfSignData {
+	VrfTranscript::new(
+		VRF_TRANSCRIPT_LABEL,
+		&[(b"slot", &slot.to_le_bytes()), (b"randomness", randomness)],
+	)
+	.into_sign_data()
+}
+
+/// Verify a VRF signature by `author` over the given slot and randomness.
+///
+/// Returns the VRF output as a number, or `None` if the proof does not check out.
+pub fn verify_vrf(
+	author: &NimbusId,
+	slot: u32,
+	randomness: &[u8; 32],
+	signature: &VrfSignature,
+) -> Option<u128> {
+	let public = sr25519::Public::from_slice(author.as_slice()).ok()?;
+	let sign_data = make_vrf_sign_data(slot, randomness);
+
+	if !public.vrf_verify(&sign_data, signature) {
+		return None;
+	}
+
+	let bytes: [u8; 16] = signature
+		.output
+		.make_bytes(VRF_INOUT_CONTEXT, sign_data.as_ref(), &public)
+		.ok()?;
+
+	Some(u128::from_le_bytes(bytes))
+}
+
+/// Whether a VRF output qualifies its author under the given threshold. The threshold is the
+/// probability that any single author is eligible in a given slot.
+pub fn is_below_threshold(output: u128, threshold: Perbill) -> bool {
+	output < threshold.mul_floor(u128::MAX)
+}
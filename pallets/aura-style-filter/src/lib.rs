@@ -42,11 +42,16 @@
 use frame_support::pallet;
 pub use pallet::*;
 
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
 #[pallet]
 pub mod pallet {
 
 	use frame_support::pallet_prelude::*;
-	use sp_std::vec::Vec;
+	use sp_std::{vec, vec::Vec};
 
 	//TODO Now that the CanAuthor trait takes a slot number, I don't think this even needs to be a pallet.
 	// I think it could eb jsut a simple type.
@@ -76,4 +81,19 @@ pub mod pallet {
 			account == active_author
 		}
 	}
+
+	// The exhaustive variant of the check above. Exactly one author is eligible in each slot,
+	// unless there are no potential authors at all.
+	impl<T: Config> nimbus_primitives::EligibleAuthors<T::AccountId> for Pallet<T> {
+		fn eligible_authors(slot: &u32) -> Vec<T::AccountId> {
+			let mut active: Vec<T::AccountId> = T::PotentialAuthors::get();
+
+			if active.is_empty() {
+				return Vec::new();
+			}
+
+			let index = *slot as usize % active.len();
+			vec![active.swap_remove(index)]
+		}
+	}
 }
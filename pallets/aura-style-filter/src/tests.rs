@@ -0,0 +1,42 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::mock::*;
+use nimbus_primitives::{CanAuthor, EligibleAuthors};
+
+#[test]
+fn test_one_author_is_eligible_in_each_slot() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(AuraStyleFilter::eligible_authors(&0), vec![1]);
+		assert_eq!(AuraStyleFilter::eligible_authors(&3), vec![4]);
+		assert_eq!(AuraStyleFilter::eligible_authors(&7), vec![3]);
+	});
+}
+
+#[test]
+fn test_eligible_authors_agrees_with_can_author() {
+	new_test_ext().execute_with(|| {
+		for slot in 0..12 {
+			let eligible = AuraStyleFilter::eligible_authors(&slot);
+			for author in Authors::get() {
+				assert_eq!(
+					eligible.contains(&author),
+					AuraStyleFilter::can_author(&author, &slot)
+				);
+			}
+		}
+	});
+}
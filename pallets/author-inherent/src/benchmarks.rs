@@ -16,15 +16,72 @@
 
 #![cfg(feature = "runtime-benchmarks")]
 
-use crate::{Call, Config, Pallet};
+use crate::{
+	AuthorStats, Call, Config, ConsecutiveMissedSlots, Pallet, RoundAuthorCount, StatsBucketRound,
+};
 use frame_benchmarking::benchmarks;
+use frame_support::{traits::Get, weights::Weight};
 use frame_system::RawOrigin;
-use nimbus_primitives::CanAuthor;
-use nimbus_primitives::SlotBeacon;
+use nimbus_primitives::{AuthorRoundStats, CanAuthor, SlotBeacon};
+use parity_scale_codec::{Decode, Encode};
+use sp_runtime::traits::TrailingZeroInput;
+
+/// A distinct author for each index.
+fn author<T: Config>(index: u32) -> T::AuthorId {
+	T::AuthorId::decode(&mut TrailingZeroInput::new(&index.encode()))
+		.expect("infinite input is enough to decode an author")
+}
+
 benchmarks! {
 	kick_off_authorship_validation {
 		// The slot inserted needs to be higher than that already in storage
 		T::SlotBeacon::set_slot(100);
 		Pallet::<T>::set_eligible_author(&T::SlotBeacon::slot());
 	}: _(RawOrigin::None)
+
+	claim_round {
+		let a in 0 .. T::MaxAuthorsPerRound::get();
+
+		// The bucket of the claimed round is occupied by an older round with `a` authors.
+		let round = T::HistoryDepth::get().max(1);
+		StatsBucketRound::<T>::insert(0, 0);
+		for i in 0 .. a {
+			AuthorStats::<T>::insert(0, author::<T>(i), AuthorRoundStats::default());
+		}
+		RoundAuthorCount::<T>::insert(0, a);
+		let mut weight = Weight::zero();
+	}: {
+		Pallet::<T>::claim_round(round, &mut weight);
+	}
+	verify {
+		assert_eq!(StatsBucketRound::<T>::get(0), Some(round));
+		assert_eq!(RoundAuthorCount::<T>::get(0), 0);
+	}
+
+	update_stats {
+		// The worst case adds a new author to the round.
+		let author = author::<T>(0);
+		let mut weight = Weight::zero();
+	}: {
+		Pallet::<T>::update_stats(0, &author, &mut weight, |stats| {
+			stats.slots_eligible = stats.slots_eligible.saturating_add(1);
+		});
+	}
+	verify {
+		assert_eq!(RoundAuthorCount::<T>::get(0), 1);
+	}
+
+	note_missed_slot {
+		// The threshold is already passed, so `OnAuthorOffline`, whose weight is added
+		// separately, is not called.
+		let author = author::<T>(0);
+		let missed = T::MissedSlotsThreshold::get().saturating_add(1);
+		ConsecutiveMissedSlots::<T>::insert(&author, missed);
+		let mut weight = Weight::zero();
+	}: {
+		Pallet::<T>::note_missed_slot(author.clone(), 1, &mut weight);
+	}
+	verify {
+		assert_eq!(ConsecutiveMissedSlots::<T>::get(&author), missed + 1);
+	}
 }
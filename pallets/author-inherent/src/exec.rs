@@ -21,9 +21,7 @@ use frame_support::traits::ExecuteBlock;
 use sp_api::{BlockT, HeaderT};
 // For some reason I can't get these logs to actually print
 use log::debug;
-use nimbus_primitives::{digests::CompatibleDigestItem, NimbusId, NIMBUS_ENGINE_ID};
-use sp_application_crypto::ByteArray;
-use sp_runtime::{generic::DigestItem, RuntimeAppPublic};
+use nimbus_primitives::strip_and_verify_seal;
 
 /// Block executive to be used by relay chain validators when validating parachain blocks built
 /// with the nimubs consensus family.
@@ -31,9 +29,9 @@ use sp_runtime::{generic::DigestItem, RuntimeAppPublic};
 /// This will strip the seal digest, and confirm that it contains a valid signature
 /// By the block author reported in the author inherent.
 ///
-/// Essentially this contains the logic of the verifier plus the inner executive.
+/// Essentially this contains the logic of the verifier plus the inner executive. The seal is
+/// checked with `nimbus_primitives::strip_and_verify_seal`, just like the verifier does.
 /// TODO Degisn improvement:
-/// Can we share code with the verifier?
 /// Can this struct take a verifier as an associated type?
 /// Or maybe this will just get simpler in general when https://github.com/paritytech/polkadot/issues/2888 lands
 pub struct BlockExecutor<T, I>(sp_std::marker::PhantomData<(T, I)>);
@@ -48,50 +46,14 @@ where
 
 		debug!(target: "executive", "In hacked Executive. Initial digests are {:?}", header.digest());
 
-		// Set the seal aside for checking.
-		let seal = header
-			.digest_mut()
-			.pop()
-			.expect("Seal digest is present and is last item");
+		// Strip the seal and check that it is valid.
+		let (claimed_author, seal) = strip_and_verify_seal(&mut header)
+			.unwrap_or_else(|e| panic!("Invalid nimbus seal: {:?}", e));
 
 		debug!(target: "executive", "In hacked Executive. digests after stripping {:?}", header.digest());
 		debug!(target: "executive", "The seal we got {:?}", seal);
-
-		let signature = seal
-			.as_nimbus_seal()
-			.unwrap_or_else(|| panic!("HeaderUnsealed"));
-
-		debug!(target: "executive", "🪲 Header hash after popping digest {:?}", header.hash());
-
-		debug!(target: "executive", "🪲 Signature according to executive is {:?}", signature);
-
-		// Grab the author information from the preruntime digest
-		//TODO use the trait
-		let claimed_author = header
-			.digest()
-			.logs
-			.iter()
-			.find_map(|digest| match *digest {
-				DigestItem::PreRuntime(id, ref author_id) if id == NIMBUS_ENGINE_ID => {
-					Some(author_id.clone())
-				}
-				_ => None,
-			})
-			.expect("Expected pre-runtime digest that contains author id bytes");
-
 		debug!(target: "executive", "🪲 Claimed Author according to executive is {:?}", claimed_author);
 
-		// Verify the signature
-		let valid_signature = NimbusId::from_slice(&claimed_author)
-			.expect("Expected claimed author to be a valid NimbusId.")
-			.verify(&header.hash(), &signature);
-
-		debug!(target: "executive", "🪲 Valid signature? {:?}", valid_signature);
-
-		if !valid_signature {
-			panic!("Block signature invalid");
-		}
-
 		// Now that we've verified the signature, hand execution off to the inner executor
 		// which is probably the normal frame executive.
 		I::execute_block(Block::new(header, extrinsics));
@@ -22,11 +22,14 @@
 
 use frame_support::traits::{FindAuthor, Get};
 use nimbus_primitives::{
-	AccountLookup, CanAuthor, NimbusId, SlotBeacon, INHERENT_IDENTIFIER, NIMBUS_ENGINE_ID,
+	AccountLookup, AuthorRoundStats, CanAuthor, CompatibleDigestItem, EligibleAuthors,
+	EventHandler, MultiNimbusId, NimbusId, NimbusPreDigest, OnAuthorOffline, SlotBeacon,
+	INHERENT_IDENTIFIER, NIMBUS_ENGINE_ID,
 };
 use parity_scale_codec::{Decode, Encode, FullCodec};
 use sp_inherents::{InherentIdentifier, IsFatalError};
 use sp_runtime::{ConsensusEngineId, RuntimeString};
+use sp_std::vec::Vec;
 
 mod exec;
 pub use exec::BlockExecutor;
@@ -47,6 +50,7 @@ mod tests;
 pub mod pallet {
 	use super::*;
 	use crate::weights::WeightInfo;
+	use frame_support::dispatch::PostDispatchInfo;
 	use frame_support::pallet_prelude::*;
 	use frame_system::pallet_prelude::*;
 
@@ -56,6 +60,9 @@ pub mod pallet {
 
 	#[pallet::config]
 	pub trait Config: frame_system::Config {
+		/// The overarching event type
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
 		/// Type used to refer to a block author.
 		type AuthorId: sp_std::fmt::Debug + PartialEq + Clone + FullCodec + TypeInfo + MaxEncodedLen;
 
@@ -74,6 +81,58 @@ pub mod pallet {
 		/// Some way of determining the current slot for purposes of verifying the author's eligibility
 		type SlotBeacon: SlotBeacon;
 
+		/// Something that is notified of the author of each block, once the author has been
+		/// validated. Use this rather than reading the `Author` storage item from other pallets.
+		type EventHandler: EventHandler<Self::AuthorId>;
+
+		/// The exhaustive set of authors eligible in a slot. This is only used to count the slots
+		/// each author was eligible for, so it should agree with `CanAuthor`. Use `()` if the
+		/// filter cannot enumerate its authors; eligibility will then not be counted.
+		type EligibleAuthors: EligibleAuthors<Self::AuthorId>;
+
+		/// The number of slots in a round. Block production statistics are kept per round.
+		#[pallet::constant]
+		type SlotsPerRound: Get<u32>;
+
+		/// The number of rounds for which block production statistics are kept.
+		#[pallet::constant]
+		type HistoryDepth: Get<u32>;
+
+		/// The maximum number of authors whose statistics are kept in a single round.
+		/// Authors beyond this limit are not counted.
+		#[pallet::constant]
+		type MaxAuthorsPerRound: Get<u32>;
+
+		/// The maximum number of eligible authors of a slot that are counted and checked for a
+		/// missed slot. It should be at least the number of authors `EligibleAuthors` returns for
+		/// a slot, or the authors beyond it are never reported as offline.
+		#[pallet::constant]
+		type MaxEligibleAuthors: Get<u32>;
+
+		/// The maximum number of skipped slots that are checked for missing authors in a single
+		/// block. When more slots were skipped, only the most recent ones are checked.
+		///
+		/// Use zero when a block is not expected in every slot, which disables missed slot
+		/// detection. This is the case with a slot beacon that follows the relay chain block
+		/// number, because a parachain does not author on every relay chain block: each relay
+		/// chain block without a parachain block would count as a missed slot.
+		#[pallet::constant]
+		type MaxSkippedSlots: Get<u32>;
+
+		/// The number of eligible slots an author must miss in a row to be reported to
+		/// `OnAuthorOffline`. Use zero to never report.
+		#[pallet::constant]
+		type MissedSlotsThreshold: Get<u32>;
+
+		/// Notified when an author reaches `MissedSlotsThreshold` missed slots in a row.
+		type OnAuthorOffline: OnAuthorOffline<Self::AuthorId>;
+
+		/// The maximum number of blocks that may be authored in the same slot. Use 1 to require a
+		/// strictly increasing slot in every block. Higher values let collators that use
+		/// asynchronous backing author several blocks per relay chain slot.
+		#[pallet::constant]
+		type MaxBlocksPerSlot: Get<u32>;
+
 		type WeightInfo: WeightInfo;
 	}
 
@@ -91,6 +150,15 @@ pub mod pallet {
 		CannotBeAuthor,
 	}
 
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The author of this block passed validation in the given slot.
+		AuthorValidated { author: T::AuthorId, slot: u32 },
+		/// An eligible author did not produce a block in the given slot.
+		AuthorMissedSlot { author: T::AuthorId, slot: u32 },
+	}
+
 	/// Author of current block.
 	#[pallet::storage]
 	pub type Author<T: Config> = StorageValue<_, T::AuthorId, OptionQuery>;
@@ -100,6 +168,37 @@ pub mod pallet {
 	#[pallet::storage]
 	pub type HighestSlotSeen<T: Config> = StorageValue<_, u32, ValueQuery>;
 
+	/// The number of blocks authored so far in `HighestSlotSeen`.
+	#[pallet::storage]
+	pub type BlocksInSlot<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+	/// Block production statistics per round and author.
+	#[pallet::storage]
+	pub type AuthorStats<T: Config> = StorageDoubleMap<
+		_,
+		Twox64Concat,
+		u32,
+		Blake2_128Concat,
+		T::AuthorId,
+		AuthorRoundStats,
+		OptionQuery,
+	>;
+
+	/// The number of authors with statistics in each round.
+	#[pallet::storage]
+	pub type RoundAuthorCount<T: Config> = StorageMap<_, Twox64Concat, u32, u32, ValueQuery>;
+
+	/// The round whose statistics occupy each of the `HistoryDepth` history buckets. A round uses
+	/// bucket `round % HistoryDepth`, and clears the statistics of the previous occupant.
+	#[pallet::storage]
+	pub type StatsBucketRound<T: Config> = StorageMap<_, Twox64Concat, u32, u32, OptionQuery>;
+
+	/// The number of eligible slots each author has missed since it last authored a block.
+	#[pallet::storage]
+	#[pallet::getter(fn consecutive_missed_slots)]
+	pub type ConsecutiveMissedSlots<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AuthorId, u32, ValueQuery>;
+
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
 		fn on_initialize(_: T::BlockNumber) -> Weight {
@@ -122,27 +221,71 @@ pub mod pallet {
 		// This should go into on_post_inherents when it is ready https://github.com/paritytech/substrate/pull/10128
 		// TODO better weight. For now we just set a somewhat conservative fudge factor
 		#[pallet::call_index(0)]
-		#[pallet::weight((T::WeightInfo::kick_off_authorship_validation(), DispatchClass::Mandatory))]
+		#[pallet::weight((
+			T::WeightInfo::kick_off_authorship_validation()
+				.saturating_add(T::EventHandler::note_author_weight())
+				.saturating_add(Pallet::<T>::max_stats_weight()),
+			DispatchClass::Mandatory,
+		))]
 		pub fn kick_off_authorship_validation(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
 			ensure_none(origin)?;
 
-			// First check that the slot number is valid (greater than the previous highest)
+			// First check that the slot number is valid (greater than the previous highest, or
+			// equal to it while the slot has room for more blocks)
 			let slot = T::SlotBeacon::slot();
-			assert!(
-				slot > HighestSlotSeen::<T>::get(),
-				"Block invalid; Supplied slot number is not high enough"
-			);
+			// There is no previous slot before the first block, so nothing can have been missed.
+			let previous_slot = HighestSlotSeen::<T>::try_get().ok();
+			if previous_slot == Some(slot) && T::MaxBlocksPerSlot::get() > 1 {
+				// The previous block was in this slot, so at least one block has been authored in it,
+				// even if this counter was not kept yet.
+				let blocks_in_slot = BlocksInSlot::<T>::get().max(1);
+				assert!(
+					blocks_in_slot < T::MaxBlocksPerSlot::get(),
+					"Block invalid; Too many blocks in this slot"
+				);
+				BlocksInSlot::<T>::put(blocks_in_slot + 1);
+			} else {
+				assert!(
+					slot > previous_slot.unwrap_or_default(),
+					"Block invalid; Supplied slot number is not high enough"
+				);
+				BlocksInSlot::<T>::put(1);
+			}
+
+			// A versioned pre-runtime digest reports the slot as well. Equivocation reports and the
+			// client's fork choice rely on it, so it must be the slot the runtime sees.
+			if let Some(claimed_slot) = Self::pre_digest_slot() {
+				assert!(
+					claimed_slot == slot,
+					"Block invalid; Pre-runtime digest reports the wrong slot"
+				);
+			}
 
 			// Now check that the author is valid in this slot
+			let author = Self::get();
 			assert!(
-				T::CanAuthor::can_author(&Self::get(), &slot),
+				T::CanAuthor::can_author(&author, &slot),
 				"Block invalid, supplied author is not eligible."
 			);
 
 			// Once that is validated, update the stored slot number
 			HighestSlotSeen::<T>::put(slot);
 
-			Ok(Pays::No.into())
+			// Keep track of how each author is performing
+			let stats_weight = Self::record_stats(&author, previous_slot, slot);
+
+			// And let the rest of the runtime know who authored this block
+			T::EventHandler::note_author(author.clone());
+			Self::deposit_event(Event::AuthorValidated { author, slot });
+
+			Ok(PostDispatchInfo {
+				actual_weight: Some(
+					T::WeightInfo::kick_off_authorship_validation()
+						.saturating_add(T::EventHandler::note_author_weight())
+						.saturating_add(stats_weight),
+				),
+				pays_fee: Pays::No,
+			})
 		}
 	}
 
@@ -173,6 +316,203 @@ pub mod pallet {
 		}
 	}
 
+	impl<T: Config> Pallet<T> {
+		/// The round that the given slot belongs to.
+		pub fn round_of(slot: u32) -> u32 {
+			slot / T::SlotsPerRound::get().max(1)
+		}
+
+		/// The round of the highest slot seen so far.
+		pub fn current_round() -> u32 {
+			Self::round_of(HighestSlotSeen::<T>::get())
+		}
+
+		/// The statistics of one author in the given round. Rounds older than the history depth
+		/// have no statistics.
+		pub fn author_stats(round: u32, author: &T::AuthorId) -> Option<AuthorRoundStats> {
+			if !Self::is_in_history(round) {
+				return None;
+			}
+			AuthorStats::<T>::get(round, author)
+		}
+
+		/// The statistics of every author in the given round.
+		pub fn round_stats(round: u32) -> Vec<(T::AuthorId, AuthorRoundStats)> {
+			if !Self::is_in_history(round) {
+				return Vec::new();
+			}
+			AuthorStats::<T>::iter_prefix(round).collect()
+		}
+
+		/// The slot reported by the nimbus pre-runtime digest of the current block. Version 1
+		/// digests do not report a slot.
+		fn pre_digest_slot() -> Option<u32> {
+			<frame_system::Pallet<T>>::digest()
+				.logs
+				.iter()
+				.find_map(|digest| digest.as_nimbus_versioned_pre_digest())?
+				.slot()
+		}
+
+		fn is_in_history(round: u32) -> bool {
+			let current = Self::current_round();
+			round <= current && current - round < T::HistoryDepth::get().max(1)
+		}
+		/// The worst case weight of `record_stats`.
+		pub(crate) fn max_stats_weight() -> Weight {
+			let max_authors = T::MaxAuthorsPerRound::get();
+			let max_eligible = T::MaxEligibleAuthors::get() as u64;
+			let max_skipped = T::MaxSkippedSlots::get() as u64;
+			// Claiming a history bucket for the current round and each skipped slot's round, which
+			// clears a full round each time.
+			let buckets = T::WeightInfo::claim_round(max_authors).saturating_mul(max_skipped + 1);
+			// Updating the author and every eligible author of the current slot, and forgetting
+			// the author's missed slots.
+			let current = T::WeightInfo::update_stats()
+				.saturating_mul(max_eligible + 1)
+				.saturating_add(T::DbWeight::get().writes(1));
+			// Every eligible author of each skipped slot has its stats and missed slot counter
+			// updated, and may be reported as offline.
+			let missed = T::WeightInfo::update_stats()
+				.saturating_add(T::WeightInfo::note_missed_slot())
+				.saturating_add(T::OnAuthorOffline::on_author_offline_weight())
+				.saturating_mul(max_eligible * max_skipped);
+
+			buckets.saturating_add(current).saturating_add(missed)
+		}
+
+		/// Count the block authored by `author` in `slot`, the eligible authors of `slot`, and the
+		/// eligible authors of the slots skipped since `previous_slot`. Returns the weight consumed.
+		pub(crate) fn record_stats(
+			author: &T::AuthorId,
+			previous_slot: Option<u32>,
+			slot: u32,
+		) -> Weight {
+			let mut weight = Weight::zero();
+			let max_eligible = T::MaxEligibleAuthors::get() as usize;
+
+			// The eligible authors of the slots that were skipped since the previous block have
+			// missed them. Evaluating the filter now gives the right answer as long as it only
+			// depends on the slot and the parent block, which a skipped slot shares with us.
+			// Only the most recent `MaxSkippedSlots` are considered. The author of this block is
+			// evidently online, so it is not recorded as having missed any of them.
+			if let Some(previous_slot) = previous_slot {
+				let first_skipped = previous_slot
+					.saturating_add(1)
+					.max(slot.saturating_sub(T::MaxSkippedSlots::get()));
+				for skipped in first_skipped..slot {
+					let round = Self::round_of(skipped);
+					let tracked = Self::claim_round(round, &mut weight);
+					for missed in T::EligibleAuthors::eligible_authors(&skipped)
+						.into_iter()
+						.take(max_eligible)
+					{
+						let is_author = &missed == author;
+						if tracked {
+							Self::update_stats(round, &missed, &mut weight, |stats| {
+								stats.slots_eligible = stats.slots_eligible.saturating_add(1);
+								if !is_author {
+									stats.slots_missed = stats.slots_missed.saturating_add(1);
+								}
+							});
+						}
+						if !is_author {
+							Self::note_missed_slot(missed, skipped, &mut weight);
+						}
+					}
+				}
+			}
+
+			// The current slot always belongs to the newest round, so it can always be tracked.
+			let round = Self::round_of(slot);
+			Self::claim_round(round, &mut weight);
+			Self::update_stats(round, author, &mut weight, |stats| {
+				stats.blocks_authored = stats.blocks_authored.saturating_add(1);
+			});
+			// The eligible authors of a slot are only counted with its first block.
+			if previous_slot != Some(slot) {
+				for eligible in T::EligibleAuthors::eligible_authors(&slot)
+					.into_iter()
+					.take(max_eligible)
+				{
+					Self::update_stats(round, &eligible, &mut weight, |stats| {
+						stats.slots_eligible = stats.slots_eligible.saturating_add(1);
+					});
+				}
+			}
+
+			// The author is evidently online.
+			ConsecutiveMissedSlots::<T>::remove(author);
+			weight.saturating_add(T::DbWeight::get().writes(1))
+		}
+
+		/// Make sure the given round owns its history bucket, clearing the statistics of an older
+		/// occupant. Returns false if the bucket belongs to a newer round, in which case the given
+		/// round is out of history and must not be tracked.
+		pub(crate) fn claim_round(round: u32, weight: &mut Weight) -> bool {
+			let bucket = round % T::HistoryDepth::get().max(1);
+			let occupant = StatsBucketRound::<T>::get(bucket);
+
+			match occupant {
+				Some(occupant) if occupant >= round => {
+					*weight = weight.saturating_add(T::DbWeight::get().reads(1));
+					occupant == round
+				}
+				_ => {
+					let mut removed = 0;
+					if let Some(old_round) = occupant {
+						removed = AuthorStats::<T>::clear_prefix(
+							old_round,
+							T::MaxAuthorsPerRound::get(),
+							None,
+						)
+						.unique;
+						RoundAuthorCount::<T>::remove(old_round);
+					}
+					StatsBucketRound::<T>::insert(bucket, round);
+					*weight = weight.saturating_add(T::WeightInfo::claim_round(removed));
+					true
+				}
+			}
+		}
+
+		/// Update an author's statistics in the given round, unless the round is full.
+		pub(crate) fn update_stats(
+			round: u32,
+			author: &T::AuthorId,
+			weight: &mut Weight,
+			f: impl FnOnce(&mut AuthorRoundStats),
+		) {
+			AuthorStats::<T>::mutate(round, author, |maybe_stats| {
+				if maybe_stats.is_none() {
+					let count = RoundAuthorCount::<T>::get(round);
+					if count >= T::MaxAuthorsPerRound::get() {
+						return;
+					}
+					RoundAuthorCount::<T>::insert(round, count + 1);
+				}
+				f(maybe_stats.get_or_insert_with(Default::default));
+			});
+			*weight = weight.saturating_add(T::WeightInfo::update_stats());
+		}
+
+		/// Record that `author` missed `slot`, and report it once it has missed too many in a row.
+		pub(crate) fn note_missed_slot(author: T::AuthorId, slot: u32, weight: &mut Weight) {
+			let missed = ConsecutiveMissedSlots::<T>::mutate(&author, |missed| {
+				*missed = missed.saturating_add(1);
+				*missed
+			});
+			*weight = weight.saturating_add(T::WeightInfo::note_missed_slot());
+
+			if missed == T::MissedSlotsThreshold::get() {
+				T::OnAuthorOffline::on_author_offline(&author, missed);
+				*weight = weight.saturating_add(T::OnAuthorOffline::on_author_offline_weight());
+			}
+
+			Self::deposit_event(Event::AuthorMissedSlot { author, slot });
+		}
+	}
+
 	impl<T: Config> FindAuthor<T::AuthorId> for Pallet<T> {
 		fn find_author<'a, I>(digests: I) -> Option<T::AuthorId>
 		where
@@ -180,10 +520,13 @@ pub mod pallet {
 		{
 			for (id, mut data) in digests.into_iter() {
 				if id == NIMBUS_ENGINE_ID {
-					let author_id = NimbusId::decode(&mut data)
-						.expect("NimbusId encoded in preruntime digest must be valid");
+					// Accepts both the bare NimbusId of version 1 and later versioned digests,
+					// whose author may use any signature scheme
+					let pre_digest = NimbusPreDigest::decode(&mut data)
+						.expect("Nimbus preruntime digest must be valid");
+					let author_id = pre_digest.author();
 
-					let author_account = T::AccountLookup::lookup_account(&author_id)
+					let author_account = T::AccountLookup::lookup_multi_account(&author_id)
 						.expect("No Account Mapped to this NimbusId");
 
 					return Some(author_account);
@@ -200,6 +543,21 @@ pub mod pallet {
 		}
 	}
 
+	/// Like the `NimbusId` implementation below, for authors using any signature scheme.
+	impl<T: Config> CanAuthor<MultiNimbusId> for Pallet<T> {
+		fn can_author(author: &MultiNimbusId, slot: &u32) -> bool {
+			match T::AccountLookup::lookup_multi_account(author) {
+				Some(account) => T::CanAuthor::can_author(&account, slot),
+				// Authors whose account lookups fail will not be eligible
+				None => false,
+			}
+		}
+		#[cfg(feature = "runtime-benchmarks")]
+		fn set_eligible_author(slot: &u32) {
+			<Self as CanAuthor<NimbusId>>::set_eligible_author(slot)
+		}
+	}
+
 	/// To learn whether a given NimbusId can author, as opposed to an account id, you
 	/// can ask this pallet directly. It will do the mapping for you.
 	impl<T: Config> CanAuthor<NimbusId> for Pallet<T> {
@@ -16,14 +16,16 @@
 
 use crate::{self as pallet_testing, AccountLookup, NimbusId};
 use frame_support::parameter_types;
-use frame_support::traits::ConstU32;
-use frame_support::weights::RuntimeDbWeight;
+use frame_support::traits::{ConstU32, ConstU64};
+use frame_support::weights::{RuntimeDbWeight, Weight};
 use frame_system;
+use nimbus_primitives::{EligibleAuthors, EventHandler, IntervalBeacon, OnAuthorOffline};
 use sp_core::H256;
 use sp_runtime::{
 	testing::Header,
 	traits::{BlakeTwo256, IdentityLookup},
 };
+use std::cell::RefCell;
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 type Block = frame_system::mocking::MockBlock<Test>;
@@ -35,7 +37,8 @@ frame_support::construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic,
 	{
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
-		AuthorInherent: pallet_testing::{Pallet, Call, Storage},
+		Timestamp: pallet_timestamp::{Pallet, Storage},
+		AuthorInherent: pallet_testing::{Pallet, Call, Storage, Event<T>},
 	}
 );
 
@@ -75,15 +78,25 @@ impl frame_system::Config for Test {
 	type MaxConsumers = ConstU32<16>;
 }
 
-pub struct DummyBeacon {}
-impl nimbus_primitives::SlotBeacon for DummyBeacon {
-	fn slot() -> u32 {
-		0
-	}
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = ConstU64<1>;
+	type WeightInfo = ();
+}
+
+pub const SLOT_DURATION: u64 = 12_000;
+pub const GENESIS_TIME: u64 = 1_000_000;
+
+parameter_types! {
+	pub const SlotDuration: u64 = SLOT_DURATION;
+	pub const GenesisTime: u64 = GENESIS_TIME;
 }
 
 pub const ALICE: u64 = 1;
 pub const ALICE_NIMBUS: [u8; 32] = [1; 32];
+pub const BOB: u64 = 2;
+pub const CHARLIE: u64 = 3;
 pub struct MockAccountLookup;
 impl AccountLookup<u64> for MockAccountLookup {
 	fn lookup_account(nimbus_id: &NimbusId) -> Option<u64> {
@@ -97,11 +110,80 @@ impl AccountLookup<u64> for MockAccountLookup {
 	}
 }
 
+thread_local! {
+	/// The authors noted by the event handler, in order.
+	pub static NOTED_AUTHORS: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+}
+
+pub struct MockEventHandler;
+impl EventHandler<u64> for MockEventHandler {
+	fn note_author(author: u64) {
+		NOTED_AUTHORS.with(|authors| authors.borrow_mut().push(author));
+	}
+	fn note_author_weight() -> Weight {
+		Weight::from_parts(1_000, 0)
+	}
+}
+
+pub fn noted_authors() -> Vec<u64> {
+	NOTED_AUTHORS.with(|authors| authors.borrow().clone())
+}
+
+thread_local! {
+	/// The offline reports received by the offence hook, in order.
+	pub static OFFLINE_REPORTS: RefCell<Vec<(u64, u32)>> = RefCell::new(Vec::new());
+}
+
+pub struct MockOnAuthorOffline;
+impl OnAuthorOffline<u64> for MockOnAuthorOffline {
+	fn on_author_offline(author: &u64, missed_slots: u32) {
+		OFFLINE_REPORTS.with(|reports| reports.borrow_mut().push((*author, missed_slots)));
+	}
+}
+
+pub fn offline_reports() -> Vec<(u64, u32)> {
+	OFFLINE_REPORTS.with(|reports| reports.borrow().clone())
+}
+
+/// Alice, Bob and Charlie are eligible in every slot.
+pub struct MockEligibleAuthors;
+impl EligibleAuthors<u64> for MockEligibleAuthors {
+	fn eligible_authors(_: &u32) -> Vec<u64> {
+		vec![ALICE, BOB, CHARLIE]
+	}
+}
+
+pub const SLOTS_PER_ROUND: u32 = 10;
+pub const HISTORY_DEPTH: u32 = 3;
+pub const MAX_SKIPPED_SLOTS: u32 = 4;
+pub const MISSED_SLOTS_THRESHOLD: u32 = 3;
+
+parameter_types! {
+	pub const SlotsPerRound: u32 = SLOTS_PER_ROUND;
+	pub const HistoryDepth: u32 = HISTORY_DEPTH;
+	pub const MaxAuthorsPerRound: u32 = 2;
+	pub static MaxEligibleAuthors: u32 = 3;
+	pub const MaxSkippedSlots: u32 = MAX_SKIPPED_SLOTS;
+	pub const MissedSlotsThreshold: u32 = MISSED_SLOTS_THRESHOLD;
+	pub static MaxBlocksPerSlot: u32 = 1;
+}
+
 impl pallet_testing::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
 	type AuthorId = u64;
 	type AccountLookup = MockAccountLookup;
 	type CanAuthor = ();
-	type SlotBeacon = DummyBeacon;
+	type SlotBeacon = IntervalBeacon<Test, SlotDuration, GenesisTime>;
+	type EventHandler = MockEventHandler;
+	type EligibleAuthors = MockEligibleAuthors;
+	type SlotsPerRound = SlotsPerRound;
+	type HistoryDepth = HistoryDepth;
+	type MaxAuthorsPerRound = MaxAuthorsPerRound;
+	type MaxEligibleAuthors = MaxEligibleAuthors;
+	type MaxSkippedSlots = MaxSkippedSlots;
+	type MissedSlotsThreshold = MissedSlotsThreshold;
+	type OnAuthorOffline = MockOnAuthorOffline;
+	type MaxBlocksPerSlot = MaxBlocksPerSlot;
 	type WeightInfo = ();
 }
 
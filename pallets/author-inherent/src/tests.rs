@@ -15,9 +15,12 @@
 // along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::mock::*;
-use crate::pallet::Author;
+use crate::pallet::{Author, AuthorStats, BlocksInSlot, Config, HighestSlotSeen, RoundAuthorCount};
+use frame_support::assert_ok;
 use frame_support::traits::{OnFinalize, OnInitialize};
-use nimbus_primitives::{NimbusId, NIMBUS_ENGINE_ID};
+use nimbus_primitives::{
+	AuthorRoundStats, CompatibleDigestItem, NimbusId, NimbusPreDigest, SlotBeacon, NIMBUS_ENGINE_ID,
+};
 use parity_scale_codec::Encode;
 use sp_core::{ByteArray, H256};
 use sp_runtime::{Digest, DigestItem};
@@ -70,3 +73,414 @@ fn test_author_is_still_available_after_on_finalize() {
 		assert_eq!(Some(ALICE), <Author<Test>>::get());
 	});
 }
+
+#[test]
+fn author_is_found_in_versioned_pre_digest() {
+	new_test_ext().execute_with(|| {
+		let block_number = 1;
+		System::initialize(
+			&block_number,
+			&H256::default(),
+			&Digest {
+				logs: vec![DigestItem::nimbus_versioned_pre_digest(
+					NimbusPreDigest::V2 {
+						author: NimbusId::from_slice(&ALICE_NIMBUS).unwrap().into(),
+						slot: 7,
+						extension: Some(vec![1, 2, 3]),
+					},
+				)],
+			},
+		);
+
+		AuthorInherent::on_initialize(block_number);
+		assert_eq!(Some(ALICE), <Author<Test>>::get());
+	});
+}
+
+/// Start a block whose pre-runtime digest reports Alice and the given slot.
+fn initialize_with_pre_digest_slot(slot: u32) {
+	System::initialize(
+		&1,
+		&H256::default(),
+		&Digest {
+			logs: vec![DigestItem::nimbus_versioned_pre_digest(
+				NimbusPreDigest::V2 {
+					author: NimbusId::from_slice(&ALICE_NIMBUS).unwrap().into(),
+					slot,
+					extension: None,
+				},
+			)],
+		},
+	);
+	AuthorInherent::on_initialize(1);
+}
+
+#[test]
+fn pre_digest_slot_matching_the_slot_beacon_is_accepted() {
+	new_test_ext().execute_with(|| {
+		initialize_with_pre_digest_slot(3);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 3 * SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+	});
+}
+
+#[test]
+#[should_panic(expected = "Block invalid; Pre-runtime digest reports the wrong slot")]
+fn pre_digest_slot_must_match_the_slot_beacon() {
+	new_test_ext().execute_with(|| {
+		initialize_with_pre_digest_slot(7);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 3 * SLOT_DURATION);
+		let _ = AuthorInherent::kick_off_authorship_validation(RuntimeOrigin::none());
+	});
+}
+
+#[test]
+fn interval_beacon_derives_slot_from_timestamp() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(GENESIS_TIME + 3 * SLOT_DURATION + SLOT_DURATION / 2);
+		assert_eq!(<Test as Config>::SlotBeacon::slot(), 3);
+	});
+}
+
+#[test]
+fn interval_beacon_reports_slot_zero_before_genesis_time() {
+	new_test_ext().execute_with(|| {
+		Timestamp::set_timestamp(GENESIS_TIME - 1);
+		assert_eq!(<Test as Config>::SlotBeacon::slot(), 0);
+	});
+}
+
+#[test]
+fn interval_beacon_slots_advance_highest_slot_seen() {
+	new_test_ext().execute_with(|| {
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+		assert_eq!(HighestSlotSeen::<Test>::get(), 1);
+
+		// Skipping slots is fine, as long as the slot keeps increasing.
+		Timestamp::set_timestamp(GENESIS_TIME + 4 * SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+		assert_eq!(HighestSlotSeen::<Test>::get(), 4);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Block invalid; Supplied slot number is not high enough")]
+fn interval_beacon_rejects_second_block_in_same_slot() {
+	new_test_ext().execute_with(|| {
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 2 * SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+
+		Timestamp::set_timestamp(GENESIS_TIME + 3 * SLOT_DURATION - 1);
+		let _ = AuthorInherent::kick_off_authorship_validation(RuntimeOrigin::none());
+	});
+}
+
+#[test]
+fn blocks_in_same_slot_are_accepted_up_to_max_blocks_per_slot() {
+	new_test_ext().execute_with(|| {
+		MaxBlocksPerSlot::set(&3);
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 2 * SLOT_DURATION);
+		for blocks in 1..=3 {
+			assert_ok!(AuthorInherent::kick_off_authorship_validation(
+				RuntimeOrigin::none()
+			));
+			assert_eq!(BlocksInSlot::<Test>::get(), blocks);
+		}
+		assert_eq!(HighestSlotSeen::<Test>::get(), 2);
+
+		// A new slot starts a new count.
+		Timestamp::set_timestamp(GENESIS_TIME + 3 * SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+		assert_eq!(BlocksInSlot::<Test>::get(), 1);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Block invalid; Too many blocks in this slot")]
+fn blocks_beyond_max_blocks_per_slot_are_rejected() {
+	new_test_ext().execute_with(|| {
+		MaxBlocksPerSlot::set(&2);
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 2 * SLOT_DURATION);
+		for _ in 0..2 {
+			assert_ok!(AuthorInherent::kick_off_authorship_validation(
+				RuntimeOrigin::none()
+			));
+		}
+		let _ = AuthorInherent::kick_off_authorship_validation(RuntimeOrigin::none());
+	});
+}
+
+#[test]
+#[should_panic(expected = "Block invalid; Supplied slot number is not high enough")]
+fn earlier_slots_are_rejected_with_several_blocks_per_slot() {
+	new_test_ext().execute_with(|| {
+		MaxBlocksPerSlot::set(&2);
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 2 * SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+
+		Timestamp::set_timestamp(GENESIS_TIME + SLOT_DURATION);
+		let _ = AuthorInherent::kick_off_authorship_validation(RuntimeOrigin::none());
+	});
+}
+
+#[test]
+#[should_panic(expected = "Block invalid; Supplied slot number is not high enough")]
+fn interval_beacon_rejects_block_within_first_slot_after_genesis_time() {
+	new_test_ext().execute_with(|| {
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + SLOT_DURATION - 1);
+		let _ = AuthorInherent::kick_off_authorship_validation(RuntimeOrigin::none());
+	});
+}
+
+#[test]
+fn kick_off_authorship_validation_charges_event_handler_and_stats_weight() {
+	use crate::weights::WeightInfo;
+	use frame_support::dispatch::GetDispatchInfo;
+	use nimbus_primitives::EventHandler;
+
+	let info = crate::Call::<Test>::kick_off_authorship_validation {}.get_dispatch_info();
+	assert_eq!(
+		info.weight,
+		<() as WeightInfo>::kick_off_authorship_validation()
+			+ MockEventHandler::note_author_weight()
+			+ AuthorInherent::max_stats_weight()
+	);
+}
+
+#[test]
+fn validated_author_is_noted_and_announced() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		Author::<Test>::put(ALICE);
+
+		Timestamp::set_timestamp(GENESIS_TIME + 3 * SLOT_DURATION);
+		assert_ok!(AuthorInherent::kick_off_authorship_validation(
+			RuntimeOrigin::none()
+		));
+
+		assert_eq!(noted_authors(), vec![ALICE]);
+		System::assert_last_event(RuntimeEvent::AuthorInherent(
+			crate::Event::AuthorValidated {
+				author: ALICE,
+				slot: 3,
+			},
+		));
+	});
+}
+
+/// Validate a block authored by Alice in the given slot.
+fn author_in_slot(slot: u32) {
+	Author::<Test>::put(ALICE);
+	Timestamp::set_timestamp(GENESIS_TIME + slot as u64 * SLOT_DURATION);
+	assert_ok!(AuthorInherent::kick_off_authorship_validation(
+		RuntimeOrigin::none()
+	));
+}
+
+fn stats(blocks_authored: u32, slots_eligible: u32, slots_missed: u32) -> Option<AuthorRoundStats> {
+	Some(AuthorRoundStats {
+		blocks_authored,
+		slots_eligible,
+		slots_missed,
+	})
+}
+
+#[test]
+fn blocks_and_eligible_slots_are_counted_per_round() {
+	new_test_ext().execute_with(|| {
+		author_in_slot(1);
+		author_in_slot(2);
+		// Skips slots 3 to 9, of which only the last `MAX_SKIPPED_SLOTS` are counted
+		author_in_slot(SLOTS_PER_ROUND);
+
+		assert_eq!(AuthorInherent::current_round(), 1);
+		// Alice authors the block after the skipped slots, so she did not miss them
+		assert_eq!(AuthorInherent::author_stats(0, &ALICE), stats(2, 6, 0));
+		assert_eq!(AuthorInherent::author_stats(0, &BOB), stats(0, 6, 4));
+		assert_eq!(AuthorInherent::author_stats(1, &ALICE), stats(1, 1, 0));
+		assert_eq!(AuthorInherent::round_stats(1).len(), 2);
+	});
+}
+
+#[test]
+fn eligibility_is_counted_once_per_slot() {
+	new_test_ext().execute_with(|| {
+		MaxBlocksPerSlot::set(&2);
+		author_in_slot(1);
+		author_in_slot(1);
+
+		assert_eq!(AuthorInherent::author_stats(0, &ALICE), stats(2, 1, 0));
+		assert_eq!(AuthorInherent::author_stats(0, &BOB), stats(0, 1, 0));
+	});
+}
+
+#[test]
+fn authors_beyond_the_round_limit_are_not_counted() {
+	new_test_ext().execute_with(|| {
+		author_in_slot(1);
+
+		// Charlie is eligible, but the round only has room for Alice and Bob.
+		assert_eq!(AuthorInherent::author_stats(0, &CHARLIE), None);
+		assert_eq!(RoundAuthorCount::<Test>::get(0), 2);
+	});
+}
+
+#[test]
+fn stats_older_than_history_depth_are_pruned() {
+	new_test_ext().execute_with(|| {
+		author_in_slot(1);
+		author_in_slot(HISTORY_DEPTH * SLOTS_PER_ROUND - 1);
+		assert_eq!(AuthorInherent::author_stats(0, &ALICE), stats(1, 1, 0));
+
+		// Round `HISTORY_DEPTH` reuses the storage of round 0.
+		author_in_slot(HISTORY_DEPTH * SLOTS_PER_ROUND);
+		assert_eq!(AuthorInherent::author_stats(0, &ALICE), None);
+		assert_eq!(AuthorStats::<Test>::iter_prefix(0).count(), 0);
+		assert_eq!(RoundAuthorCount::<Test>::get(0), 0);
+		assert_eq!(
+			AuthorInherent::author_stats(HISTORY_DEPTH - 1, &ALICE),
+			stats(1, 1 + MAX_SKIPPED_SLOTS, 0)
+		);
+	});
+}
+
+#[test]
+fn stats_beyond_history_depth_are_not_served() {
+	new_test_ext().execute_with(|| {
+		author_in_slot(1);
+
+		// Skip ahead so that round 0 is out of history, without reusing its storage.
+		author_in_slot((HISTORY_DEPTH + 2) * SLOTS_PER_ROUND + 1);
+		assert!(AuthorStats::<Test>::get(0, ALICE).is_some());
+		assert_eq!(AuthorInherent::author_stats(0, &ALICE), None);
+		assert!(AuthorInherent::round_stats(0).is_empty());
+	});
+}
+
+fn missed_slot_events() -> Vec<(u64, u32)> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			RuntimeEvent::AuthorInherent(crate::Event::AuthorMissedSlot { author, slot }) => {
+				Some((author, slot))
+			}
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn first_block_reports_no_missed_slots() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		author_in_slot(10);
+
+		assert!(missed_slot_events().is_empty());
+		assert_eq!(AuthorInherent::consecutive_missed_slots(BOB), 0);
+	});
+}
+
+#[test]
+fn eligible_authors_of_skipped_slots_are_reported() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		author_in_slot(1);
+		author_in_slot(4);
+
+		assert_eq!(
+			missed_slot_events(),
+			vec![(BOB, 2), (CHARLIE, 2), (BOB, 3), (CHARLIE, 3)]
+		);
+		assert_eq!(AuthorInherent::consecutive_missed_slots(BOB), 2);
+		// Charlie is beyond `MaxAuthorsPerRound`, so he has no statistics, but he is still
+		// checked for missed slots.
+		assert_eq!(AuthorInherent::consecutive_missed_slots(CHARLIE), 2);
+		assert_eq!(AuthorInherent::author_stats(0, &CHARLIE), None);
+		// Alice authored this block, so she did not miss the slots before it.
+		assert_eq!(AuthorInherent::consecutive_missed_slots(ALICE), 0);
+		assert_eq!(AuthorInherent::author_stats(0, &ALICE), stats(2, 4, 0));
+	});
+}
+
+#[test]
+fn only_max_eligible_authors_are_checked_for_missed_slots() {
+	new_test_ext().execute_with(|| {
+		MaxEligibleAuthors::set(&2);
+		System::set_block_number(1);
+		author_in_slot(1);
+		author_in_slot(3);
+
+		assert_eq!(missed_slot_events(), vec![(BOB, 2)]);
+		assert_eq!(AuthorInherent::consecutive_missed_slots(CHARLIE), 0);
+	});
+}
+
+#[test]
+fn only_most_recent_skipped_slots_are_checked() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		author_in_slot(1);
+		author_in_slot(20);
+
+		assert_eq!(
+			AuthorInherent::consecutive_missed_slots(BOB),
+			MAX_SKIPPED_SLOTS
+		);
+		assert!(missed_slot_events()
+			.iter()
+			.all(|(_, slot)| *slot >= 20 - MAX_SKIPPED_SLOTS));
+	});
+}
+
+#[test]
+fn offline_author_is_reported_once_threshold_is_reached() {
+	new_test_ext().execute_with(|| {
+		// Bob misses every other slot
+		author_in_slot(1);
+		author_in_slot(3);
+		author_in_slot(5);
+		assert!(offline_reports().is_empty());
+
+		author_in_slot(7);
+		let reports = vec![
+			(BOB, MISSED_SLOTS_THRESHOLD),
+			(CHARLIE, MISSED_SLOTS_THRESHOLD),
+		];
+		assert_eq!(offline_reports(), reports);
+
+		// Bob is only reported once until he authors again
+		author_in_slot(9);
+		assert_eq!(offline_reports(), reports);
+		assert_eq!(
+			AuthorInherent::consecutive_missed_slots(BOB),
+			MISSED_SLOTS_THRESHOLD + 1
+		);
+	});
+}
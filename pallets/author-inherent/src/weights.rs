@@ -43,6 +43,9 @@
 // --output
 // weights/
 
+// The `claim_round`, `update_stats` and `note_missed_slot` weights are conservative estimates
+// based on their storage accesses, until they are benchmarked on reference hardware.
+
 #![cfg_attr(rustfmt, rustfmt_skip)]
 #![allow(unused_parens)]
 #![allow(unused_imports)]
@@ -53,6 +56,9 @@ use sp_std::marker::PhantomData;
 /// Weight functions needed for pallet_author_inherent.
 pub trait WeightInfo {
 	fn kick_off_authorship_validation() -> Weight;
+	fn claim_round(a: u32, ) -> Weight;
+	fn update_stats() -> Weight;
+	fn note_missed_slot() -> Weight;
 }
 
 /// Weights for pallet_author_inherent using the Substrate node and recommended hardware.
@@ -79,6 +85,38 @@ impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
 			.saturating_add(T::DbWeight::get().reads(6_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: AuthorInherent StatsBucketRound (r:1 w:1)
+	/// Proof Skipped: AuthorInherent StatsBucketRound (max_values: None, max_size: None, mode: Measured)
+	/// Storage: AuthorInherent AuthorStats (r:0 w:a)
+	/// Proof Skipped: AuthorInherent AuthorStats (max_values: None, max_size: None, mode: Measured)
+	/// Storage: AuthorInherent RoundAuthorCount (r:0 w:1)
+	/// Proof Skipped: AuthorInherent RoundAuthorCount (max_values: None, max_size: None, mode: Measured)
+	/// The range of component `a` is `[0, 32]`.
+	fn claim_round(a: u32, ) -> Weight {
+		Weight::from_parts(10_000_000, 3_500)
+			.saturating_add(Weight::from_parts(2_000_000, 0).saturating_mul(a.into()))
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+			.saturating_add(T::DbWeight::get().writes((1_u64).saturating_mul(a.into())))
+	}
+	/// Storage: AuthorInherent AuthorStats (r:1 w:1)
+	/// Proof Skipped: AuthorInherent AuthorStats (max_values: None, max_size: None, mode: Measured)
+	/// Storage: AuthorInherent RoundAuthorCount (r:1 w:1)
+	/// Proof Skipped: AuthorInherent RoundAuthorCount (max_values: None, max_size: None, mode: Measured)
+	fn update_stats() -> Weight {
+		Weight::from_parts(15_000_000, 7_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+	}
+	/// Storage: AuthorInherent ConsecutiveMissedSlots (r:1 w:1)
+	/// Proof Skipped: AuthorInherent ConsecutiveMissedSlots (max_values: None, max_size: None, mode: Measured)
+	/// Storage: System Events (r:0 w:1)
+	/// Proof Skipped: System Events (max_values: Some(1), max_size: None, mode: Measured)
+	fn note_missed_slot() -> Weight {
+		Weight::from_parts(15_000_000, 3_500)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+	}
 }
 
 // For backwards compatibility and tests
@@ -104,4 +142,36 @@ impl WeightInfo for () {
 			.saturating_add(RocksDbWeight::get().reads(6_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: AuthorInherent StatsBucketRound (r:1 w:1)
+	/// Proof Skipped: AuthorInherent StatsBucketRound (max_values: None, max_size: None, mode: Measured)
+	/// Storage: AuthorInherent AuthorStats (r:0 w:a)
+	/// Proof Skipped: AuthorInherent AuthorStats (max_values: None, max_size: None, mode: Measured)
+	/// Storage: AuthorInherent RoundAuthorCount (r:0 w:1)
+	/// Proof Skipped: AuthorInherent RoundAuthorCount (max_values: None, max_size: None, mode: Measured)
+	/// The range of component `a` is `[0, 32]`.
+	fn claim_round(a: u32, ) -> Weight {
+		Weight::from_parts(10_000_000, 3_500)
+			.saturating_add(Weight::from_parts(2_000_000, 0).saturating_mul(a.into()))
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+			.saturating_add(RocksDbWeight::get().writes((1_u64).saturating_mul(a.into())))
+	}
+	/// Storage: AuthorInherent AuthorStats (r:1 w:1)
+	/// Proof Skipped: AuthorInherent AuthorStats (max_values: None, max_size: None, mode: Measured)
+	/// Storage: AuthorInherent RoundAuthorCount (r:1 w:1)
+	/// Proof Skipped: AuthorInherent RoundAuthorCount (max_values: None, max_size: None, mode: Measured)
+	fn update_stats() -> Weight {
+		Weight::from_parts(15_000_000, 7_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+	}
+	/// Storage: AuthorInherent ConsecutiveMissedSlots (r:1 w:1)
+	/// Proof Skipped: AuthorInherent ConsecutiveMissedSlots (max_values: None, max_size: None, mode: Measured)
+	/// Storage: System Events (r:0 w:1)
+	/// Proof Skipped: System Events (max_values: Some(1), max_size: None, mode: Measured)
+	fn note_missed_slot() -> Weight {
+		Weight::from_parts(15_000_000, 3_500)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+	}
 }
\ No newline at end of file
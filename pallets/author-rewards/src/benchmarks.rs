@@ -0,0 +1,56 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+#![cfg(feature = "runtime-benchmarks")]
+
+use crate::{Config, CurrentAuthor, Pallet, PayoutInProgress, PendingRewards};
+use frame_benchmarking::{account, benchmarks, impl_benchmark_test_suite};
+use frame_support::traits::{Currency, Get};
+use nimbus_primitives::EventHandler;
+
+benchmarks! {
+	note_author {
+		let author: T::AccountId = account("author", 0, 0);
+	}: {
+		<Pallet<T> as EventHandler<T::AccountId>>::note_author(author.clone());
+	}
+	verify {
+		assert_eq!(CurrentAuthor::<T>::get(), Some(author));
+	}
+
+	pay_out_batch {
+		let n in 0 .. 100;
+
+		let amount = T::Currency::minimum_balance() * 10u32.into();
+		let _ = T::Currency::make_free_balance_be(
+			&Pallet::<T>::account_id(),
+			amount * (n + 1).into(),
+		);
+		for i in 0 .. n {
+			PendingRewards::<T>::insert(account::<T::AccountId>("author", i, 0), amount);
+		}
+		PayoutInProgress::<T>::put(true);
+	}: {
+		Pallet::<T>::pay_out_batch();
+	}
+	verify {
+		if n <= T::MaxPayoutsPerBlock::get() {
+			assert!(!PayoutInProgress::<T>::get());
+		}
+	}
+}
+
+impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
@@ -0,0 +1,301 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Pays block authors for the blocks they author.
+//!
+//! This pallet is meant to be the author inherent's `EventHandler`. Because the author inherent
+//! resolves the `NimbusId` in the pre-runtime digest through its `AccountLookup` before notifying
+//! us, rewards go to the account mapped to the author's nimbus key. Authors earn:
+//! * A fixed `RewardPerBlock` for each validated block, which is newly minted.
+//! * A `FeeShare` of the transaction fees in the blocks they author. Use this pallet as the
+//!   `OnUnbalanced` handler of the transaction payment pallet; whatever is not shared with the
+//!   author is passed on to `FeeRemainder`.
+//!
+//! Rewards are accumulated in a pot account and paid out in batches every `PayoutInterval` blocks.
+//! Each block pays at most `MaxPayoutsPerBlock` authors, so a large payout is spread over several
+//! blocks. Rewards that cannot be transferred, for example because they are below the existential
+//! deposit of a new account, stay pending and are retried in the next payout.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::pallet;
+
+pub use pallet::*;
+
+#[cfg(any(test, feature = "runtime-benchmarks"))]
+mod benchmarks;
+
+pub mod weights;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+#[pallet]
+pub mod pallet {
+
+	use crate::weights::WeightInfo;
+	use frame_support::pallet_prelude::*;
+	use frame_support::traits::{Currency, ExistenceRequirement, Imbalance, OnUnbalanced};
+	use frame_support::PalletId;
+	use frame_system::pallet_prelude::*;
+	use log::warn;
+	use nimbus_primitives::EventHandler;
+	use sp_runtime::traits::{AccountIdConversion, Saturating, Zero};
+	use sp_runtime::Perbill;
+	use sp_std::vec::Vec;
+
+	pub type BalanceOf<T> =
+		<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+	pub type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+		<T as frame_system::Config>::AccountId,
+	>>::NegativeImbalance;
+
+	/// The Author Rewards pallet
+	#[pallet::pallet]
+	pub struct Pallet<T>(PhantomData<T>);
+
+	/// Configuration trait of this pallet.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// The overarching event type
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		/// The currency in which rewards are paid
+		type Currency: Currency<Self::AccountId>;
+		/// The amount minted for the author of each block
+		#[pallet::constant]
+		type RewardPerBlock: Get<BalanceOf<Self>>;
+		/// The share of each block's transaction fees that goes to its author
+		#[pallet::constant]
+		type FeeShare: Get<Perbill>;
+		/// Handler for the fees that are not shared with the author. Use `()` to burn them.
+		type FeeRemainder: OnUnbalanced<NegativeImbalanceOf<Self>>;
+		/// The number of blocks between payouts. Must not be zero.
+		#[pallet::constant]
+		type PayoutInterval: Get<Self::BlockNumber>;
+		/// The maximum number of authors paid in a single block
+		#[pallet::constant]
+		type MaxPayoutsPerBlock: Get<u32>;
+		/// Used to derive the account of the pot that holds rewards until they are paid
+		#[pallet::constant]
+		type PalletId: Get<PalletId>;
+		type WeightInfo: WeightInfo;
+	}
+
+	/// The author of the current block, as reported by the author inherent.
+	/// Only present between the author inherent and the end of the block.
+	#[pallet::storage]
+	pub type CurrentAuthor<T: Config> = StorageValue<_, T::AccountId, OptionQuery>;
+
+	/// Rewards earned by each author since they were last paid.
+	#[pallet::storage]
+	#[pallet::getter(fn pending_rewards)]
+	pub type PendingRewards<T: Config> =
+		StorageMap<_, Twox64Concat, T::AccountId, BalanceOf<T>, ValueQuery>;
+
+	/// Whether a payout has started and not all pending rewards have been paid yet.
+	#[pallet::storage]
+	pub type PayoutInProgress<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+	/// The last author visited by the payout in progress. The payout continues after it.
+	#[pallet::storage]
+	pub type PayoutCursor<T: Config> = StorageValue<_, T::AccountId, OptionQuery>;
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(n: T::BlockNumber) -> Weight {
+			// Account for reading the payout flag and clearing the author in on_finalize
+			let mut weight = T::DbWeight::get().reads_writes(1, 1);
+
+			let interval = T::PayoutInterval::get();
+			if !interval.is_zero() && (n % interval).is_zero() {
+				PayoutInProgress::<T>::put(true);
+				weight = weight.saturating_add(T::DbWeight::get().writes(1));
+			}
+
+			if PayoutInProgress::<T>::get() {
+				weight = weight.saturating_add(Self::pay_out_batch());
+			}
+
+			weight
+		}
+
+		fn on_finalize(_: T::BlockNumber) {
+			CurrentAuthor::<T>::kill();
+		}
+
+		fn on_runtime_upgrade() -> Weight {
+			// Genesis creates the pot, but not when the pallet is added by a runtime upgrade
+			Self::ensure_pot()
+		}
+	}
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// An author has been paid their accumulated rewards.
+		RewardPaid {
+			author: T::AccountId,
+			amount: BalanceOf<T>,
+		},
+		/// The rewards of an author could not be transferred. They stay pending, and are retried
+		/// with the author's later rewards in the next payout.
+		RewardDeferred {
+			author: T::AccountId,
+			amount: BalanceOf<T>,
+		},
+	}
+
+	impl<T: Config> Pallet<T> {
+		/// The account that holds rewards until they are paid.
+		pub fn account_id() -> T::AccountId {
+			T::PalletId::get().into_account_truncating()
+		}
+
+		/// Create the pot if it does not exist, so that rewards below the existential deposit are
+		/// not lost.
+		pub(crate) fn ensure_pot() -> Weight {
+			let pot = Self::account_id();
+			let min = T::Currency::minimum_balance();
+			if T::Currency::free_balance(&pot) >= min {
+				return T::DbWeight::get().reads(1);
+			}
+
+			let _ = T::Currency::make_free_balance_be(&pot, min);
+			// The pot's account and the total issuance
+			T::DbWeight::get().reads_writes(2, 2)
+		}
+
+		/// Pay up to `MaxPayoutsPerBlock` pending rewards after the payout cursor, and end the
+		/// payout when every author has been visited. Rewards earned while a payout is in progress
+		/// may be paid in the same payout.
+		pub(crate) fn pay_out_batch() -> Weight {
+			let pot = Self::account_id();
+			let mut pending = match PayoutCursor::<T>::get() {
+				Some(last) => {
+					PendingRewards::<T>::iter_from(PendingRewards::<T>::hashed_key_for(last))
+				}
+				None => PendingRewards::<T>::iter(),
+			};
+			let batch: Vec<_> = pending
+				.by_ref()
+				.take(T::MaxPayoutsPerBlock::get() as usize)
+				.collect();
+			let finished = pending.next().is_none();
+			let paid = batch.len() as u32;
+
+			let mut last = None;
+			for (author, amount) in batch {
+				// Payouts below the existential deposit of a new account can fail. They stay
+				// pending, so that they are paid once the author has earned enough.
+				match T::Currency::transfer(&pot, &author, amount, ExistenceRequirement::AllowDeath)
+				{
+					Ok(()) => {
+						PendingRewards::<T>::remove(&author);
+						Self::deposit_event(Event::RewardPaid {
+							author: author.clone(),
+							amount,
+						});
+					}
+					Err(e) => {
+						warn!(
+							target: "author-rewards",
+							"Could not pay {:?} to author {:?}: {:?}",
+							amount,
+							author,
+							e
+						);
+						Self::deposit_event(Event::RewardDeferred {
+							author: author.clone(),
+							amount,
+						});
+					}
+				}
+				last = Some(author);
+			}
+
+			match last {
+				Some(last) if !finished => PayoutCursor::<T>::put(last),
+				_ => {
+					PayoutCursor::<T>::kill();
+					PayoutInProgress::<T>::put(false);
+				}
+			}
+
+			T::WeightInfo::pay_out_batch(paid)
+		}
+
+		/// Record an amount that has already been deposited into the pot for the given author.
+		fn credit(author: &T::AccountId, amount: BalanceOf<T>) {
+			if !amount.is_zero() {
+				PendingRewards::<T>::mutate(author, |pending| {
+					*pending = pending.saturating_add(amount)
+				});
+			}
+		}
+	}
+
+	// This code will be called by the author-inherent pallet once the author of this block has
+	// been validated.
+	impl<T: Config> EventHandler<T::AccountId> for Pallet<T> {
+		fn note_author(author: T::AccountId) {
+			let minted =
+				T::Currency::deposit_creating(&Self::account_id(), T::RewardPerBlock::get());
+			Self::credit(&author, minted.peek());
+			CurrentAuthor::<T>::put(author);
+		}
+
+		fn note_author_weight() -> Weight {
+			T::WeightInfo::note_author()
+		}
+	}
+
+	// Transaction fees are split between the author of the block and the `FeeRemainder`.
+	impl<T: Config> OnUnbalanced<NegativeImbalanceOf<T>> for Pallet<T> {
+		fn on_nonzero_unbalanced(amount: NegativeImbalanceOf<T>) {
+			let author = match CurrentAuthor::<T>::get() {
+				Some(author) => author,
+				// Fees paid before the author is known, eg. by inherents, are not shared.
+				None => return T::FeeRemainder::on_unbalanced(amount),
+			};
+
+			let share = T::FeeShare::get();
+			let (to_author, remainder) =
+				amount.ration(share.deconstruct(), (Perbill::one() - share).deconstruct());
+			T::FeeRemainder::on_unbalanced(remainder);
+
+			let value = to_author.peek();
+			match T::Currency::resolve_into_existing(&Self::account_id(), to_author) {
+				Ok(()) => Self::credit(&author, value),
+				// The pot does not exist yet and the share is below the existential deposit.
+				Err(to_author) => T::FeeRemainder::on_unbalanced(to_author),
+			}
+		}
+	}
+
+	#[pallet::genesis_config]
+	#[derive(Default)]
+	pub struct GenesisConfig {}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			Pallet::<T>::ensure_pot();
+		}
+	}
+}
@@ -0,0 +1,144 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate as pallet_testing;
+use frame_support::traits::{ConstU32, Currency, GenesisBuild, OnUnbalanced};
+use frame_support::weights::RuntimeDbWeight;
+use frame_support::{parameter_types, PalletId};
+use sp_core::H256;
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, IdentityLookup},
+	Perbill,
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+		AuthorRewards: pallet_testing::{Pallet, Storage, Config, Event<T>},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const TestDbWeight: RuntimeDbWeight = RuntimeDbWeight {
+		read: 1,
+		write: 10,
+	};
+}
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = TestDbWeight;
+	type RuntimeOrigin = RuntimeOrigin;
+	type RuntimeCall = RuntimeCall;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type RuntimeEvent = RuntimeEvent;
+	type BlockHashCount = BlockHashCount;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = pallet_balances::AccountData<u64>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+	type MaxConsumers = ConstU32<16>;
+}
+
+parameter_types! {
+	pub static ExistentialDeposit: u64 = 1;
+}
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = ();
+	type Balance = u64;
+	type RuntimeEvent = RuntimeEvent;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+	type MaxReserves = ();
+	type ReserveIdentifier = [u8; 8];
+	type HoldIdentifier = ();
+	type FreezeIdentifier = ();
+	type MaxHolds = ();
+	type MaxFreezes = ();
+}
+
+pub const ALICE: u64 = 1;
+pub const BOB: u64 = 2;
+pub const CHARLIE: u64 = 3;
+/// Receives the fees that are not shared with the author.
+pub const TREASURY: u64 = 100;
+
+pub const REWARD_PER_BLOCK: u64 = 10;
+pub const PAYOUT_INTERVAL: u64 = 10;
+
+parameter_types! {
+	pub const RewardPerBlock: u64 = REWARD_PER_BLOCK;
+	pub const FeeShare: Perbill = Perbill::from_percent(80);
+	pub const PayoutInterval: u64 = PAYOUT_INTERVAL;
+	pub const MaxPayoutsPerBlock: u32 = 2;
+	pub const RewardsPalletId: PalletId = PalletId(*b"nmbs/rwd");
+}
+
+pub struct ToTreasury;
+impl OnUnbalanced<pallet_testing::NegativeImbalanceOf<Test>> for ToTreasury {
+	fn on_nonzero_unbalanced(amount: pallet_testing::NegativeImbalanceOf<Test>) {
+		Balances::resolve_creating(&TREASURY, amount);
+	}
+}
+
+impl pallet_testing::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type Currency = Balances;
+	type RewardPerBlock = RewardPerBlock;
+	type FeeShare = FeeShare;
+	type FeeRemainder = ToTreasury;
+	type PayoutInterval = PayoutInterval;
+	type MaxPayoutsPerBlock = MaxPayoutsPerBlock;
+	type PalletId = RewardsPalletId;
+	type WeightInfo = ();
+}
+
+/// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut t = frame_system::GenesisConfig::default()
+		.build_storage::<Test>()
+		.unwrap();
+	GenesisBuild::<Test>::assimilate_storage(&pallet_testing::GenesisConfig {}, &mut t).unwrap();
+
+	let mut ext: sp_io::TestExternalities = t.into();
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
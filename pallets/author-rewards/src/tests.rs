@@ -0,0 +1,196 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+use crate::mock::*;
+
+use frame_support::traits::{Currency, OnFinalize, OnInitialize, OnRuntimeUpgrade, OnUnbalanced};
+use nimbus_primitives::EventHandler;
+
+fn pot_balance() -> u64 {
+	Balances::free_balance(AuthorRewards::account_id())
+}
+
+/// Run a block authored by the given account, without any payout.
+fn author_block(author: u64) {
+	AuthorRewards::note_author(author);
+	AuthorRewards::on_finalize(System::block_number());
+}
+
+fn run_to_block(n: u64) {
+	while System::block_number() < n {
+		AuthorRewards::on_finalize(System::block_number());
+		System::set_block_number(System::block_number() + 1);
+		AuthorRewards::on_initialize(System::block_number());
+	}
+}
+
+#[test]
+fn genesis_creates_pot() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(pot_balance(), ExistentialDeposit::get());
+	});
+}
+
+#[test]
+fn runtime_upgrade_creates_missing_pot() {
+	new_test_ext().execute_with(|| {
+		// As if the pallet was added by a runtime upgrade, with rewards below the existential
+		// deposit
+		ExistentialDeposit::set(&(REWARD_PER_BLOCK + 1));
+		let _ = Balances::make_free_balance_be(&AuthorRewards::account_id(), 0);
+
+		// Without a pot, the reward is not minted
+		author_block(ALICE);
+		assert_eq!(AuthorRewards::pending_rewards(ALICE), 0);
+
+		AuthorRewards::on_runtime_upgrade();
+		assert_eq!(pot_balance(), REWARD_PER_BLOCK + 1);
+
+		author_block(BOB);
+		assert_eq!(AuthorRewards::pending_rewards(BOB), REWARD_PER_BLOCK);
+		assert_eq!(pot_balance(), 2 * REWARD_PER_BLOCK + 1);
+
+		// Later upgrades leave the pot alone
+		AuthorRewards::on_runtime_upgrade();
+		assert_eq!(pot_balance(), 2 * REWARD_PER_BLOCK + 1);
+	});
+}
+
+#[test]
+fn noted_author_earns_block_reward() {
+	new_test_ext().execute_with(|| {
+		AuthorRewards::note_author(ALICE);
+
+		assert_eq!(CurrentAuthor::<Test>::get(), Some(ALICE));
+		assert_eq!(AuthorRewards::pending_rewards(ALICE), REWARD_PER_BLOCK);
+		assert_eq!(pot_balance(), ExistentialDeposit::get() + REWARD_PER_BLOCK);
+		// Nothing is paid before the payout
+		assert_eq!(Balances::free_balance(ALICE), 0);
+	});
+}
+
+#[test]
+fn current_author_is_cleared_on_finalize() {
+	new_test_ext().execute_with(|| {
+		author_block(ALICE);
+
+		assert_eq!(CurrentAuthor::<Test>::get(), None);
+	});
+}
+
+#[test]
+fn fees_are_shared_with_author() {
+	new_test_ext().execute_with(|| {
+		AuthorRewards::note_author(ALICE);
+		AuthorRewards::on_unbalanced(Balances::issue(100));
+
+		assert_eq!(AuthorRewards::pending_rewards(ALICE), REWARD_PER_BLOCK + 80);
+		assert_eq!(Balances::free_balance(TREASURY), 20);
+	});
+}
+
+#[test]
+fn fees_without_author_go_to_remainder() {
+	new_test_ext().execute_with(|| {
+		AuthorRewards::on_unbalanced(Balances::issue(100));
+
+		assert_eq!(Balances::free_balance(TREASURY), 100);
+		assert_eq!(pot_balance(), ExistentialDeposit::get());
+	});
+}
+
+#[test]
+fn rewards_are_paid_at_payout_interval() {
+	new_test_ext().execute_with(|| {
+		author_block(ALICE);
+		run_to_block(2);
+		author_block(ALICE);
+
+		run_to_block(PAYOUT_INTERVAL - 1);
+		assert_eq!(Balances::free_balance(ALICE), 0);
+
+		run_to_block(PAYOUT_INTERVAL);
+		assert_eq!(Balances::free_balance(ALICE), 2 * REWARD_PER_BLOCK);
+		assert_eq!(AuthorRewards::pending_rewards(ALICE), 0);
+		assert_eq!(pot_balance(), ExistentialDeposit::get());
+		assert!(!PayoutInProgress::<Test>::get());
+		System::assert_last_event(RuntimeEvent::AuthorRewards(Event::RewardPaid {
+			author: ALICE,
+			amount: 2 * REWARD_PER_BLOCK,
+		}));
+	});
+}
+
+#[test]
+fn payout_is_spread_over_several_blocks() {
+	new_test_ext().execute_with(|| {
+		author_block(ALICE);
+		author_block(BOB);
+		author_block(CHARLIE);
+
+		// Only two authors are paid in each block
+		run_to_block(PAYOUT_INTERVAL);
+		let paid = [ALICE, BOB, CHARLIE]
+			.into_iter()
+			.filter(|author| Balances::free_balance(author) == REWARD_PER_BLOCK)
+			.count();
+		assert_eq!(paid, 2);
+		assert!(PayoutInProgress::<Test>::get());
+
+		run_to_block(PAYOUT_INTERVAL + 1);
+		for author in [ALICE, BOB, CHARLIE] {
+			assert_eq!(Balances::free_balance(author), REWARD_PER_BLOCK);
+		}
+		assert!(!PayoutInProgress::<Test>::get());
+	});
+}
+
+#[test]
+fn failed_transfers_stay_pending_until_the_next_payout() {
+	new_test_ext().execute_with(|| {
+		author_block(ALICE);
+		// More than the pot holds, so the transfer fails
+		let owed = pot_balance() * 10;
+		PendingRewards::<Test>::insert(BOB, owed);
+
+		run_to_block(PAYOUT_INTERVAL);
+		assert_eq!(Balances::free_balance(ALICE), REWARD_PER_BLOCK);
+		assert_eq!(Balances::free_balance(BOB), 0);
+		assert_eq!(AuthorRewards::pending_rewards(BOB), owed);
+		System::assert_has_event(RuntimeEvent::AuthorRewards(Event::RewardDeferred {
+			author: BOB,
+			amount: owed,
+		}));
+		// The payout ends even though Bob could not be paid
+		assert!(!PayoutInProgress::<Test>::get());
+		assert_eq!(PayoutCursor::<Test>::get(), None);
+
+		// Once the pot holds enough, Bob is paid in the next payout
+		Balances::make_free_balance_be(&AuthorRewards::account_id(), owed + 1);
+		run_to_block(2 * PAYOUT_INTERVAL);
+		assert_eq!(Balances::free_balance(BOB), owed);
+		assert_eq!(AuthorRewards::pending_rewards(BOB), 0);
+	});
+}
+
+#[test]
+fn note_author_weight_is_charged() {
+	assert_eq!(
+		<AuthorRewards as EventHandler<u64>>::note_author_weight(),
+		<() as crate::weights::WeightInfo>::note_author()
+	);
+}
@@ -0,0 +1,86 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Weights for pallet_author_rewards
+//!
+//! These are conservative estimates based on the storage accesses of each operation and the
+//! cost of a balance transfer in pallet_balances. They should be replaced with the output of
+//! the `note_author` and `pay_out_batch` benchmarks on reference hardware.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_author_rewards.
+pub trait WeightInfo {
+	fn note_author() -> Weight;
+	fn pay_out_batch(n: u32) -> Weight;
+}
+
+/// Weights for pallet_author_rewards using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	/// Storage: System Account (r:1 w:1)
+	/// Storage: AuthorRewards PendingRewards (r:1 w:1)
+	/// Storage: AuthorRewards CurrentAuthor (r:0 w:1)
+	fn note_author() -> Weight {
+		Weight::from_parts(30_000_000, 5_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
+	/// Storage: AuthorRewards PendingRewards (r:n+1 w:n)
+	/// Storage: System Account (r:n+1 w:n+1)
+	/// Storage: AuthorRewards PayoutCursor (r:1 w:1)
+	/// Storage: AuthorRewards PayoutInProgress (r:0 w:1)
+	/// The range of component `n` is `[0, 100]`.
+	fn pay_out_batch(n: u32, ) -> Weight {
+		Weight::from_parts(10_000_000, 5_000)
+			.saturating_add(Weight::from_parts(40_000_000, 5_000).saturating_mul(n.into()))
+			.saturating_add(T::DbWeight::get().reads(3_u64))
+			.saturating_add(T::DbWeight::get().reads((2_u64).saturating_mul(n.into())))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+			.saturating_add(T::DbWeight::get().writes((2_u64).saturating_mul(n.into())))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	/// Storage: System Account (r:1 w:1)
+	/// Storage: AuthorRewards PendingRewards (r:1 w:1)
+	/// Storage: AuthorRewards CurrentAuthor (r:0 w:1)
+	fn note_author() -> Weight {
+		Weight::from_parts(30_000_000, 5_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
+	/// Storage: AuthorRewards PendingRewards (r:n+1 w:n)
+	/// Storage: System Account (r:n+1 w:n+1)
+	/// Storage: AuthorRewards PayoutCursor (r:1 w:1)
+	/// Storage: AuthorRewards PayoutInProgress (r:0 w:1)
+	/// The range of component `n` is `[0, 100]`.
+	fn pay_out_batch(n: u32, ) -> Weight {
+		Weight::from_parts(10_000_000, 5_000)
+			.saturating_add(Weight::from_parts(40_000_000, 5_000).saturating_mul(n.into()))
+			.saturating_add(RocksDbWeight::get().reads(3_u64))
+			.saturating_add(RocksDbWeight::get().reads((2_u64).saturating_mul(n.into())))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+			.saturating_add(RocksDbWeight::get().writes((2_u64).saturating_mul(n.into())))
+	}
+}
@@ -50,7 +50,7 @@ pub mod pallet {
 	use frame_support::{pallet_prelude::*, traits::Randomness};
 	use frame_system::pallet_prelude::*;
 	use log::debug;
-	use nimbus_primitives::CanAuthor;
+	use nimbus_primitives::{CanAuthor, EligibleAuthors};
 	use sp_core::H256;
 	use sp_runtime::Percent;
 	use sp_std::vec::Vec;
@@ -67,10 +67,9 @@ pub mod pallet {
 		type RuntimeEvent: From<Event> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
 		/// Deterministic on-chain pseudo-randomness used to do the filtering
 		type RandomnessSource: Randomness<H256, Self::BlockNumber>;
-		//TODO introduce a new trait for exhaustive sets and use it here.
-		// Oh actually, we can use the same trait. First we call the inner one
+		//TODO Consider chaining an inner `CanAuthor` filter instead. First we call the inner one
 		// to determine whether this particular author is eligible there. then we
-		// use the author as part of the subject when querying eligibility. I like this better.
+		// use the author as part of the subject when querying eligibility.
 		/// A source for the complete set of potential authors.
 		/// The starting point of the filtering.
 		type PotentialAuthors: Get<Vec<Self::AccountId>>;
@@ -145,6 +144,14 @@ pub mod pallet {
 		}
 		#[cfg(feature = "runtime-benchmarks")]
 		fn get_authors(slot: &u32) -> Vec<T::AccountId> {
+			Self::eligible_authors(slot)
+		}
+	}
+
+	// The exhaustive variant of the check above. Because the potential authors are a finite set
+	// we can simply return the entire pseudo-random subset.
+	impl<T: Config> EligibleAuthors<T::AccountId> for Pallet<T> {
+		fn eligible_authors(slot: &u32) -> Vec<T::AccountId> {
 			// Compute pseudo-random subset of potential authors
 			let (eligible, _) = compute_pseudo_random_subset::<T>(T::PotentialAuthors::get(), slot);
 			eligible
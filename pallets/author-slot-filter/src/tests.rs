@@ -20,6 +20,7 @@ use crate::num::NonZeroU32;
 
 use frame_support::assert_ok;
 use frame_support::{traits::OnRuntimeUpgrade, weights::Weight};
+use nimbus_primitives::{CanAuthor, EligibleAuthors};
 use sp_runtime::Percent;
 
 #[test]
@@ -35,6 +36,52 @@ fn test_set_eligibility_works() {
 	});
 }
 
+#[test]
+fn test_eligible_authors_has_eligible_count_members() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(AuthorSlotFilter::set_eligible(
+			RuntimeOrigin::root(),
+			num::NonZeroU32::new_unchecked(3)
+		));
+
+		let eligible = AuthorSlotFilter::eligible_authors(&7);
+		assert_eq!(eligible.len(), 3);
+		for author in &eligible {
+			assert!(Authors::get().contains(author));
+			assert_eq!(eligible.iter().filter(|a| *a == author).count(), 1);
+		}
+	});
+}
+
+#[test]
+fn test_eligible_authors_is_capped_at_potential_authors() {
+	new_test_ext().execute_with(|| {
+		let mut eligible = AuthorSlotFilter::eligible_authors(&7);
+		eligible.sort();
+		assert_eq!(eligible, Authors::get());
+	});
+}
+
+#[test]
+fn test_eligible_authors_agrees_with_can_author() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(AuthorSlotFilter::set_eligible(
+			RuntimeOrigin::root(),
+			num::NonZeroU32::new_unchecked(2)
+		));
+
+		for slot in 0..10 {
+			let eligible = AuthorSlotFilter::eligible_authors(&slot);
+			for author in Authors::get() {
+				assert_eq!(
+					eligible.contains(&author),
+					AuthorSlotFilter::can_author(&author, &slot)
+				);
+			}
+		}
+	});
+}
+
 #[allow(deprecated)]
 #[test]
 fn test_migration_works_for_converting_existing_eligible_ratio_to_eligible_count() {
@@ -0,0 +1,268 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Accepts reports of nimbus authors that sealed two different blocks in the same slot.
+//!
+//! Nimbus is permissive: any block that faithfully reports its author is valid, so on its own
+//! nothing stops an eligible author from sealing many competing blocks in its slot. This pallet
+//! lets anyone submit an `EquivocationProof` (for example, one produced by the nimbus import
+//! queue's equivocation detection) through the unsigned `report_equivocation` extrinsic.
+//!
+//! The proof is checked the same way the `BlockExecutor` checks seals. The offending `NimbusId`
+//! is mapped to an account with `AccountLookup` and the offence is passed to `OnOffenceHandler`,
+//! which decides what the consequences are. Each (author, slot) pair is only punished once.
+//!
+//! Only equivocations in the last `ReportWindow` slots can be reported. The reports of each slot
+//! are remembered in one of `ReportWindow` buckets, and forgotten once a newer slot needs the
+//! bucket, so the pallet's storage stays bounded.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::pallet;
+
+pub use pallet::*;
+
+pub mod weights;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+#[pallet]
+pub mod pallet {
+
+	use crate::weights::WeightInfo;
+	use frame_support::dispatch::PostDispatchInfo;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+	use log::debug;
+	use nimbus_primitives::{
+		check_equivocation_proof, AccountLookup, EquivocationProof, MultiNimbusId,
+		OnOffenceHandler, SlotBeacon,
+	};
+	use sp_std::boxed::Box;
+
+	/// The Equivocation Slashing pallet
+	#[pallet::pallet]
+	pub struct Pallet<T>(PhantomData<T>);
+
+	/// Configuration trait of this pallet.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// The overarching event type
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		/// A type to map the author id that sealed the headers to the offending AccountId.
+		/// This should be the same mapping the author inherent pallet uses.
+		type AccountLookup: AccountLookup<Self::AccountId>;
+		/// What to do with authors that have equivocated
+		type OnOffenceHandler: OnOffenceHandler<Self::AccountId>;
+		/// Some way of determining the current slot. This should be the same beacon the author
+		/// inherent pallet uses.
+		type SlotBeacon: SlotBeacon;
+		/// The number of blocks a report stays valid in the transaction pool
+		#[pallet::constant]
+		type ReportLongevity: Get<u64>;
+		/// The number of slots, up to and including the current one, whose equivocations can be
+		/// reported. Reports of older slots are rejected, and their records forgotten.
+		#[pallet::constant]
+		type ReportWindow: Get<u32>;
+		/// The maximum number of authors reported for the same slot. It should be at least the
+		/// number of authors eligible in a slot.
+		#[pallet::constant]
+		type MaxReportsPerSlot: Get<u32>;
+		type WeightInfo: WeightInfo;
+	}
+
+	/// The equivocations that have already been reported, by slot and author.
+	#[pallet::storage]
+	pub type Reports<T: Config> =
+		StorageDoubleMap<_, Twox64Concat, u32, Blake2_128Concat, MultiNimbusId, (), OptionQuery>;
+
+	/// The slot whose reports occupy each of the `ReportWindow` buckets, and the number of its
+	/// reports. A slot uses bucket `slot % ReportWindow`, and forgets the reports of the older
+	/// slot that occupied it.
+	#[pallet::storage]
+	pub type ReportBuckets<T: Config> = StorageMap<_, Twox64Concat, u32, (u32, u32), OptionQuery>;
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// The headers are not two conflicting headers sealed by the offender in the same slot.
+		InvalidEquivocationProof,
+		/// The offender's NimbusId is not mapped to any account.
+		UnknownOffender,
+		/// This equivocation has already been reported.
+		DuplicateOffenceReport,
+		/// The equivocation is older than the report window.
+		OutdatedOffenceReport,
+		/// The equivocation is in a slot that has not started yet.
+		FutureOffenceReport,
+		/// `MaxReportsPerSlot` authors have already been reported in this slot.
+		TooManyOffenceReports,
+	}
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// An author has been reported for sealing two blocks in the same slot.
+		EquivocationReported {
+			offender: T::AccountId,
+			nimbus_id: MultiNimbusId,
+			slot: u32,
+		},
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Report an author that sealed two different blocks in the same slot.
+		/// Valid reports are free. Invalid ones are rejected by the transaction pool.
+		#[pallet::call_index(0)]
+		#[pallet::weight(
+			T::WeightInfo::report_equivocation(T::MaxReportsPerSlot::get())
+				.saturating_add(T::OnOffenceHandler::on_equivocation_weight())
+		)]
+		pub fn report_equivocation(
+			origin: OriginFor<T>,
+			equivocation_proof: Box<EquivocationProof<T::Header>>,
+		) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+
+			let offender = Self::check_report(&equivocation_proof)?;
+			let EquivocationProof {
+				offender: nimbus_id,
+				slot,
+				..
+			} = *equivocation_proof;
+
+			let forgotten = Self::note_report(slot);
+			Reports::<T>::insert(slot, &nimbus_id, ());
+			T::OnOffenceHandler::on_equivocation(&offender, slot);
+			Self::deposit_event(Event::EquivocationReported {
+				offender,
+				nimbus_id,
+				slot,
+			});
+
+			Ok(PostDispatchInfo {
+				actual_weight: Some(
+					T::WeightInfo::report_equivocation(forgotten)
+						.saturating_add(T::OnOffenceHandler::on_equivocation_weight()),
+				),
+				pays_fee: Pays::No,
+			})
+		}
+	}
+
+	impl<T: Config> Pallet<T> {
+		/// Check a report and return the offending account.
+		pub(crate) fn check_report(
+			proof: &EquivocationProof<T::Header>,
+		) -> Result<T::AccountId, Error<T>> {
+			let current_slot = T::SlotBeacon::slot();
+			if proof.slot > current_slot {
+				return Err(Error::<T>::FutureOffenceReport);
+			}
+			if proof.slot.saturating_add(T::ReportWindow::get()) <= current_slot {
+				return Err(Error::<T>::OutdatedOffenceReport);
+			}
+
+			if Reports::<T>::contains_key(proof.slot, &proof.offender) {
+				return Err(Error::<T>::DuplicateOffenceReport);
+			}
+			match ReportBuckets::<T>::get(Self::bucket_of(proof.slot)) {
+				Some((slot, reports))
+					if slot == proof.slot && reports >= T::MaxReportsPerSlot::get() =>
+				{
+					return Err(Error::<T>::TooManyOffenceReports)
+				}
+				_ => (),
+			}
+
+			if !check_equivocation_proof(proof) {
+				return Err(Error::<T>::InvalidEquivocationProof);
+			}
+
+			T::AccountLookup::lookup_multi_account(&proof.offender)
+				.ok_or(Error::<T>::UnknownOffender)
+		}
+
+		fn bucket_of(slot: u32) -> u32 {
+			slot % T::ReportWindow::get().max(1)
+		}
+
+		/// Count a report in the bucket of `slot`, first forgetting the reports of the slot that
+		/// occupied it before. That slot is older than the report window, so its equivocations can
+		/// no longer be reported. Returns the number of reports forgotten.
+		fn note_report(slot: u32) -> u32 {
+			let bucket = Self::bucket_of(slot);
+			let (reports, forgotten) = match ReportBuckets::<T>::get(bucket) {
+				Some((occupant, reports)) if occupant == slot => (reports, 0),
+				Some((occupant, _)) => {
+					let forgotten =
+						Reports::<T>::clear_prefix(occupant, T::MaxReportsPerSlot::get(), None)
+							.unique;
+					(0, forgotten)
+				}
+				None => (0, 0),
+			};
+			ReportBuckets::<T>::insert(bucket, (slot, reports.saturating_add(1)));
+			forgotten
+		}
+	}
+
+	#[pallet::validate_unsigned]
+	impl<T: Config> ValidateUnsigned for Pallet<T> {
+		type Call = Call<T>;
+
+		// Reports are accepted from any source and propagated, so that whichever author comes
+		// next can include them. The proof is checked here, so invalid reports never enter
+		// the pool.
+		fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+			let equivocation_proof = match call {
+				Call::report_equivocation { equivocation_proof } => equivocation_proof,
+				_ => return InvalidTransaction::Call.into(),
+			};
+
+			if let Err(e) = Self::check_report(equivocation_proof) {
+				debug!(
+					target: "equivocation-slashing",
+					"Rejecting equivocation report for {:?} in slot {}: {:?}",
+					equivocation_proof.offender,
+					equivocation_proof.slot,
+					e
+				);
+				return match e {
+					Error::<T>::DuplicateOffenceReport
+					| Error::<T>::OutdatedOffenceReport
+					| Error::<T>::TooManyOffenceReports => InvalidTransaction::Stale,
+					Error::<T>::FutureOffenceReport => InvalidTransaction::Future,
+					Error::<T>::UnknownOffender => InvalidTransaction::BadSigner,
+					_ => InvalidTransaction::BadProof,
+				}
+				.into();
+			}
+
+			// Several different proofs of the same offence may be submitted.
+			// Only one of them is ever included.
+			ValidTransaction::with_tag_prefix("NimbusEquivocation")
+				.priority(TransactionPriority::max_value())
+				.and_provides((equivocation_proof.offender.clone(), equivocation_proof.slot))
+				.longevity(T::ReportLongevity::get())
+				.propagate(true)
+				.build()
+		}
+	}
+}
@@ -0,0 +1,320 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+use crate::mock::*;
+
+use frame_support::pallet_prelude::*;
+use frame_support::{assert_noop, assert_ok, dispatch::Pays};
+use nimbus_primitives::{
+	CompatibleDigestItem, EquivocationProof, MultiNimbusId, NimbusId, NimbusPreDigest,
+};
+use sp_core::Pair;
+use sp_runtime::{
+	testing::Header,
+	traits::{Header as HeaderT, ValidateUnsigned},
+	Digest, DigestItem, DispatchError,
+};
+
+fn nimbus_id(account: u64) -> MultiNimbusId {
+	NimbusId::from(nimbus_pair(account).public()).into()
+}
+
+/// A header at the given height, authored by the given account in the given slot,
+/// and sealed by `sealer`.
+fn sealed_header(author: u64, sealer: u64, slot: u32, number: u64) -> Header {
+	let mut header = Header::new(
+		number,
+		Default::default(),
+		Default::default(),
+		Default::default(),
+		Digest {
+			logs: vec![DigestItem::nimbus_versioned_pre_digest(
+				NimbusPreDigest::V2 {
+					author: nimbus_id(author),
+					slot,
+					extension: None,
+				},
+			)],
+		},
+	);
+	let signature = nimbus_pair(sealer).sign(header.hash().as_ref());
+	header
+		.digest_mut()
+		.push(DigestItem::nimbus_seal(signature.into()));
+	header
+}
+
+/// A valid proof that the given account equivocated in the given slot.
+fn equivocation(author: u64, slot: u32) -> Box<EquivocationProof<Header>> {
+	Box::new(EquivocationProof {
+		offender: nimbus_id(author),
+		slot,
+		first_header: sealed_header(author, author, slot, 1),
+		second_header: sealed_header(author, author, slot, 2),
+	})
+}
+
+fn validate(proof: Box<EquivocationProof<Header>>) -> TransactionValidity {
+	EquivocationSlashing::validate_unsigned(
+		TransactionSource::External,
+		&Call::report_equivocation {
+			equivocation_proof: proof,
+		},
+	)
+}
+
+#[test]
+fn valid_report_is_passed_to_the_offence_handler() {
+	new_test_ext().execute_with(|| {
+		let post_info = EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, 7),
+		)
+		.unwrap();
+
+		assert_eq!(post_info.pays_fee, Pays::No);
+		assert_eq!(offences(), vec![(ALICE, 7)]);
+		assert!(Reports::<Test>::contains_key(7, nimbus_id(ALICE)));
+		System::assert_last_event(
+			Event::EquivocationReported {
+				offender: ALICE,
+				nimbus_id: nimbus_id(ALICE),
+				slot: 7,
+			}
+			.into(),
+		);
+	});
+}
+
+#[test]
+fn report_must_be_unsigned() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(
+				RuntimeOrigin::signed(BOB),
+				equivocation(ALICE, 7)
+			),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn same_offence_is_only_reported_once() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, 7)
+		));
+
+		// Even with a different pair of headers
+		let mut proof = equivocation(ALICE, 7);
+		proof.second_header = sealed_header(ALICE, ALICE, 7, 3);
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(RuntimeOrigin::none(), proof),
+			Error::<Test>::DuplicateOffenceReport
+		);
+
+		// But the same author may be reported in another slot
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, 8)
+		));
+		assert_eq!(offences(), vec![(ALICE, 7), (ALICE, 8)]);
+	});
+}
+
+#[test]
+fn identical_headers_are_rejected() {
+	new_test_ext().execute_with(|| {
+		let mut proof = equivocation(ALICE, 7);
+		proof.second_header = proof.first_header.clone();
+
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(RuntimeOrigin::none(), proof),
+			Error::<Test>::InvalidEquivocationProof
+		);
+	});
+}
+
+#[test]
+fn headers_from_different_slots_are_rejected() {
+	new_test_ext().execute_with(|| {
+		let mut proof = equivocation(ALICE, 7);
+		proof.second_header = sealed_header(ALICE, ALICE, 8, 2);
+
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(RuntimeOrigin::none(), proof),
+			Error::<Test>::InvalidEquivocationProof
+		);
+	});
+}
+
+#[test]
+fn headers_sealed_by_someone_else_are_rejected() {
+	new_test_ext().execute_with(|| {
+		// Bob cannot frame Alice by sealing headers that claim she authored them
+		let mut proof = equivocation(ALICE, 7);
+		proof.second_header = sealed_header(ALICE, BOB, 7, 2);
+
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(RuntimeOrigin::none(), proof),
+			Error::<Test>::InvalidEquivocationProof
+		);
+	});
+}
+
+#[test]
+fn unknown_offender_is_rejected() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(
+				RuntimeOrigin::none(),
+				equivocation(CHARLIE, 7)
+			),
+			Error::<Test>::UnknownOffender
+		);
+	});
+}
+
+#[test]
+fn validate_unsigned_accepts_valid_reports() {
+	new_test_ext().execute_with(|| {
+		let valid = validate(equivocation(ALICE, 7)).unwrap();
+
+		assert_eq!(valid.priority, TransactionPriority::max_value());
+		assert_eq!(valid.longevity, 10);
+		assert!(valid.propagate);
+		assert_eq!(valid.provides.len(), 1);
+
+		// Two different proofs of the same offence provide the same tag
+		let mut proof = equivocation(ALICE, 7);
+		proof.second_header = sealed_header(ALICE, ALICE, 7, 3);
+		assert_eq!(validate(proof).unwrap().provides, valid.provides);
+	});
+}
+
+#[test]
+fn validate_unsigned_rejects_bad_reports() {
+	new_test_ext().execute_with(|| {
+		let mut proof = equivocation(ALICE, 7);
+		proof.second_header = sealed_header(ALICE, BOB, 7, 2);
+		assert_eq!(validate(proof), InvalidTransaction::BadProof.into());
+
+		assert_eq!(
+			validate(equivocation(CHARLIE, 7)),
+			InvalidTransaction::BadSigner.into()
+		);
+
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, 7)
+		));
+		assert_eq!(
+			validate(equivocation(ALICE, 7)),
+			InvalidTransaction::Stale.into()
+		);
+	});
+}
+
+#[test]
+fn only_equivocations_in_the_report_window_are_accepted() {
+	new_test_ext().execute_with(|| {
+		let current_slot = CurrentSlot::get();
+		let oldest_slot = current_slot - REPORT_WINDOW + 1;
+
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(
+				RuntimeOrigin::none(),
+				equivocation(ALICE, oldest_slot - 1)
+			),
+			Error::<Test>::OutdatedOffenceReport
+		);
+		assert_eq!(
+			validate(equivocation(ALICE, oldest_slot - 1)),
+			InvalidTransaction::Stale.into()
+		);
+
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(
+				RuntimeOrigin::none(),
+				equivocation(ALICE, current_slot + 1)
+			),
+			Error::<Test>::FutureOffenceReport
+		);
+		assert_eq!(
+			validate(equivocation(ALICE, current_slot + 1)),
+			InvalidTransaction::Future.into()
+		);
+
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, oldest_slot)
+		));
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, current_slot)
+		));
+	});
+}
+
+#[test]
+fn reports_are_forgotten_once_outside_the_window() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, 7)
+		));
+		assert_eq!(ReportBuckets::<Test>::get(7 % REPORT_WINDOW), Some((7, 1)));
+
+		// Slot 7 + REPORT_WINDOW takes over the bucket of slot 7, which is no longer reportable.
+		CurrentSlot::set(&(7 + REPORT_WINDOW));
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(BOB, 7 + REPORT_WINDOW)
+		));
+
+		assert!(!Reports::<Test>::contains_key(7, nimbus_id(ALICE)));
+		assert!(Reports::<Test>::contains_key(
+			7 + REPORT_WINDOW,
+			nimbus_id(BOB)
+		));
+		assert_eq!(
+			ReportBuckets::<Test>::get(7 % REPORT_WINDOW),
+			Some((7 + REPORT_WINDOW, 1))
+		);
+	});
+}
+
+#[test]
+fn reports_per_slot_are_limited() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(EquivocationSlashing::report_equivocation(
+			RuntimeOrigin::none(),
+			equivocation(ALICE, 7)
+		));
+
+		assert_noop!(
+			EquivocationSlashing::report_equivocation(RuntimeOrigin::none(), equivocation(BOB, 7)),
+			Error::<Test>::TooManyOffenceReports
+		);
+		assert_eq!(
+			validate(equivocation(BOB, 7)),
+			InvalidTransaction::Stale.into()
+		);
+	});
+}
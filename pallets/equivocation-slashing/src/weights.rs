@@ -0,0 +1,64 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Weights for pallet_equivocation_slashing
+//!
+//! `report_equivocation` is dominated by the verification of two sr25519 seals. Until this pallet
+//! is benchmarked on reference hardware, these weights use the cost of two sr25519 verifications
+//! on the Substrate reference machine, plus the storage accesses listed below.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_equivocation_slashing.
+pub trait WeightInfo {
+	fn report_equivocation(f: u32, ) -> Weight;
+}
+
+/// Weights for pallet_equivocation_slashing using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	/// Storage: EquivocationSlashing Reports (r:1 w:f+1)
+	/// Proof Skipped: EquivocationSlashing Reports (max_values: None, max_size: None, mode: Measured)
+	/// Storage: EquivocationSlashing ReportBuckets (r:1 w:1)
+	/// Proof Skipped: EquivocationSlashing ReportBuckets (max_values: None, max_size: None, mode: Measured)
+	/// The range of component `f` is `[0, MaxReportsPerSlot]`.
+	fn report_equivocation(f: u32, ) -> Weight {
+		Weight::from_parts(110_000_000, 0)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+			.saturating_add(T::DbWeight::get().writes((1_u64).saturating_mul(f.into())))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	/// Storage: EquivocationSlashing Reports (r:1 w:f+1)
+	/// Proof Skipped: EquivocationSlashing Reports (max_values: None, max_size: None, mode: Measured)
+	/// Storage: EquivocationSlashing ReportBuckets (r:1 w:1)
+	/// Proof Skipped: EquivocationSlashing ReportBuckets (max_values: None, max_size: None, mode: Measured)
+	/// The range of component `f` is `[0, MaxReportsPerSlot]`.
+	fn report_equivocation(f: u32, ) -> Weight {
+		Weight::from_parts(110_000_000, 0)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+			.saturating_add(RocksDbWeight::get().writes((1_u64).saturating_mul(f.into())))
+	}
+}
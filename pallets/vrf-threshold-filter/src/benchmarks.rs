@@ -0,0 +1,78 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+#![cfg(feature = "runtime-benchmarks")]
+
+use crate::{Call, Config, LastVrfOutput, Pallet};
+use frame_benchmarking::{benchmarks, impl_benchmark_test_suite};
+use frame_support::traits::OnFinalize;
+use frame_system::RawOrigin;
+use nimbus_primitives::{vrf::VrfSignature, CompatibleDigestItem, NimbusId};
+use parity_scale_codec::Decode;
+use sp_runtime::{DigestItem, Perbill};
+
+/// A key and a VRF signature that go through the whole VRF verification, but do not verify.
+/// Benchmarks cannot make VRF signatures, and checking a forged one costs as much.
+fn forged_vrf() -> (NimbusId, VrfSignature) {
+	// The compressed Ristretto basepoint, a valid public key and VRF output
+	let point: [u8; 32] = [
+		0xe2, 0xf2, 0xae, 0x0a, 0x6a, 0xbc, 0x4e, 0x71, 0xa8, 0x84, 0xa9, 0x61, 0xc5, 0x00, 0x51,
+		0x5f, 0x58, 0xe3, 0x0b, 0x6a, 0xa5, 0x82, 0xdd, 0x8d, 0xb6, 0xa6, 0x59, 0x45, 0xe0, 0x8d,
+		0x2d, 0x76,
+	];
+	let nimbus_id = NimbusId::decode(&mut &point[..]).expect("Any 32 bytes are a NimbusId");
+	// The output, followed by a proof made of two zero scalars
+	let mut signature = point.to_vec();
+	signature.extend([0u8; 64]);
+	let signature =
+		VrfSignature::decode(&mut &signature[..]).expect("The point and scalars are canonical");
+
+	(nimbus_id, signature)
+}
+
+benchmarks! {
+	set_threshold {
+		let threshold = Perbill::from_percent(34);
+	}: _(RawOrigin::Root, threshold)
+	verify {
+		assert_eq!(Pallet::<T>::threshold(), threshold);
+	}
+
+	check_vrf {
+		let (nimbus_id, signature) = forged_vrf();
+		// Derive the randomness from a previous output, as in all but the first block
+		LastVrfOutput::<T>::put(signature.output.clone());
+		frame_system::Pallet::<T>::deposit_log(DigestItem::nimbus_vrf_pre_digest(signature));
+		let mut eligible = true;
+	}: {
+		eligible = Pallet::<T>::check_vrf(&nimbus_id, 7);
+	}
+	verify {
+		assert!(!eligible);
+	}
+
+	on_finalize {
+		let (_, signature) = forged_vrf();
+		frame_system::Pallet::<T>::deposit_log(DigestItem::nimbus_vrf_pre_digest(signature));
+	}: {
+		Pallet::<T>::on_finalize(0u32.into());
+	}
+	verify {
+		assert!(LastVrfOutput::<T>::get().is_some());
+	}
+}
+
+impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
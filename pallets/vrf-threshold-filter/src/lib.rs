@@ -0,0 +1,233 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A Nimbus filter where an author is eligible when its VRF output is below a threshold.
+//!
+//! The collator computes an sr25519 VRF over the slot and some on-chain randomness using its
+//! `NimbusId` key, and includes the output and proof in a dedicated pre-runtime digest. This
+//! filter checks the proof against the block author's key and compares the output with a
+//! governance-set threshold. Unlike the pseudo-random subset in `pallet_author_slot_filter`,
+//! nobody (including the previous author) can predict or grind who will be eligible in a slot.
+//!
+//! The randomness is chained through the VRF outputs themselves. Each block's VRF input uses the
+//! VRF output of its parent, or the parent hash if the parent had no VRF digest. See
+//! `nimbus_primitives::vrf` for the details.
+//!
+//! This filter cannot be evaluated through `NimbusApi::can_author`, because the VRF digest only
+//! exists once the collator has signed it. Runtimes using this filter should therefore answer
+//! `NimbusApi::can_author` with the rest of their filter pipeline, and implement `VrfApi` so that
+//! the collator can check its own VRF output against the threshold before authoring.
+//!
+//! The threshold is the probability that any single author is eligible in a given slot. Notice
+//! that slots may have zero or several eligible authors.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use frame_support::pallet;
+
+pub use pallet::*;
+
+#[cfg(any(test, feature = "runtime-benchmarks"))]
+mod benchmarks;
+
+pub mod weights;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+#[pallet]
+pub mod pallet {
+
+	use crate::weights::WeightInfo;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+	use log::debug;
+	use nimbus_primitives::{
+		vrf::{self, VrfOutput, VrfSignature},
+		AccountLookup, CanAuthor, CompatibleDigestItem, NimbusId,
+	};
+	use sp_runtime::Perbill;
+
+	/// The VRF Threshold Filter pallet
+	#[pallet::pallet]
+	pub struct Pallet<T>(PhantomData<T>);
+
+	/// Configuration trait of this pallet.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// The overarching event type
+		type RuntimeEvent: From<Event> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		/// A type to map the NimbusId that signed the VRF to the AccountId being checked.
+		/// This should be the same mapping the author inherent pallet uses.
+		type AccountLookup: AccountLookup<Self::AccountId>;
+		type WeightInfo: WeightInfo;
+	}
+
+	/// The probability that any single author is eligible in a given slot.
+	#[pallet::storage]
+	#[pallet::getter(fn threshold)]
+	pub type Threshold<T: Config> = StorageValue<_, Perbill, ValueQuery, DefaultThreshold<T>>;
+
+	// Default value for the `Threshold`.
+	#[pallet::type_value]
+	pub fn DefaultThreshold<T: Config>() -> Perbill {
+		Perbill::from_percent(50)
+	}
+
+	/// The VRF output of the previous block, if it had one. The randomness for the current block's
+	/// VRF is derived from it.
+	#[pallet::storage]
+	pub type LastVrfOutput<T: Config> = StorageValue<_, VrfOutput, OptionQuery>;
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_: T::BlockNumber) -> Weight {
+			// Account for the VRF check that the author inherent asks for, and for on_finalize
+			T::WeightInfo::check_vrf().saturating_add(T::WeightInfo::on_finalize())
+		}
+
+		fn on_finalize(_: T::BlockNumber) {
+			// Remember this block's output so the next block can derive its randomness from it.
+			// This must mirror what the client sees in the parent header, so we clear the value
+			// when there is no VRF digest.
+			match Self::vrf_signature() {
+				Some(signature) => LastVrfOutput::<T>::put(signature.output),
+				None => LastVrfOutput::<T>::kill(),
+			}
+		}
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Update the threshold. Intended to be called by governance.
+		#[pallet::call_index(0)]
+		#[pallet::weight(T::WeightInfo::set_threshold())]
+		pub fn set_threshold(origin: OriginFor<T>, new: Perbill) -> DispatchResultWithPostInfo {
+			ensure_root(origin)?;
+			Threshold::<T>::put(new);
+			<Pallet<T>>::deposit_event(Event::ThresholdUpdated(new));
+
+			Ok(Default::default())
+		}
+	}
+
+	impl<T: Config> Pallet<T> {
+		/// The VRF signature from the current block's pre-runtime digest, if any.
+		pub fn vrf_signature() -> Option<VrfSignature> {
+			<frame_system::Pallet<T>>::digest()
+				.logs
+				.iter()
+				.find_map(|digest| digest.as_nimbus_vrf_pre_digest())
+		}
+
+		/// Whether the VRF digest of the current block proves that `nimbus_id` is eligible in
+		/// `slot`.
+		pub fn check_vrf(nimbus_id: &NimbusId, slot: u32) -> bool {
+			let signature = match Self::vrf_signature() {
+				Some(signature) => signature,
+				None => {
+					debug!(target: "vrf-filter", "No VRF digest found for {:?}", nimbus_id);
+					return false;
+				}
+			};
+
+			let output = match vrf::verify_vrf(nimbus_id, slot, &Self::vrf_randomness(), &signature)
+			{
+				Some(output) => output,
+				None => {
+					debug!(target: "vrf-filter", "Invalid VRF proof by {:?}", nimbus_id);
+					return false;
+				}
+			};
+
+			vrf::is_below_threshold(output, Threshold::<T>::get())
+		}
+
+		/// The randomness that the current block's VRF must be computed over.
+		pub fn vrf_randomness() -> [u8; 32] {
+			vrf::make_vrf_randomness(
+				LastVrfOutput::<T>::get().as_ref(),
+				<frame_system::Pallet<T>>::parent_hash().as_ref(),
+			)
+		}
+	}
+
+	// This code will be called by the author-inherent pallet to check whether the reported author
+	// of this block is eligible in this slot. We verify the VRF on demand and do not record the
+	// result in storage.
+	impl<T: Config> CanAuthor<T::AccountId> for Pallet<T> {
+		#[cfg(not(feature = "try-runtime"))]
+		fn can_author(author: &T::AccountId, slot: &u32) -> bool {
+			let digest = <frame_system::Pallet<T>>::digest();
+
+			// The VRF must be made with the key that seals the block, which is the one reported
+			// in the nimbus pre-runtime digest.
+			let nimbus_id: NimbusId = match digest
+				.logs
+				.iter()
+				.find_map(|digest| digest.as_nimbus_pre_digest())
+			{
+				Some(id) => id,
+				None => return false,
+			};
+
+			// And that key must belong to the author we are asked about.
+			if T::AccountLookup::lookup_account(&nimbus_id).as_ref() != Some(author) {
+				debug!(target: "vrf-filter", "VRF key does not belong to author {:?}", author);
+				return false;
+			}
+
+			let eligible = Self::check_vrf(&nimbus_id, *slot);
+			debug!(target: "vrf-filter",
+				"Current author, {:?}, is eligible: {}",
+				author,
+				eligible
+			);
+
+			eligible
+		}
+	}
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig {
+		pub threshold: Perbill,
+	}
+
+	#[cfg(feature = "std")]
+	impl Default for GenesisConfig {
+		fn default() -> Self {
+			Self {
+				threshold: Perbill::from_percent(50),
+			}
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			Threshold::<T>::put(self.threshold);
+		}
+	}
+
+	#[pallet::event]
+	#[pallet::generate_deposit(fn deposit_event)]
+	pub enum Event {
+		/// The VRF threshold has been changed.
+		ThresholdUpdated(Perbill),
+	}
+}
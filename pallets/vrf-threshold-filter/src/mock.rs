@@ -0,0 +1,106 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate as pallet_testing;
+use frame_support::parameter_types;
+use frame_support::traits::ConstU32;
+use frame_support::weights::RuntimeDbWeight;
+use nimbus_primitives::{AccountLookup, NimbusId};
+use sp_core::{sr25519, Pair, H256};
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, IdentityLookup},
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		VrfFilter: pallet_testing::{Pallet, Call, Storage, Event},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const TestDbWeight: RuntimeDbWeight = RuntimeDbWeight {
+		read: 1,
+		write: 10,
+	};
+}
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = TestDbWeight;
+	type RuntimeOrigin = RuntimeOrigin;
+	type RuntimeCall = RuntimeCall;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type RuntimeEvent = RuntimeEvent;
+	type BlockHashCount = BlockHashCount;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+	type MaxConsumers = ConstU32<16>;
+}
+
+pub const ALICE: u64 = 1;
+pub const BOB: u64 = 2;
+
+/// The nimbus key of the given account.
+pub fn nimbus_pair(account: u64) -> sr25519::Pair {
+	sr25519::Pair::from_seed(&[account as u8; 32])
+}
+
+pub struct MockAccountLookup;
+impl AccountLookup<u64> for MockAccountLookup {
+	fn lookup_account(nimbus_id: &NimbusId) -> Option<u64> {
+		[ALICE, BOB]
+			.into_iter()
+			.find(|account| NimbusId::from(nimbus_pair(*account).public()) == *nimbus_id)
+	}
+}
+
+impl pallet_testing::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type AccountLookup = MockAccountLookup;
+	type WeightInfo = ();
+}
+
+/// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	frame_system::GenesisConfig::default()
+		.build_storage::<Test>()
+		.unwrap()
+		.into()
+}
@@ -0,0 +1,194 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::*;
+use crate::mock::*;
+
+use frame_support::traits::{OnFinalize, OnInitialize};
+use frame_support::{assert_noop, assert_ok};
+use nimbus_primitives::{
+	vrf::{make_vrf_randomness, make_vrf_sign_data, VrfSignature},
+	CanAuthor, CompatibleDigestItem, NimbusId,
+};
+use sp_core::{crypto::VrfSecret, Pair, H256};
+use sp_runtime::{Digest, DigestItem, DispatchError, Perbill};
+
+/// Start a block whose nimbus pre-runtime digest names the given account's key.
+fn initialize_block(number: u64, author: u64) {
+	System::initialize(
+		&number,
+		&H256::repeat_byte(number as u8),
+		&Digest {
+			logs: vec![DigestItem::nimbus_pre_digest(NimbusId::from(
+				nimbus_pair(author).public(),
+			))],
+		},
+	);
+}
+
+/// Sign the current block's VRF input for the given slot with the given account's key.
+fn sign_vrf(signer: u64, slot: u32) -> VrfSignature {
+	nimbus_pair(signer).vrf_sign(&make_vrf_sign_data(slot, &VrfFilter::vrf_randomness()))
+}
+
+fn deposit_vrf(signature: VrfSignature) {
+	System::deposit_log(DigestItem::nimbus_vrf_pre_digest(signature));
+}
+
+#[test]
+fn set_threshold_works() {
+	new_test_ext().execute_with(|| {
+		let value = Perbill::from_percent(34);
+
+		assert_ok!(VrfFilter::set_threshold(RuntimeOrigin::root(), value));
+		assert_eq!(VrfFilter::threshold(), value);
+	});
+}
+
+#[test]
+fn set_threshold_requires_root() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			VrfFilter::set_threshold(RuntimeOrigin::signed(ALICE), Perbill::zero()),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn author_with_valid_vrf_is_eligible_under_full_threshold() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(VrfFilter::set_threshold(
+			RuntimeOrigin::root(),
+			Perbill::one()
+		));
+		initialize_block(1, ALICE);
+		deposit_vrf(sign_vrf(ALICE, 7));
+
+		assert!(VrfFilter::can_author(&ALICE, &7));
+	});
+}
+
+#[test]
+fn author_with_valid_vrf_is_not_eligible_under_zero_threshold() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(VrfFilter::set_threshold(
+			RuntimeOrigin::root(),
+			Perbill::zero()
+		));
+		initialize_block(1, ALICE);
+		deposit_vrf(sign_vrf(ALICE, 7));
+
+		assert!(!VrfFilter::can_author(&ALICE, &7));
+	});
+}
+
+#[test]
+fn author_without_vrf_digest_is_not_eligible() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(VrfFilter::set_threshold(
+			RuntimeOrigin::root(),
+			Perbill::one()
+		));
+		initialize_block(1, ALICE);
+
+		assert!(!VrfFilter::can_author(&ALICE, &7));
+	});
+}
+
+#[test]
+fn vrf_for_another_slot_is_rejected() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(VrfFilter::set_threshold(
+			RuntimeOrigin::root(),
+			Perbill::one()
+		));
+		initialize_block(1, ALICE);
+		deposit_vrf(sign_vrf(ALICE, 6));
+
+		assert!(!VrfFilter::can_author(&ALICE, &7));
+	});
+}
+
+#[test]
+fn vrf_by_another_key_is_rejected() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(VrfFilter::set_threshold(
+			RuntimeOrigin::root(),
+			Perbill::one()
+		));
+		initialize_block(1, ALICE);
+		deposit_vrf(sign_vrf(BOB, 7));
+
+		assert!(!VrfFilter::can_author(&ALICE, &7));
+	});
+}
+
+#[test]
+fn vrf_is_checked_against_block_author() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(VrfFilter::set_threshold(
+			RuntimeOrigin::root(),
+			Perbill::one()
+		));
+		initialize_block(1, ALICE);
+		deposit_vrf(sign_vrf(ALICE, 7));
+
+		assert!(!VrfFilter::can_author(&BOB, &7));
+	});
+}
+
+#[test]
+fn randomness_is_chained_through_vrf_outputs() {
+	new_test_ext().execute_with(|| {
+		// Without a previous output, the randomness comes from the parent hash.
+		initialize_block(1, ALICE);
+		let parent_hash = H256::repeat_byte(1);
+		assert_eq!(
+			VrfFilter::vrf_randomness(),
+			make_vrf_randomness(None, parent_hash.as_ref())
+		);
+
+		let signature = sign_vrf(ALICE, 7);
+		deposit_vrf(signature.clone());
+		VrfFilter::on_initialize(1);
+		VrfFilter::on_finalize(1);
+		assert_eq!(LastVrfOutput::<Test>::get(), Some(signature.output.clone()));
+
+		// The next block derives its randomness from the output above.
+		initialize_block(2, BOB);
+		assert_eq!(
+			VrfFilter::vrf_randomness(),
+			make_vrf_randomness(Some(&signature.output), &[])
+		);
+
+		// And a block without a VRF digest resets the chain.
+		VrfFilter::on_finalize(2);
+		assert_eq!(LastVrfOutput::<Test>::get(), None);
+	});
+}
+
+#[test]
+fn on_initialize_charges_the_vrf_check_and_on_finalize() {
+	use crate::weights::WeightInfo;
+
+	new_test_ext().execute_with(|| {
+		assert_eq!(
+			VrfFilter::on_initialize(1),
+			<() as WeightInfo>::check_vrf().saturating_add(<() as WeightInfo>::on_finalize())
+		);
+	});
+}
@@ -0,0 +1,95 @@
+// Copyright 2019-2022 PureStake Inc.
+// This file is part of Nimbus.
+
+// Nimbus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Nimbus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Nimbus.  If not, see <http://www.gnu.org/licenses/>.
+
+
+//! Weights for pallet_vrf_threshold_filter
+//!
+//! These are conservative estimates based on the storage accesses of each benchmark and on the
+//! cost of verifying an sr25519 VRF in Wasm, until they are generated by running the
+//! `set_threshold`, `check_vrf` and `on_finalize` benchmarks on reference hardware.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_vrf_threshold_filter.
+pub trait WeightInfo {
+	fn set_threshold() -> Weight;
+	fn check_vrf() -> Weight;
+	fn on_finalize() -> Weight;
+}
+
+/// Weights for pallet_vrf_threshold_filter using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	/// Storage: VrfFilter Threshold (r:0 w:1)
+	/// Proof: VrfFilter Threshold (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
+	fn set_threshold() -> Weight {
+		Weight::from_parts(15_000_000, 0)
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: System Digest (r:1 w:0)
+	/// Proof Skipped: System Digest (max_values: Some(1), max_size: None, mode: Measured)
+	/// Storage: VrfFilter LastVrfOutput (r:1 w:0)
+	/// Proof: VrfFilter LastVrfOutput (max_values: Some(1), max_size: Some(32), added: 527, mode: MaxEncodedLen)
+	/// Storage: VrfFilter Threshold (r:1 w:0)
+	/// Proof: VrfFilter Threshold (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
+	fn check_vrf() -> Weight {
+		Weight::from_parts(1_000_000_000, 0)
+			.saturating_add(T::DbWeight::get().reads(3_u64))
+	}
+	/// Storage: System Digest (r:1 w:0)
+	/// Proof Skipped: System Digest (max_values: Some(1), max_size: None, mode: Measured)
+	/// Storage: VrfFilter LastVrfOutput (r:0 w:1)
+	/// Proof: VrfFilter LastVrfOutput (max_values: Some(1), max_size: Some(32), added: 527, mode: MaxEncodedLen)
+	fn on_finalize() -> Weight {
+		Weight::from_parts(15_000_000, 0)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	/// Storage: VrfFilter Threshold (r:0 w:1)
+	/// Proof: VrfFilter Threshold (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
+	fn set_threshold() -> Weight {
+		Weight::from_parts(15_000_000, 0)
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: System Digest (r:1 w:0)
+	/// Proof Skipped: System Digest (max_values: Some(1), max_size: None, mode: Measured)
+	/// Storage: VrfFilter LastVrfOutput (r:1 w:0)
+	/// Proof: VrfFilter LastVrfOutput (max_values: Some(1), max_size: Some(32), added: 527, mode: MaxEncodedLen)
+	/// Storage: VrfFilter Threshold (r:1 w:0)
+	/// Proof: VrfFilter Threshold (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
+	fn check_vrf() -> Weight {
+		Weight::from_parts(1_000_000_000, 0)
+			.saturating_add(RocksDbWeight::get().reads(3_u64))
+	}
+	/// Storage: System Digest (r:1 w:0)
+	/// Proof Skipped: System Digest (max_values: Some(1), max_size: None, mode: Measured)
+	/// Storage: VrfFilter LastVrfOutput (r:0 w:1)
+	/// Proof: VrfFilter LastVrfOutput (max_values: Some(1), max_size: Some(32), added: 527, mode: MaxEncodedLen)
+	fn on_finalize() -> Weight {
+		Weight::from_parts(15_000_000, 0)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+}
@@ -186,6 +186,7 @@ fn testnet_genesis(
 		potential_author_set: parachain_template_runtime::PotentialAuthorSetConfig {
 			mapping: authorities,
 		},
+		author_rewards: Default::default(),
 		parachain_system: Default::default(),
 	}
 }
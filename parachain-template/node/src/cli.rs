@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use nimbus_consensus::{slashing_protection::Interchange, SlashingProtectionDb};
+use sc_client_api::backend::AuxStore;
+use std::{path::PathBuf, sync::Arc};
 
 /// Sub-commands supported by the collator.
 #[derive(Debug, clap::Subcommand)]
@@ -33,12 +35,72 @@ pub enum Subcommand {
 	/// Run Instant Seal
 	RunInstantSeal(sc_cli::RunCmd),
 
+	/// Import or export the records that keep the nimbus keys from signing conflicting blocks.
+	SlashingProtection(SlashingProtectionCmd),
+
 	/// Sub-commands concerned with benchmarking.
 	/// The pallet benchmarking moved to the `pallet` sub-command.
 	#[command(subcommand)]
 	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
 }
 
+/// The `slashing-protection` command.
+///
+/// Before moving a nimbus key to another node, export the records of the old node and import
+/// them into the new one. The file format is described in `nimbus_consensus::slashing_protection`.
+#[derive(Debug, clap::Parser)]
+pub struct SlashingProtectionCmd {
+	#[command(subcommand)]
+	pub action: SlashingProtectionAction,
+
+	#[allow(missing_docs)]
+	#[command(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SlashingProtectionAction {
+	/// Write the records of every key to a JSON file.
+	Export {
+		/// The file to write
+		path: PathBuf,
+	},
+	/// Merge the records from a JSON file into the node's records.
+	Import {
+		/// The file to read
+		path: PathBuf,
+	},
+}
+
+impl SlashingProtectionCmd {
+	/// Run the command against the records stored in `client`.
+	pub fn run<C: AuxStore>(&self, client: Arc<C>) -> sc_cli::Result<()> {
+		let db = SlashingProtectionDb::new(client, 1);
+		match &self.action {
+			SlashingProtectionAction::Export { path } => {
+				let interchange = db
+					.export()
+					.map_err(|e| sc_cli::Error::Application(Box::new(e)))?;
+				std::fs::write(path, interchange.to_json())?;
+			}
+			SlashingProtectionAction::Import { path } => {
+				let interchange = Interchange::from_json(&std::fs::read_to_string(path)?)
+					.map_err(|e| sc_cli::Error::Application(Box::new(e)))?;
+				db.import(&interchange)
+					.map_err(|e| sc_cli::Error::Application(Box::new(e)))?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl sc_cli::CliConfiguration for SlashingProtectionCmd {
+	fn shared_params(&self) -> &sc_cli::SharedParams {
+		&self.shared_params
+	}
+}
+
 #[derive(Debug, clap::Parser)]
 #[clap(propagate_version = true)]
 #[clap(args_conflicts_with_subcommands = true)]
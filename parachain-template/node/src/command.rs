@@ -244,6 +244,13 @@ pub fn run() -> Result<()> {
 				}),
 			}
 		}
+		Some(Subcommand::SlashingProtection(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| {
+				let partials = new_partial::<RuntimeApi, TemplateRuntimeExecutor>(&config, false)?;
+				cmd.run(partials.client)
+			})
+		}
 		Some(Subcommand::RunInstantSeal(run_cmd)) => {
 			let runner = cli.create_runner(run_cmd)?;
 			runner.run_node_until_exit(|config| async move {
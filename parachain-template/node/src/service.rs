@@ -7,7 +7,8 @@ use std::{sync::Arc, time::Duration};
 use parachain_template_runtime::{opaque::Block, AccountId, Balance, Index as Nonce, RuntimeApi};
 
 use nimbus_consensus::{
-	BuildNimbusConsensusParams, NimbusConsensus, NimbusManualSealConsensusDataProvider,
+	BuildNimbusConsensusParams, NimbusBlockImport, NimbusConsensus,
+	NimbusManualSealConsensusDataProvider, SlashingProtectionDb, SlotForkChoice,
 };
 
 // Cumulus Imports
@@ -70,9 +71,12 @@ pub fn new_partial<RuntimeApi, Executor>(
 		TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<Executor>>,
 		TFullBackend<Block>,
 		sc_consensus::LongestChain<TFullBackend<Block>, Block>,
-		sc_consensus::DefaultImportQueue<
+		nimbus_consensus::NimbusImportQueue<
 			Block,
-			TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<Executor>>,
+			sp_api::TransactionFor<
+				TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<Executor>>,
+				Block,
+			>,
 		>,
 		sc_transaction_pool::FullPool<
 			Block,
@@ -94,7 +98,8 @@ where
 			Block,
 			StateBackend = sc_client_api::StateBackendFor<TFullBackend<Block>, Block>,
 		> + sp_offchain::OffchainWorkerApi<Block>
-		+ sp_block_builder::BlockBuilder<Block>,
+		+ sp_block_builder::BlockBuilder<Block>
+		+ nimbus_primitives::NimbusApi<Block>,
 	sc_client_api::StateBackendFor<TFullBackend<Block>, Block>: sp_api::StateBackend<BlakeTwo256>,
 	Executor: sc_executor::NativeExecutionDispatch + 'static,
 {
@@ -165,6 +170,9 @@ where
 		&task_manager.spawn_essential_handle(),
 		config.prometheus_registry().clone(),
 		parachain,
+		true,
+		nimbus_consensus::SealVerification::parallel(),
+		nimbus_consensus::SlotForkChoice::new(client.clone()),
 	)?;
 
 	let params = PartialComponents {
@@ -239,7 +247,8 @@ where
 		+ sp_block_builder::BlockBuilder<Block>
 		+ cumulus_primitives_core::CollectCollationInfo<Block>
 		+ pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>
-		+ substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Nonce>,
+		+ substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Nonce>
+		+ nimbus_primitives::NimbusApi<Block>,
 	sc_client_api::StateBackendFor<TFullBackend<Block>, Block>: sp_api::StateBackend<BlakeTwo256>,
 	Executor: sc_executor::NativeExecutionDispatch + 'static,
 	RB: Fn(
@@ -267,7 +276,7 @@ where
 {
 	let parachain_config = prepare_node_config(parachain_config);
 
-	let params = new_partial::<RuntimeApi, Executor>(&parachain_config, true)?;
+	let mut params = new_partial::<RuntimeApi, Executor>(&parachain_config, true)?;
 	let (mut telemetry, telemetry_worker_handle) = params.other;
 
 	let client = params.client.clone();
@@ -291,6 +300,7 @@ where
 	let prometheus_registry = parachain_config.prometheus_registry().cloned();
 	let transaction_pool = params.transaction_pool.clone();
 	let import_queue_service = params.import_queue.service();
+	let reputation_changes = params.import_queue.take_reputation_changes();
 
 	let net_config = FullNetworkConfiguration::new(&parachain_config.network);
 
@@ -308,6 +318,14 @@ where
 			net_config,
 		})?;
 
+	if let Some(reputation_changes) = reputation_changes {
+		task_manager.spawn_handle().spawn(
+			"nimbus-peer-reporting",
+			None,
+			nimbus_consensus::report_peers(reputation_changes, network.clone()),
+		);
+	}
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let transaction_pool = transaction_pool.clone();
@@ -474,6 +492,12 @@ pub async fn start_parachain_node(
 					}
 				},
 				additional_digests_provider: (),
+				eligibility_prover: (),
+				authoring_config: Default::default(),
+				authoring_budget_provider: (),
+				key_selector: (),
+				slashing_protection: SlashingProtectionDb::new(client.clone(), 1),
+				prometheus_registry: prometheus_registry.cloned(),
 			}))
 		},
 	)
@@ -486,13 +510,14 @@ pub fn start_instant_seal_node(config: Configuration) -> Result<TaskManager, sc_
 		client,
 		backend,
 		mut task_manager,
-		import_queue,
+		mut import_queue,
 		keystore_container,
 		select_chain,
 		transaction_pool,
 		other: (mut telemetry, _),
 	} = new_partial::<RuntimeApi, TemplateRuntimeExecutor>(&config, false)?;
 
+	let reputation_changes = import_queue.take_reputation_changes();
 	let net_config = FullNetworkConfiguration::new(&config.network);
 
 	let (network, system_rpc_tx, tx_handler_controller, network_starter, sync_service) =
@@ -507,6 +532,14 @@ pub fn start_instant_seal_node(config: Configuration) -> Result<TaskManager, sc_
 			net_config,
 		})?;
 
+	if let Some(reputation_changes) = reputation_changes {
+		task_manager.spawn_handle().spawn(
+			"nimbus-peer-reporting",
+			None,
+			nimbus_consensus::report_peers(reputation_changes, network.clone()),
+		);
+	}
+
 	if config.offchain_worker.enabled {
 		sc_service::build_offchain_workers(
 			&config,
@@ -565,7 +598,8 @@ pub fn start_instant_seal_node(config: Configuration) -> Result<TaskManager, sc_
 		let (_hrmp_xcm_sender, hrmp_xcm_receiver) = flume::bounded::<(ParaId, Vec<u8>)>(100);
 
 		let authorship_future = run_instant_seal(InstantSealParams {
-			block_import: client.clone(),
+			block_import: NimbusBlockImport::new(client.clone(), false)
+				.with_fork_choice(SlotForkChoice::new(client.clone())),
 			env: proposer,
 			client: client.clone(),
 			pool: transaction_pool.clone(),
@@ -574,6 +608,9 @@ pub fn start_instant_seal_node(config: Configuration) -> Result<TaskManager, sc_
 				keystore: keystore_container.keystore(),
 				client: client.clone(),
 				additional_digests_provider: (),
+				eligibility_prover: (),
+				key_selector: (),
+				slashing_protection: (),
 				_phantom: Default::default(),
 			})),
 			create_inherent_data_providers: move |block, _extra_args| {
@@ -36,6 +36,7 @@ use frame_support::{
 		ConstantMultiplier, IdentityFee, Weight, WeightToFeeCoefficient, WeightToFeeCoefficients,
 		WeightToFeePolynomial,
 	},
+	PalletId,
 };
 use frame_system::{
 	limits::{BlockLength, BlockWeights},
@@ -378,7 +379,8 @@ parameter_types! {
 }
 
 impl pallet_transaction_payment::Config for Runtime {
-	type OnChargeTransaction = pallet_transaction_payment::CurrencyAdapter<Balances, ()>;
+	// Block authors get a share of the fees, see `pallet_author_rewards`.
+	type OnChargeTransaction = pallet_transaction_payment::CurrencyAdapter<Balances, AuthorRewards>;
 	type WeightToFee = WeightToFee;
 	type LengthToFee = ConstantMultiplier<Balance, TransactionByteFee>;
 	type FeeMultiplierUpdate = SlowAdjustingFeeUpdate<Self>;
@@ -583,12 +585,59 @@ impl cumulus_pallet_dmp_queue::Config for Runtime {
 	type ExecuteOverweightOrigin = EnsureRoot<AccountId>;
 }
 
+parameter_types! {
+	// Slots follow relay chain blocks, so a round is about an hour.
+	pub const AuthorStatsSlotsPerRound: u32 = 600;
+	pub const AuthorStatsHistoryDepth: u32 = 24;
+	// The author inherent is charged for the worst case of these bounds in every block, as
+	// mandatory weight, so they must stay small.
+	pub const MaxAuthorsPerRound: u32 = 32;
+	pub const MaxEligibleAuthors: u32 = 32;
+	// Slots follow relay chain blocks, and not every relay chain block has a parachain block, so
+	// skipped slots are not missed slots. Missed slot detection is disabled.
+	pub const MaxSkippedSlots: u32 = 0;
+	pub const MissedSlotsThreshold: u32 = 50;
+}
+
 impl pallet_author_inherent::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
 	type AuthorId = AccountId;
 	// We start a new slot each time we see a new relay block.
 	type SlotBeacon = cumulus_pallet_parachain_system::RelaychainDataProvider<Self>;
 	type AccountLookup = PotentialAuthorSet;
 	type CanAuthor = AuthorFilter;
+	type EventHandler = AuthorRewards;
+	type EligibleAuthors = AuthorFilter;
+	type SlotsPerRound = AuthorStatsSlotsPerRound;
+	type HistoryDepth = AuthorStatsHistoryDepth;
+	type MaxAuthorsPerRound = MaxAuthorsPerRound;
+	type MaxEligibleAuthors = MaxEligibleAuthors;
+	type MaxSkippedSlots = MaxSkippedSlots;
+	type MissedSlotsThreshold = MissedSlotsThreshold;
+	// Nothing is reported while missed slot detection is disabled, see `MaxSkippedSlots`.
+	type OnAuthorOffline = ();
+	type MaxBlocksPerSlot = ConstU32<1>;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const RewardPerBlock: Balance = UNIT / 10;
+	pub const AuthorFeeShare: Perbill = Perbill::from_percent(50);
+	pub const RewardPayoutInterval: BlockNumber = 600;
+	pub const MaxRewardPayoutsPerBlock: u32 = 32;
+	pub const AuthorRewardsPalletId: PalletId = PalletId(*b"nmbs/rwd");
+}
+
+impl pallet_author_rewards::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type Currency = Balances;
+	type RewardPerBlock = RewardPerBlock;
+	type FeeShare = AuthorFeeShare;
+	// The fees that are not shared with the author are burned.
+	type FeeRemainder = ();
+	type PayoutInterval = RewardPayoutInterval;
+	type MaxPayoutsPerBlock = MaxRewardPayoutsPerBlock;
+	type PalletId = AuthorRewardsPalletId;
 	type WeightInfo = ();
 }
 
@@ -632,9 +681,10 @@ construct_runtime!(
 		TransactionPayment: pallet_transaction_payment::{Pallet, Storage, Event<T>} = 11,
 
 		// Nimbus support. The order of these are important and shall not change.
-		AuthorInherent: pallet_author_inherent::{Pallet, Call, Storage, Inherent} = 20,
+		AuthorInherent: pallet_author_inherent::{Pallet, Call, Storage, Inherent, Event<T>} = 20,
 		AuthorFilter: pallet_author_slot_filter::{Pallet, Storage, Event, Config} = 21,
 		PotentialAuthorSet: pallet_account_set::{Pallet, Storage, Config<T>} = 22,
+		AuthorRewards: pallet_author_rewards::{Pallet, Storage, Config, Event<T>} = 23,
 
 		// XCM helpers.
 		XcmpQueue: cumulus_pallet_xcmp_queue::{Pallet, Call, Storage, Event<T>} = 30,
@@ -758,6 +808,7 @@ impl_runtime_apis! {
 		}
 	}
 
+	#[api_version(4)]
 	impl nimbus_primitives::NimbusApi<Block> for Runtime {
 		fn can_author(author: NimbusId, slot: u32, parent_header: &<Block as BlockT>::Header) -> bool {
 			// This runtime uses an entropy source that is updated during block initialization
@@ -770,6 +821,49 @@ impl_runtime_apis! {
 			// And now the actual prediction call
 			<AuthorInherent as nimbus_primitives::CanAuthor<_>>::can_author(&author, &slot)
 		}
+
+		fn eligible_authors(slot: u32, parent_header: &<Block as BlockT>::Header) -> Vec<NimbusId> {
+			// Same initialization as in `can_author` so the entropy matches the next block.
+			System::reset_events();
+			System::initialize(&(parent_header.number + 1), &parent_header.hash(), &parent_header.digest);
+			<Self as pallet_author_slot_filter::Config>::RandomnessSource::on_initialize(System::block_number());
+
+			let eligible_accounts =
+				<AuthorFilter as nimbus_primitives::EligibleAuthors<_>>::eligible_authors(&slot);
+			PotentialAuthorSet::nimbus_ids_of(&eligible_accounts)
+		}
+
+		fn can_author_multi(
+			author: nimbus_primitives::MultiNimbusId,
+			slot: u32,
+			parent_header: &<Block as BlockT>::Header,
+		) -> bool {
+			// Same initialization as in `can_author` so the entropy matches the next block.
+			System::reset_events();
+			System::initialize(&(parent_header.number + 1), &parent_header.hash(), &parent_header.digest);
+			<Self as pallet_author_slot_filter::Config>::RandomnessSource::on_initialize(System::block_number());
+
+			<AuthorInherent as nimbus_primitives::CanAuthor<_>>::can_author(&author, &slot)
+		}
+
+		fn checks_pre_digest_slot() -> bool {
+			// The author inherent checks the slot of version 2 pre-runtime digests.
+			true
+		}
+	}
+
+	impl nimbus_primitives::AuthorStatsApi<Block, AccountId> for Runtime {
+		fn current_round() -> u32 {
+			AuthorInherent::current_round()
+		}
+
+		fn author_stats(round: u32, author: AccountId) -> Option<nimbus_primitives::AuthorRoundStats> {
+			AuthorInherent::author_stats(round, &author)
+		}
+
+		fn round_stats(round: u32) -> Vec<(AccountId, nimbus_primitives::AuthorRoundStats)> {
+			AuthorInherent::round_stats(round)
+		}
 	}
 
 	#[cfg(feature = "runtime-benchmarks")]
@@ -19,8 +19,8 @@ pub mod pallet {
 	use frame_support::pallet_prelude::*;
 	#[cfg(feature = "std")]
 	use log::warn;
-	use nimbus_primitives::{AccountLookup, CanAuthor, NimbusId};
-	use sp_std::vec::Vec;
+	use nimbus_primitives::{AccountLookup, CanAuthor, EligibleAuthors, NimbusId};
+	use sp_std::{collections::btree_set::BTreeSet, vec::Vec};
 
 	/// The Account Set pallet
 	#[pallet::pallet]
@@ -83,9 +83,84 @@ pub mod pallet {
 		}
 	}
 
+	/// Every account stored in this pallet is eligible in every slot.
+	impl<T: Config> EligibleAuthors<T::AccountId> for Pallet<T> {
+		fn eligible_authors(_slot: &u32) -> Vec<T::AccountId> {
+			StoredAccounts::<T>::get()
+		}
+	}
+
 	impl<T: Config> AccountLookup<T::AccountId> for Pallet<T> {
 		fn lookup_account(author: &NimbusId) -> Option<T::AccountId> {
 			Mapping::<T>::get(&author)
 		}
 	}
+
+	impl<T: Config> Pallet<T> {
+		/// The reverse of the account lookup. Returns every NimbusId that is mapped to one of the
+		/// given accounts.
+		///
+		/// The mapping is keyed by NimbusId, so this reads every one of its entries: one storage
+		/// read per author mapped at genesis, whatever the number of accounts asked for. It is
+		/// only intended to be used from runtime APIs, not during block execution.
+		pub fn nimbus_ids_of(accounts: &[T::AccountId]) -> Vec<NimbusId> {
+			let accounts: BTreeSet<_> = accounts.iter().collect();
+			Mapping::<T>::iter()
+				.filter(|(_, account)| accounts.contains(account))
+				.map(|(nimbus_id, _)| nimbus_id)
+				.collect()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::pallet::*;
+	use crate::{AccountId, Runtime};
+	use frame_support::traits::GenesisBuild;
+	use nimbus_primitives::{CanAuthor, EligibleAuthors, NimbusId};
+	use sp_core::crypto::UncheckedFrom;
+
+	fn account(seed: u8) -> AccountId {
+		AccountId::new([seed; 32])
+	}
+
+	fn nimbus_id(seed: u8) -> NimbusId {
+		NimbusId::unchecked_from([seed; 32])
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		GenesisConfig::<Runtime> {
+			mapping: vec![(account(1), nimbus_id(1)), (account(2), nimbus_id(2))],
+		}
+		.build_storage()
+		.unwrap()
+		.into()
+	}
+
+	#[test]
+	fn eligible_authors_agrees_with_can_author() {
+		new_test_ext().execute_with(|| {
+			for slot in 0..5 {
+				let eligible = Pallet::<Runtime>::eligible_authors(&slot);
+				for author in [account(1), account(2), account(3)] {
+					assert_eq!(
+						eligible.contains(&author),
+						Pallet::<Runtime>::can_author(&author, &slot)
+					);
+				}
+			}
+		});
+	}
+
+	#[test]
+	fn nimbus_ids_of_only_returns_the_given_accounts() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(
+				Pallet::<Runtime>::nimbus_ids_of(&[account(2), account(3)]),
+				vec![nimbus_id(2)]
+			);
+			assert!(Pallet::<Runtime>::nimbus_ids_of(&[]).is_empty());
+		});
+	}
 }